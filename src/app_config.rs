@@ -1,6 +1,9 @@
 use crate::domain::GeoLocation;
+use crate::history::HistoryRetention;
 use config::Config;
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
 #[derive(Debug, Deserialize)]
@@ -8,7 +11,12 @@ pub struct AppConfig {
     core: Core,
     flows: Flows,
     hue: Hue,
+    history: Option<History>, // The history is not recorded if absent
     location: GeoLocation,
+    #[serde(default)]
+    tags: HashMap<String, Vec<String>>, // Device ids by tag
+    #[serde(default)]
+    calendars: HashMap<String, PathBuf>, // Calendar files by calendar name
 }
 
 impl AppConfig {
@@ -35,20 +43,50 @@ impl AppConfig {
         &self.hue
     }
 
+    pub fn history(&self) -> Option<&History> {
+        self.history.as_ref()
+    }
+
     pub fn geo_location(&self) -> &GeoLocation {
         &self.location
     }
+
+    pub fn calendars(&self) -> &HashMap<String, PathBuf> {
+        &self.calendars
+    }
+
+    /// Returns the tags of a device, sorted by name.
+    pub fn tags_of(&self, device_id: &str) -> Vec<String> {
+        let mut tags: Vec<String> = self
+            .tags
+            .iter()
+            .filter(|(_, device_ids)| device_ids.iter().any(|id| id == device_id))
+            .map(|(tag, _)| tag.clone())
+            .collect();
+        tags.sort();
+        tags
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Core {
     store_buffer_size: usize,
+    store_file: Option<PathBuf>, // The store is not persisted if absent
+    store_persist_interval_ms: u64,
 }
 
 impl Core {
     pub fn store_buffer_size(&self) -> usize {
         self.store_buffer_size
     }
+
+    pub fn store_file(&self) -> Option<&Path> {
+        self.store_file.as_deref()
+    }
+
+    pub fn store_persist_interval_ms(&self) -> Duration {
+        Duration::from_millis(self.store_persist_interval_ms)
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -93,6 +131,28 @@ impl Hue {
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct History {
+    file: PathBuf,
+    retention_days: u64,
+    downsample_after_days: u64,
+    downsample_interval_minutes: u64,
+}
+
+impl History {
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn retention(&self) -> HistoryRetention {
+        HistoryRetention {
+            retention: Duration::from_secs(self.retention_days * 24 * 3600),
+            downsample_after: Duration::from_secs(self.downsample_after_days * 24 * 3600),
+            downsample_interval: Duration::from_secs(self.downsample_interval_minutes * 60),
+        }
+    }
+}
+
 #[cfg(test)]
 pub struct AppConfigBuilder {
     config: AppConfig,
@@ -103,7 +163,11 @@ impl AppConfigBuilder {
     pub fn new() -> Self {
         AppConfigBuilder {
             config: AppConfig {
-                core: Core { store_buffer_size: 1 },
+                core: Core {
+                    store_buffer_size: 1,
+                    store_file: None,
+                    store_persist_interval_ms: 60_000,
+                },
                 flows: Flows { directory: "flows".to_string() },
                 hue: Hue {
                     url: "https://hue.url/".to_string(),
@@ -112,11 +176,14 @@ impl AppConfigBuilder {
                     stale_connection_timeout_ms: 30_000,
                     application_key: "key".to_string(),
                 },
+                history: None,
                 location: GeoLocation {
                     latitude: 51.8615899,
                     longitude: 4.3580323,
                     altitude: 0.0,
                 },
+                tags: HashMap::new(),
+                calendars: HashMap::new(),
             },
         }
     }
@@ -126,7 +193,30 @@ impl AppConfigBuilder {
         self
     }
 
+    pub fn tag(mut self, tag: &str, device_ids: &[&str]) -> Self {
+        self.config.tags.insert(tag.to_string(), device_ids.iter().map(|id| id.to_string()).collect());
+        self
+    }
+
     pub fn build(self) -> AppConfig {
         self.config
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn returns_the_sorted_tags_of_a_device() {
+        let config = AppConfigBuilder::new()
+            .tag("lamps", &["hallway", "kitchen"])
+            .tag("downstairs", &["hallway"])
+            .tag("upstairs", &["bedroom"])
+            .build();
+
+        assert_eq!(config.tags_of("hallway"), vec!["downstairs".to_string(), "lamps".to_string()]);
+        assert_eq!(config.tags_of("garage"), Vec::<String>::new());
+    }
+}
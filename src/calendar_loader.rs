@@ -0,0 +1,238 @@
+use crate::domain::{Calendar, Calendars, Date, InvalidDateError};
+use chrono::{Datelike, NaiveDate};
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::fs;
+use tracing::{info, instrument, warn};
+
+/// Loads the calendars by name from their files, a calendar that fails to load is left out.
+#[instrument(skip_all)]
+pub async fn load_calendars(files: &HashMap<String, PathBuf>) -> Calendars {
+    let mut calendars = Calendars::new();
+    for (name, path) in files {
+        match load_calendar(path).await {
+            Ok(calendar) => {
+                calendars.insert(name.clone(), calendar);
+            }
+            Err(err) => warn!("⚠️ Unable to load calendar '{}' from {}: {}", name, path.display(), err),
+        }
+    }
+
+    info!("📅 Loaded {} of {} calendar(s)", calendars.len(), files.len());
+    calendars
+}
+
+/// Loads an iCalendar file if its extension is `ics`, or else a file with a date per line.
+async fn load_calendar(path: &Path) -> Result<Calendar, CalendarError> {
+    let content = fs::read_to_string(path).await?;
+    let days = if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("ics")) {
+        parse_icalendar(&content)?
+    } else {
+        parse_date_list(&content)?
+    };
+    Ok(Calendar::new(days))
+}
+
+/// Parses a date per line, in YYYY-MM-DD format or MM-DD for a date that recurs every year. Empty lines and lines that
+/// start with `#` are skipped.
+fn parse_date_list(content: &str) -> Result<Vec<Date>, CalendarError> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(line, text)| text.parse().map_err(|err: InvalidDateError| CalendarError::InvalidLine { line, message: err.to_string() }))
+        .collect()
+}
+
+/// Parses the days of the events in an iCalendar file. An event covers the days from its start up to its end, and
+/// recurs every year if it has a yearly recurrence rule. Other recurrence rules are not supported, those events only
+/// cover their first occurrence.
+fn parse_icalendar(content: &str) -> Result<Vec<Date>, CalendarError> {
+    let mut days = Vec::new();
+    let mut event: Option<Event> = None;
+
+    for (line, name, value) in unfold(content) {
+        match (name.as_str(), &mut event) {
+            ("BEGIN", _) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(Event {
+                    line,
+                    start: None,
+                    end: None,
+                    rule: None,
+                })
+            }
+            ("DTSTART", Some(event)) => event.start = Some(parse_icalendar_date(line, &value)?),
+            ("DTEND", Some(event)) => event.end = Some(parse_icalendar_date(line, &value)?),
+            ("RRULE", Some(event)) => event.rule = Some(value),
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                if let Some(event) = event.take() {
+                    days.extend(event.days()?);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(days)
+}
+
+struct Event {
+    line: usize, // Of its BEGIN
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>, // Exclusive
+    rule: Option<String>,
+}
+
+impl Event {
+    fn days(self) -> Result<Vec<Date>, CalendarError> {
+        let Some(start) = self.start else {
+            return Err(CalendarError::InvalidLine {
+                line: self.line,
+                message: "event without a DTSTART".to_string(),
+            });
+        };
+
+        let yearly = match self.rule {
+            Some(rule) if rule.split(';').any(|part| part.eq_ignore_ascii_case("FREQ=YEARLY")) => true,
+            Some(rule) => {
+                warn!(
+                    "⚠️ Unsupported recurrence rule '{}' of the event at line {}, only its first occurrence is included",
+                    rule, self.line
+                );
+                false
+            }
+            None => false,
+        };
+
+        let end = self.end.filter(|end| *end > start).unwrap_or(start.succ_opt().unwrap_or(start));
+        Ok(start
+            .iter_days()
+            .take_while(|day| *day < end)
+            .map(|day| Date {
+                year: if yearly { None } else { Some(day.year()) },
+                ..Date::from(day)
+            })
+            .collect())
+    }
+}
+
+/// Parses the date of a DATE or DATE-TIME value like `20261225` or `20261225T200000Z`, the time is ignored.
+fn parse_icalendar_date(line: usize, value: &str) -> Result<NaiveDate, CalendarError> {
+    value
+        .get(..8)
+        .and_then(|date| NaiveDate::parse_from_str(date, "%Y%m%d").ok())
+        .ok_or_else(|| CalendarError::InvalidLine {
+            line,
+            message: format!("invalid date '{}'", value),
+        })
+}
+
+/// Joins folded lines and splits each content line into its line number, upper case name without parameters, and value.
+fn unfold(content: &str) -> Vec<(usize, String, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some((_, previous))) => previous.push_str(continuation),
+            _ => lines.push((index + 1, line.to_string())),
+        }
+    }
+
+    lines
+        .into_iter()
+        .filter_map(|(line, text)| {
+            let (name, value) = text.split_once(':')?;
+            let name = name.split(';').next().unwrap_or(name).to_ascii_uppercase();
+            Some((line, name, value.trim().to_string()))
+        })
+        .collect()
+}
+
+#[derive(Error, Debug)]
+pub enum CalendarError {
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("invalid line {line}, {message}")]
+    InvalidLine { line: usize, message: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn date(year: Option<i32>, month: u8, day: u8) -> Date {
+        Date { year, month, day }
+    }
+
+    fn resource(file_name: &str) -> PathBuf {
+        PathBuf::from(format!("{}/tests/resources/calendars/{}", env!("CARGO_MANIFEST_DIR"), file_name))
+    }
+
+    #[test]
+    fn parses_a_date_list() {
+        let days = parse_date_list(include_str!("../tests/resources/calendars/vacation.txt")).unwrap();
+
+        assert_eq!(days, vec![date(Some(2026), 7, 20), date(Some(2026), 7, 21), date(None, 12, 24)]);
+    }
+
+    #[test]
+    fn fails_on_an_invalid_date_in_a_date_list() {
+        let result = parse_date_list("2026-07-20\n\n2026-13-01");
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "invalid line 3, invalid date '2026-13-01', expected YYYY-MM-DD or MM-DD for a date that recurs every year"
+        );
+    }
+
+    #[test]
+    fn parses_the_days_of_icalendar_events() {
+        let days = parse_icalendar(include_str!("../tests/resources/calendars/holidays.ics")).unwrap();
+
+        assert_eq!(
+            days,
+            vec![
+                date(None, 1, 1),
+                date(Some(2026), 4, 27),
+                date(Some(2026), 12, 25),
+                date(Some(2026), 12, 26),
+                date(Some(2026), 12, 31),
+            ]
+        );
+    }
+
+    #[test]
+    fn fails_on_an_invalid_icalendar_date() {
+        let result = parse_icalendar("BEGIN:VEVENT\nDTSTART;VALUE=DATE:2026-12-25\nEND:VEVENT");
+
+        assert_eq!(result.unwrap_err().to_string(), "invalid line 2, invalid date '2026-12-25'");
+    }
+
+    #[test]
+    fn fails_on_an_event_without_a_start() {
+        let result = parse_icalendar("BEGIN:VCALENDAR\nBEGIN:VEVENT\nSUMMARY:Someday\nEND:VEVENT\nEND:VCALENDAR");
+
+        assert_eq!(result.unwrap_err().to_string(), "invalid line 2, event without a DTSTART");
+    }
+
+    #[tokio::test]
+    async fn load_calendars_leaves_out_calendars_that_fail_to_load() {
+        let files = HashMap::from([
+            ("holidays".to_string(), resource("holidays.ics")),
+            ("vacation".to_string(), resource("vacation.txt")),
+            ("missing".to_string(), resource("missing.txt")),
+        ]);
+
+        let calendars = load_calendars(&files).await;
+
+        let christmas = NaiveDate::from_ymd_opt(2026, 12, 25).unwrap();
+        let mut names = calendars.keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, vec!["holidays", "vacation"]);
+        assert!(calendars["holidays"].includes(christmas));
+        assert!(!calendars["vacation"].includes(christmas));
+    }
+}
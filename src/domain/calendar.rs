@@ -0,0 +1,22 @@
+use crate::domain::Date;
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+/// A set of days like public holidays or vacation days, its dates recur every year if they have no year.
+#[derive(PartialEq, Debug, Default, Clone)]
+pub struct Calendar {
+    days: Vec<Date>,
+}
+
+impl Calendar {
+    pub fn new(days: Vec<Date>) -> Self {
+        Calendar { days }
+    }
+
+    pub fn includes(&self, date: NaiveDate) -> bool {
+        self.days.iter().any(|day| day.falls_on(date))
+    }
+}
+
+/// Calendars by name.
+pub type Calendars = HashMap<String, Calendar>;
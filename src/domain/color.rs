@@ -1,5 +1,7 @@
 use crate::domain::color::Color::{CIE_xyY, Hex, RGB};
 use crate::domain::property::CartesianCoordinate;
+use ordered_float::OrderedFloat;
+use std::hash::{Hash, Hasher};
 use thiserror::Error;
 
 #[derive(PartialEq, Clone, Debug)]
@@ -50,8 +52,61 @@ impl Color {
             CIE_xyY { .. } => Ok(self),
         }
     }
+
+    /// Returns the distance between the chromaticities of both colors in the CIE xy space, their brightness is ignored.
+    /// Colors a light can show are at most about 0.6 apart, colors closer than about 0.01 look alike.
+    pub fn distance(&self, other: &Color) -> Result<f64, ColorConversionError> {
+        let (a, b) = (self.chromaticity()?, other.chromaticity()?);
+        Ok(((a.x() - b.x()).powi(2) + (a.y() - b.y()).powi(2)).sqrt())
+    }
+
+    /// Returns the hue in degrees (0-360) and the saturation (0-1) of the color, its brightness is ignored.
+    pub fn hue_saturation(&self) -> Result<(f64, f64), ColorConversionError> {
+        let (r, g, b) = match self {
+            RGB(r, g, b) => (*r as f64 / 255.0, *g as f64 / 255.0, *b as f64 / 255.0),
+            Hex(value) => {
+                let (r, g, b) = hex_to_rgb(value)?;
+                (r as f64 / 255.0, g as f64 / 255.0, b as f64 / 255.0)
+            }
+            CIE_xyY { xy, .. } => chromaticity_to_rgb(xy),
+        };
+
+        let max = r.max(g).max(b);
+        let delta = max - r.min(g).min(b);
+        let saturation = if max == 0.0 { 0.0 } else { delta / max };
+        let hue = if delta == 0.0 {
+            0.0
+        } else if max == r {
+            60.0 * ((g - b) / delta).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / delta + 2.0)
+        } else {
+            60.0 * ((r - g) / delta + 4.0)
+        };
+        Ok((hue, saturation))
+    }
+
+    fn chromaticity(&self) -> Result<CartesianCoordinate, ColorConversionError> {
+        match self.clone().to_cie_xyY()? {
+            CIE_xyY { xy, .. } => Ok(xy),
+            _ => unreachable!("to_cie_xyY always returns a CIE_xyY color"),
+        }
+    }
 }
 
+impl Hash for Color {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            RGB(r, g, b) => (r, g, b).hash(state),
+            Hex(value) => value.hash(state),
+            CIE_xyY { xy, brightness } => (OrderedFloat(xy.x()), OrderedFloat(xy.y()), OrderedFloat(*brightness)).hash(state),
+        }
+    }
+}
+
+impl Eq for Color {}
+
 #[derive(Error, Debug)]
 pub enum ColorConversionError {
     #[error("invalid hexadecimal value '{0}'")]
@@ -119,6 +174,25 @@ fn xyY_to_rgb(xy: &CartesianCoordinate, brightness: f64) -> (u8, u8, u8) {
     ((r * 255.0).round() as u8, (g * 255.0).round() as u8, (b * 255.0).round() as u8)
 }
 
+/// Converts a chromaticity to gamma-corrected sRGB (0-1), at the highest brightness that keeps all channels in range.
+fn chromaticity_to_rgb(xy: &CartesianCoordinate) -> (f64, f64, f64) {
+    if xy.y() <= 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+
+    let x = xy.x() / xy.y();
+    let z = (1.0 - xy.x() - xy.y()) / xy.y();
+    let r = (x * 3.2406 - 1.5372 + z * -0.4986).max(0.0);
+    let g = (x * -0.9689 + 1.8758 + z * 0.0415).max(0.0);
+    let b = (x * 0.0557 - 0.2040 + z * 1.0570).max(0.0);
+
+    let max = r.max(g).max(b);
+    if max == 0.0 {
+        return (0.0, 0.0, 0.0);
+    }
+    (gamma_correct_rev(r / max), gamma_correct_rev(g / max), gamma_correct_rev(b / max))
+}
+
 fn gamma_correct_rev(channel: f64) -> f64 {
     if channel <= 0.0031308 {
         channel * 12.92
@@ -231,4 +305,51 @@ mod tests {
             );
         }
     }
+
+    mod hue_saturation {
+        use super::*;
+        use rstest::rstest;
+
+        #[rstest]
+        #[case(RGB(255, 0, 0), 0.0, 1.0)]
+        #[case(RGB(0, 255, 0), 120.0, 1.0)]
+        #[case(Hex("#0000ff".to_string()), 240.0, 1.0)]
+        #[case(RGB(255, 0, 255), 300.0, 1.0)]
+        #[case(RGB(255, 128, 128), 0.0, 0.4980392156862745)]
+        #[case(RGB(128, 128, 128), 0.0, 0.0)]
+        fn from_rgb(#[case] color: Color, #[case] hue: f64, #[case] saturation: f64) {
+            assert_eq!(color.hue_saturation().unwrap(), (hue, saturation));
+        }
+
+        #[test]
+        fn from_cie_xyy_ignores_the_brightness() {
+            let (hue, saturation) = CIE_xyY {
+                xy: CartesianCoordinate::new(0.32092016238159676, 0.15415426251691475),
+                brightness: 0.01,
+            }
+            .hue_saturation()
+            .unwrap();
+
+            assert!((hue - 300.0).abs() < 1.0, "hue {}", hue);
+            assert!(saturation > 0.99, "saturation {}", saturation);
+        }
+    }
+
+    mod distance {
+        use super::*;
+
+        #[test]
+        fn is_zero_for_the_same_chromaticity() {
+            let distance = RGB(255, 0, 255).distance(&Hex("#7f007f".to_string())).unwrap();
+
+            assert!(distance < 0.001, "distance {}", distance);
+        }
+
+        #[test]
+        fn is_large_for_different_colors() {
+            let distance = RGB(255, 0, 0).distance(&RGB(0, 0, 255)).unwrap();
+
+            assert!((distance - 0.56).abs() < 0.01, "distance {}", distance);
+        }
+    }
 }
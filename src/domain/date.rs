@@ -0,0 +1,70 @@
+use chrono::{Datelike, NaiveDate};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// A calendar date, a date without a year recurs every year.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Date {
+    pub year: Option<i32>,
+    pub month: u8,
+    pub day: u8,
+}
+
+impl Date {
+    /// Returns whether this date falls on the given day, a recurring date falls on it in any year.
+    pub fn falls_on(&self, date: NaiveDate) -> bool {
+        self.year.is_none_or(|year| year == date.year()) && self.month as u32 == date.month() && self.day as u32 == date.day()
+    }
+}
+
+impl From<NaiveDate> for Date {
+    fn from(date: NaiveDate) -> Self {
+        Date {
+            year: Some(date.year()),
+            month: date.month() as u8,
+            day: date.day() as u8,
+        }
+    }
+}
+
+impl FromStr for Date {
+    type Err = InvalidDateError;
+
+    /// Parses a date in YYYY-MM-DD format, or in MM-DD format for a date that recurs every year.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidDateError(text.to_string());
+
+        let parts: Vec<&str> = text.split('-').collect();
+        let (year, month, day) = match parts.as_slice() {
+            [year, month, day] if year.len() == 4 => (Some(year.parse::<i32>().map_err(|_| invalid())?), *month, *day),
+            [month, day] => (None, *month, *day),
+            _ => return Err(invalid()),
+        };
+        if month.len() != 2 || day.len() != 2 {
+            return Err(invalid());
+        }
+        let month: u8 = month.parse().map_err(|_| invalid())?;
+        let day: u8 = day.parse().map_err(|_| invalid())?;
+
+        // A recurring date is validated against a leap year, so it can be the 29th of February
+        if NaiveDate::from_ymd_opt(year.unwrap_or(2000), month as u32, day as u32).is_none() {
+            return Err(invalid());
+        }
+
+        Ok(Date { year, month, day })
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.year {
+            Some(year) => write!(f, "{:04}-{:02}-{:02}", year, self.month, self.day),
+            None => write!(f, "{:02}-{:02}", self.month, self.day),
+        }
+    }
+}
+
+#[derive(Error, PartialEq, Debug)]
+#[error("invalid date '{0}', expected YYYY-MM-DD or MM-DD for a date that recurs every year")]
+pub struct InvalidDateError(String);
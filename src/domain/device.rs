@@ -1,4 +1,5 @@
 use crate::domain::property::{Property, PropertyType};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 #[derive(Clone, PartialEq, Debug)]
@@ -13,6 +14,8 @@ pub struct Device {
     pub external_id: Option<String>,
     pub address: Option<String>,
     pub controller_id: Option<&'static str>,
+    pub room: Option<String>,
+    pub tags: Vec<String>,
 }
 
 impl Device {
@@ -28,7 +31,7 @@ impl Device {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub enum DeviceType {
     Light,
 }
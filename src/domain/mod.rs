@@ -1,7 +1,9 @@
+mod calendar;
 pub mod color;
 pub mod commands;
 pub mod controller;
 pub mod controller_registry;
+mod date;
 pub mod device;
 pub mod events;
 mod geo_location;
@@ -11,8 +13,10 @@ mod time;
 mod weekday;
 mod weekday_condition;
 
+pub use calendar::{Calendar, Calendars};
+pub use date::{Date, InvalidDateError};
 pub use geo_location::GeoLocation;
-pub use number::Number;
+pub use number::{Number, NumberError};
 pub use time::Time;
 pub use weekday::Weekday;
 pub use weekday_condition::WeekdayCondition;
@@ -2,7 +2,8 @@ use ordered_float::OrderedFloat;
 use std::cmp::Ordering;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
-use std::ops::{Add, Sub};
+use std::ops::{Add, Div, Mul, Rem, Sub};
+use thiserror::Error;
 
 #[derive(Copy, Clone, Debug)]
 pub enum Number {
@@ -41,6 +42,47 @@ impl Number {
             Number::Float(n) => Some(n.clone()),
         }
     }
+
+    pub fn abs(self) -> Number {
+        match self {
+            Number::PositiveInt(n) => Number::PositiveInt(n),
+            Number::NegativeInt(n) => Number::PositiveInt(n.unsigned_abs()),
+            Number::Float(n) => Number::Float(n.abs()),
+        }
+    }
+
+    /// Rounds half-way cases away from zero, a rounded Float becomes an integer unless it is not finite.
+    pub fn round(self) -> Number {
+        match self {
+            Number::Float(n) if n.is_finite() => Number::from_i128(n.round() as i128),
+            number => number,
+        }
+    }
+
+    fn as_i128(&self) -> Option<i128> {
+        match self {
+            Number::PositiveInt(n) => Some(*n as i128),
+            Number::NegativeInt(n) => Some(*n as i128),
+            Number::Float(_) => None,
+        }
+    }
+
+    fn float_value(&self) -> f64 {
+        match self {
+            Number::PositiveInt(n) => *n as f64,
+            Number::NegativeInt(n) => *n as f64,
+            Number::Float(n) => *n,
+        }
+    }
+
+    /// Saturates at the bounds of the integer types.
+    fn from_i128(value: i128) -> Number {
+        if value >= 0 {
+            Number::PositiveInt(value.min(u64::MAX as i128) as u64)
+        } else {
+            Number::NegativeInt(value.max(i64::MIN as i128) as i64)
+        }
+    }
 }
 
 impl Add for Number {
@@ -100,6 +142,48 @@ impl Sub for Number {
     }
 }
 
+impl Mul for Number {
+    type Output = Number;
+
+    fn mul(self, rhs: Number) -> Number {
+        match (self.as_i128(), rhs.as_i128()) {
+            (Some(a), Some(b)) => Number::from_i128(a.saturating_mul(b)),
+            _ => Number::Float(self.float_value() * rhs.float_value()),
+        }
+    }
+}
+
+impl Div for Number {
+    type Output = Result<Number, NumberError>;
+
+    /// Dividing integers results in an integer if there is no remainder, and in a Float otherwise.
+    fn div(self, rhs: Number) -> Self::Output {
+        if rhs.float_value() == 0.0 {
+            return Err(NumberError::DivisionByZero);
+        }
+
+        match (self.as_i128(), rhs.as_i128()) {
+            (Some(a), Some(b)) if a % b == 0 => Ok(Number::from_i128(a / b)),
+            _ => Ok(Number::Float(self.float_value() / rhs.float_value())),
+        }
+    }
+}
+
+impl Rem for Number {
+    type Output = Result<Number, NumberError>;
+
+    fn rem(self, rhs: Number) -> Self::Output {
+        if rhs.float_value() == 0.0 {
+            return Err(NumberError::DivisionByZero);
+        }
+
+        match (self.as_i128(), rhs.as_i128()) {
+            (Some(a), Some(b)) => Ok(Number::from_i128(a % b)),
+            _ => Ok(Number::Float(self.float_value() % rhs.float_value())),
+        }
+    }
+}
+
 impl PartialOrd for Number {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (self, other) {
@@ -156,6 +240,12 @@ impl Display for Number {
     }
 }
 
+#[derive(Error, PartialEq, Debug)]
+pub enum NumberError {
+    #[error("division by zero")]
+    DivisionByZero,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -202,6 +292,59 @@ mod tests {
         assert_eq!(a - b, expected);
     }
 
+    #[rstest]
+    #[case(Number::PositiveInt(3), Number::PositiveInt(2), Number::PositiveInt(6))]
+    #[case(Number::PositiveInt(3), Number::NegativeInt(-2), Number::NegativeInt(-6))]
+    #[case(Number::NegativeInt(-3), Number::NegativeInt(-2), Number::PositiveInt(6))]
+    #[case(Number::Float(1.5), Number::PositiveInt(2), Number::Float(3.0))]
+    #[case(Number::NegativeInt(-2), Number::Float(1.25), Number::Float(-2.5))]
+    // Overflows
+    #[case(Number::PositiveInt(u64::MAX), Number::PositiveInt(2), Number::PositiveInt(u64::MAX))]
+    #[case(Number::PositiveInt(u64::MAX), Number::NegativeInt(-2), Number::NegativeInt(i64::MIN))]
+    fn multiply(#[case] a: Number, #[case] b: Number, #[case] expected: Number) {
+        assert_eq!(a * b, expected);
+    }
+
+    #[rstest]
+    #[case(Number::PositiveInt(6), Number::PositiveInt(2), Ok(Number::PositiveInt(3)))]
+    #[case(Number::PositiveInt(7), Number::PositiveInt(2), Ok(Number::Float(3.5)))]
+    #[case(Number::PositiveInt(6), Number::NegativeInt(-2), Ok(Number::NegativeInt(-3)))]
+    #[case(Number::Float(7.5), Number::PositiveInt(3), Ok(Number::Float(2.5)))]
+    #[case(Number::PositiveInt(1), Number::PositiveInt(0), Err(NumberError::DivisionByZero))]
+    #[case(Number::PositiveInt(1), Number::NegativeInt(0), Err(NumberError::DivisionByZero))]
+    #[case(Number::Float(1.0), Number::Float(-0.0), Err(NumberError::DivisionByZero))]
+    fn divide(#[case] a: Number, #[case] b: Number, #[case] expected: Result<Number, NumberError>) {
+        assert_eq!(a / b, expected);
+    }
+
+    #[rstest]
+    #[case(Number::PositiveInt(7), Number::PositiveInt(3), Ok(Number::PositiveInt(1)))]
+    #[case(Number::NegativeInt(-7), Number::PositiveInt(3), Ok(Number::NegativeInt(-1)))]
+    #[case(Number::Float(7.5), Number::PositiveInt(2), Ok(Number::Float(1.5)))]
+    #[case(Number::PositiveInt(7), Number::Float(0.0), Err(NumberError::DivisionByZero))]
+    fn remainder(#[case] a: Number, #[case] b: Number, #[case] expected: Result<Number, NumberError>) {
+        assert_eq!(a % b, expected);
+    }
+
+    #[rstest]
+    #[case(Number::PositiveInt(42), Number::PositiveInt(42))]
+    #[case(Number::NegativeInt(-42), Number::PositiveInt(42))]
+    #[case(Number::NegativeInt(i64::MIN), Number::PositiveInt(i64::MIN.unsigned_abs()))]
+    #[case(Number::Float(-4.2), Number::Float(4.2))]
+    fn abs(#[case] number: Number, #[case] expected: Number) {
+        assert_eq!(number.abs(), expected);
+    }
+
+    #[rstest]
+    #[case(Number::Float(4.5), Number::PositiveInt(5))]
+    #[case(Number::Float(-4.5), Number::NegativeInt(-5))]
+    #[case(Number::Float(4.49), Number::PositiveInt(4))]
+    #[case(Number::NegativeInt(-4), Number::NegativeInt(-4))]
+    fn round(#[case] number: Number, #[case] expected: Number) {
+        assert!(matches!(number.round(), Number::PositiveInt(_) | Number::NegativeInt(_)));
+        assert_eq!(number.round(), expected);
+    }
+
     #[rstest]
     #[case(Number::PositiveInt(42), Number::PositiveInt(42))]
     #[case(Number::PositiveInt(42), Number::NegativeInt(42))]
@@ -1,7 +1,9 @@
 use crate::domain::property::{Property, PropertyError, PropertyType};
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct BooleanProperty {
     name: String,
     property_type: PropertyType,
@@ -1,7 +1,9 @@
 use crate::domain::property::{Property, PropertyError, PropertyType};
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct ColorProperty {
     name: String,
     property_type: PropertyType,
@@ -36,6 +38,10 @@ impl ColorProperty {
         Ok(())
     }
 
+    pub fn xy(&self) -> &CartesianCoordinate {
+        &self.xy
+    }
+
     pub fn gamut(&self) -> Option<&Gamut> {
         self.gamut.as_ref()
     }
@@ -79,7 +85,7 @@ impl Property for ColorProperty {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct CartesianCoordinate {
     x: f64,
     y: f64,
@@ -99,7 +105,7 @@ impl CartesianCoordinate {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub struct Gamut {
     red: CartesianCoordinate,
     green: CartesianCoordinate,
@@ -1,9 +1,11 @@
 use crate::domain::Number;
 use crate::domain::property::{Property, PropertyError, PropertyType};
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::fmt::Debug;
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
 pub struct NumberProperty {
     name: String,
     property_type: PropertyType,
@@ -200,7 +202,7 @@ impl NumberPropertyBuilder {
     }
 }
 
-#[derive(Clone, PartialEq, Debug)]
+#[derive(Clone, PartialEq, Serialize, Deserialize, Debug)]
 pub enum Unit {
     Percentage,
     #[allow(dead_code)]
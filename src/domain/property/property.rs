@@ -1,3 +1,4 @@
+use serde::{Deserialize, Serialize};
 use std::any::Any;
 use std::fmt::Debug;
 use thiserror::Error;
@@ -31,7 +32,7 @@ impl PartialEq for dyn Property {
 }
 
 // Semantic property type
-#[derive(PartialEq, Debug, Clone, Copy)]
+#[derive(PartialEq, Debug, Clone, Copy, Serialize, Deserialize)]
 pub enum PropertyType {
     Brightness,
     Color,
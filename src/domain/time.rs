@@ -1,5 +1,25 @@
+use chrono::NaiveTime;
+use std::fmt::{Display, Formatter};
+
 #[derive(PartialEq, Debug, Clone)]
 pub struct Time {
     pub hour: u8,
     pub minute: u8,
+    pub second: u8,
+}
+
+impl Time {
+    pub fn to_naive_time(&self) -> Option<NaiveTime> {
+        NaiveTime::from_hms_opt(self.hour as u32, self.minute as u32, self.second as u32)
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.second == 0 {
+            write!(f, "{:02}:{:02}", self.hour, self.minute)
+        } else {
+            write!(f, "{:02}:{:02}:{:02}", self.hour, self.minute, self.second)
+        }
+    }
 }
@@ -1,105 +1,171 @@
 use crate::domain::commands::Command;
-use crate::domain::{GeoLocation, controller_registry};
+use crate::domain::{Calendars, GeoLocation, controller_registry};
 use crate::flow_engine;
+use crate::flow_engine::action::CommandMap;
 use crate::flow_engine::flow::Flow;
-use crate::flow_engine::property_value::PropertyValue;
-use crate::flow_engine::{Context, FlowEngineError, FlowExecutionReport};
+use crate::flow_engine::{Context, ContextBuilder, FlowContinuation};
+use crate::flow_registry::FlowRegistry;
 use crate::scheduler::SchedulerCommand;
 use crate::store::StoreSnapshot;
-use futures::stream::FuturesUnordered;
-use futures::stream::StreamExt;
 use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc;
+use tokio::sync::mpsc::{Sender, UnboundedReceiver};
+use tokio::sync::watch::Receiver as WatchReceiver;
 use tracing::{instrument, warn};
 
-type CommandMap = HashMap<String, HashMap<String, PropertyValue>>;
-
-#[instrument(skip_all, fields(flow = flow.name(), node_id = node_id.as_deref().unwrap_or("<start>")))]
-pub async fn execute_flow(flow: Arc<Flow>, node_id: Option<String>, snapshot: StoreSnapshot, tx: Sender<SchedulerCommand>, geo_location: GeoLocation) {
-    let context = Context::builder().snapshot(snapshot.clone()).location(geo_location).build();
-    let result = flow_engine::execute(&flow, node_id, &context, tx).await;
+/// What caused flows to run, it decides the state of the store the runs start with.
+pub enum Cause {
+    Schedule(String),           // Id of the schedule, the runs start with the current state
+    StoreChange(StoreSnapshot), // The runs start with the state right after the change
+    Reevaluation,               // Of triggers that change with the passing of time, the runs start with the current state
+}
 
-    let command_map = merge_command_maps(vec![result]);
-    dispatch_commands(&snapshot, command_map).await;
+/// Starts or resumes a run of the flow with the current state of the store.
+#[instrument(skip_all, fields(flow = flow.name(), node_id = continuation.as_ref().map(FlowContinuation::node_id).unwrap_or("<start>")))]
+pub async fn execute_flow(
+    flow: Arc<Flow>,
+    continuation: Option<FlowContinuation>,
+    notifier_rx: WatchReceiver<StoreSnapshot>,
+    tx: Sender<SchedulerCommand>,
+    flow_registry: Arc<FlowRegistry>,
+    geo_location: GeoLocation,
+    calendars: Arc<Calendars>,
+) {
+    let context = Context::builder()
+        .snapshot(notifier_rx.borrow().without_changes())
+        .location(geo_location)
+        .calendars(calendars)
+        .flow_registry(flow_registry);
+    start_runs(vec![(flow, continuation)], context, notifier_rx, tx).await;
 }
 
 #[instrument(skip_all)]
-pub async fn execute_flows(flows: Vec<Arc<Flow>>, snapshot: StoreSnapshot, tx: Sender<SchedulerCommand>, geo_location: GeoLocation) {
-    let context = Context::builder().snapshot(snapshot.clone()).location(geo_location).build();
-    let results = FuturesUnordered::from_iter(flows.iter().map(|flow| async { flow_engine::execute(flow, None, &context, tx.clone()).await }))
-        .collect::<Vec<_>>()
-        .await;
-
-    let command_map = merge_command_maps(results);
-    dispatch_commands(&snapshot, command_map).await;
+pub async fn execute_flows(
+    flows: Vec<Arc<Flow>>,
+    cause: Cause,
+    notifier_rx: WatchReceiver<StoreSnapshot>,
+    tx: Sender<SchedulerCommand>,
+    flow_registry: Arc<FlowRegistry>,
+    geo_location: GeoLocation,
+    calendars: Arc<Calendars>,
+) {
+    let context = Context::builder().location(geo_location).calendars(calendars).flow_registry(flow_registry);
+    let context = match cause {
+        Cause::Schedule(trigger_id) => context.snapshot(notifier_rx.borrow().without_changes()).trigger_id(trigger_id),
+        Cause::StoreChange(snapshot) => context.snapshot(snapshot),
+        Cause::Reevaluation => context.snapshot(notifier_rx.borrow().without_changes()),
+    };
+    let runs = flows.into_iter().map(|flow| (flow, None)).collect();
+    start_runs(runs, context, notifier_rx, tx).await;
 }
 
-fn merge_command_maps(reports: Vec<Result<FlowExecutionReport, FlowEngineError>>) -> CommandMap {
-    let mut merged_map = HashMap::new();
-
-    for report in reports.into_iter().filter_map(Result::ok) {
-        if let Some(command_map) = report.take_from_scope::<CommandMap>("command_map") {
-            for (device_id, properties) in command_map {
-                let device_properties = merged_map.entry(device_id).or_insert_with(HashMap::new);
-                device_properties.extend(properties);
+/// Starts the runs one after the other, so the triggers are evaluated in the order of the events, and executes each
+/// admitted run on a task of its own. This way a run that sleeps does not hold up the caller or other runs. The commands
+/// of the runs are dispatched as they come in.
+async fn start_runs(runs: Vec<(Arc<Flow>, Option<FlowContinuation>)>, context: ContextBuilder, notifier_rx: WatchReceiver<StoreSnapshot>, tx: Sender<SchedulerCommand>) {
+    let (commands_tx, commands_rx) = mpsc::unbounded_channel();
+    let context = context.commands(commands_tx.clone()).notifier(notifier_rx.clone()).build();
+
+    for (flow, continuation) in runs {
+        let flow_id = flow.id().to_string();
+        let run = match flow_engine::start(&flow, continuation, &context, &tx).await {
+            Ok(Some(run)) => run,
+            Ok(None) => continue,
+            Err(error) => {
+                warn!("⚠️ Starting flow '{}' failed: {}", flow.name(), error);
+                continue;
+            }
+        };
+
+        let run_id = run.run_id();
+        let tx = tx.clone();
+        let commands_tx = commands_tx.clone();
+        let task = tokio::spawn(async move {
+            match run.execute(&flow, tx).await {
+                Ok(report) => {
+                    if let Some(command_map) = report.take_from_scope::<CommandMap>("command_map") {
+                        let _ = commands_tx.send(command_map);
+                    }
+                }
+                Err(error) => warn!("⚠️ Executing flow '{}' failed: {}", flow.name(), error),
             }
+        });
+        if let Some(flow_registry) = context.flow_registry() {
+            flow_registry.runs().execute(&flow_id, run_id, task.abort_handle());
         }
     }
 
-    merged_map
+    tokio::spawn(dispatch_commands(notifier_rx, commands_rx));
 }
 
-async fn dispatch_commands(snapshot: &StoreSnapshot, command_map: CommandMap) {
-    for (device_id, properties) in command_map {
-        if let Some(device) = snapshot.devices.get(&device_id) {
-            if let Some(controller) = device.controller_id.and_then(|controller_id| controller_registry::get(controller_id)) {
-                let command = Command::ControlDevice {
-                    device: device.clone(),
-                    property: Arc::new(properties),
-                };
-                controller.execute(command).await;
-            } else {
-                warn!(device_id, "⚠️ Device '{}' is not tied to a controller", device.name);
+/// Dispatches the commands until all runs are done. Commands that are waiting already are merged, so a device receives
+/// a single command for them. The commands apply to the current state of the devices, which may have changed while a
+/// branch of a run was sleeping.
+async fn dispatch_commands(notifier_rx: WatchReceiver<StoreSnapshot>, mut commands_rx: UnboundedReceiver<CommandMap>) {
+    while let Some(command_map) = commands_rx.recv().await {
+        let mut command_maps = vec![command_map];
+        while let Ok(command_map) = commands_rx.try_recv() {
+            command_maps.push(command_map);
+        }
+
+        let devices = notifier_rx.borrow().devices.clone();
+        for (device_id, properties) in merge_command_maps(command_maps) {
+            if let Some(device) = devices.get(&device_id) {
+                if let Some(controller) = device.controller_id.and_then(|controller_id| controller_registry::get(controller_id)) {
+                    let command = Command::ControlDevice {
+                        device: device.clone(),
+                        property: Arc::new(properties),
+                    };
+                    controller.execute(command).await;
+                } else {
+                    warn!(device_id, "⚠️ Device '{}' is not tied to a controller", device.name);
+                }
             }
         }
     }
 }
 
+fn merge_command_maps(command_maps: Vec<CommandMap>) -> CommandMap {
+    let mut merged_map = HashMap::new();
+
+    for command_map in command_maps {
+        for (device_id, properties) in command_map {
+            let device_properties = merged_map.entry(device_id).or_insert_with(HashMap::new);
+            device_properties.extend(properties);
+        }
+    }
+
+    merged_map
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::flow_engine::property_value::PropertyValue;
     use crate::flow_engine::property_value::PropertyValue::SetBooleanValue;
-    use std::any::Any;
-    use std::time::Duration;
     use test_log::test;
 
     const DEVICE_ID: &str = "device_id";
 
-    fn create_report(properties: HashMap<String, PropertyValue>) -> FlowExecutionReport {
-        let mut command_map = HashMap::new();
-        command_map.insert(DEVICE_ID.to_string(), properties);
-
-        let mut scope: HashMap<String, Box<dyn Any + Send + Sync>> = HashMap::new();
-        scope.insert("command_map".to_string(), Box::new(command_map));
-
-        FlowExecutionReport::new(scope, Duration::from_millis(1))
+    fn create_command_map(properties: HashMap<String, PropertyValue>) -> CommandMap {
+        HashMap::from([(DEVICE_ID.to_string(), properties)])
     }
 
     #[test]
     fn merge_a_single_command_map() {
-        let report = create_report(HashMap::from([("property_id".to_string(), SetBooleanValue(true))]));
-        let result = merge_command_maps(vec![Ok(report)]);
+        let command_map = create_command_map(HashMap::from([("property_id".to_string(), SetBooleanValue(true))]));
+        let result = merge_command_maps(vec![command_map]);
 
         assert_eq!(result[DEVICE_ID]["property_id"], SetBooleanValue(true));
     }
 
     #[test]
     fn merge_two_maps_with_overlapping_properties() {
-        let report = create_report(HashMap::from([("property_id".to_string(), SetBooleanValue(true))]));
-        let report2 = create_report(HashMap::from([("property_id".to_string(), SetBooleanValue(false))]));
+        let command_map = create_command_map(HashMap::from([("property_id".to_string(), SetBooleanValue(true))]));
+        let command_map2 = create_command_map(HashMap::from([("property_id".to_string(), SetBooleanValue(false))]));
 
-        let result = merge_command_maps(vec![Ok(report), Ok(report2)]);
+        let result = merge_command_maps(vec![command_map, command_map2]);
 
         assert_eq!(result[DEVICE_ID]["property_id"], SetBooleanValue(false));
     }
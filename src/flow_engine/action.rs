@@ -1,14 +1,16 @@
 use crate::flow_engine::action_registry::{ACTION_REGISTRY, known_actions, register_action};
 use crate::flow_engine::context::Context;
-use crate::flow_engine::property_value::PropertyValue;
+use crate::flow_engine::expression::evaluate_with_variables;
+use crate::flow_engine::property_value::{PropertyValue, PropertyValueExpression};
 use crate::flow_engine::scope::Scope;
+use crate::flow_engine::{Expression, Value, Variables};
 use action_macros::register_action;
 use async_trait::async_trait;
 use serde::{Deserialize, Deserializer};
 use std::any::Any;
 use std::collections::HashMap;
 use std::fmt::Debug;
-use tracing::{error, info, instrument, warn};
+use tracing::{debug, error, info, instrument, warn};
 
 #[async_trait]
 pub trait Action: Debug + Send + Sync {
@@ -74,17 +76,17 @@ impl Action for LogAction {
 #[register_action]
 pub struct ControlDeviceAction {
     device_id: String,
-    property: HashMap<String, PropertyValue>,
+    property: HashMap<String, PropertyValueExpression>,
 }
 
 #[cfg(test)]
 impl ControlDeviceAction {
-    pub fn new(device_id: String, property: HashMap<String, PropertyValue>) -> ControlDeviceAction {
+    pub fn new(device_id: String, property: HashMap<String, PropertyValueExpression>) -> ControlDeviceAction {
         ControlDeviceAction { device_id, property }
     }
 }
 
-type CommandMap = HashMap<String, HashMap<String, PropertyValue>>;
+pub type CommandMap = HashMap<String, HashMap<String, PropertyValue>>;
 
 #[async_trait]
 impl Action for ControlDeviceAction {
@@ -103,14 +105,30 @@ impl Action for ControlDeviceAction {
             return;
         };
 
+        let variables = scope.get::<Variables>("variables");
+        let property_values: Vec<_> = self
+            .property
+            .iter()
+            .filter_map(|(property_id, property_value)| match property_value.evaluate(context, variables) {
+                Ok(property_value) => Some((property_id, property_value)),
+                Err(error) => {
+                    warn!(
+                        device_id = self.device_id,
+                        "⚠️ Unable to set property '{}' for device '{}', evaluating expression failed: {}", property_id, device.name, error
+                    );
+                    None
+                }
+            })
+            .collect();
+
         let Some(command_map) = scope.ensure_entry_mut::<CommandMap, _>("command_map".to_string(), HashMap::new) else {
             error!("🛑 Incorrect type for the command map");
             return;
         };
 
         let device_command_map = command_map.entry(self.device_id.clone()).or_insert_with(HashMap::new);
-        for (property_id, property_value) in self.property.iter() {
-            let result = device_command_map.insert(property_id.clone(), property_value.clone());
+        for (property_id, property_value) in property_values {
+            let result = device_command_map.insert(property_id.clone(), property_value);
             if let Some(previous_value) = result {
                 warn!(
                     device_id = self.device_id,
@@ -125,12 +143,70 @@ impl Action for ControlDeviceAction {
     }
 }
 
+#[derive(Debug, Deserialize, PartialEq)]
+#[register_action]
+pub struct SetVariableAction {
+    name: String,
+    expression: Expression,
+}
+
+impl Default for SetVariableAction {
+    fn default() -> Self {
+        SetVariableAction {
+            name: String::new(),
+            expression: Expression::Literal { value: Value::None },
+        }
+    }
+}
+
+#[cfg(test)]
+impl SetVariableAction {
+    pub fn new(name: String, expression: Expression) -> SetVariableAction {
+        SetVariableAction { name, expression }
+    }
+}
+
+#[async_trait]
+impl Action for SetVariableAction {
+    fn kind(&self) -> &'static str {
+        "setVariable"
+    }
+
+    #[instrument(fields(action = self.kind()), skip_all)]
+    async fn execute(&self, context: &Context, scope: &mut Scope) {
+        let value = match evaluate_with_variables(&self.expression, context, scope.get::<Variables>("variables")) {
+            Ok(value) => value,
+            Err(error) => {
+                warn!("⚠️ Unable to set variable '{}', evaluating expression failed: {}", self.name, error);
+                return;
+            }
+        };
+
+        let Some(variables) = scope.ensure_entry_mut::<Variables, _>("variables".to_string(), HashMap::new) else {
+            error!("🛑 Incorrect type for the variables");
+            return;
+        };
+
+        debug!(value = ?value, "Setting variable '{}'", self.name);
+        variables.insert(self.name.clone(), value);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::flow_engine::property_value::PropertyValue::SetBooleanValue;
+    use crate::domain::Number;
+    use crate::domain::device::{Device, DeviceType};
+    use crate::domain::property::{NumberProperty, Property, PropertyType};
+    use crate::flow_engine::property_value::Payload;
+    use crate::store::StoreSnapshot;
     use pretty_assertions::assert_eq;
     use std::io;
+    use std::sync::Arc;
 
     #[test]
     fn deserialize_log_action() -> io::Result<()> {
@@ -166,7 +242,7 @@ mod tests {
 
         let expected = ControlDeviceAction {
             device_id: "42".to_string(),
-            property: HashMap::from([("fan".to_string(), SetBooleanValue(true))]),
+            property: HashMap::from([("fan".to_string(), PropertyValueExpression::SetBooleanValue(Payload::Literal(true)))]),
         };
 
         let action = node.as_any().downcast_ref::<ControlDeviceAction>().unwrap();
@@ -175,6 +251,124 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn deserialize_set_variable_action() -> io::Result<()> {
+        let json = r#"{
+            "type": "setVariable",
+            "name": "counter",
+            "expression": {
+                "type": "literal",
+                "value": 1
+            }
+        }"#;
+
+        let node = serde_json::from_str::<Box<dyn Action>>(json)?;
+
+        let expected = SetVariableAction {
+            name: "counter".to_string(),
+            expression: Expression::Literal {
+                value: Value::Number(Number::PositiveInt(1)),
+            },
+        };
+
+        let action = node.as_any().downcast_ref::<SetVariableAction>().unwrap();
+        assert_eq!(&expected, action);
+
+        Ok(())
+    }
+
+    fn snapshot_with_lamps() -> StoreSnapshot {
+        let lamp = |id: &str, brightness: f64| {
+            let brightness_property: Box<dyn Property> = Box::new(
+                NumberProperty::builder("brightness".to_string(), PropertyType::Brightness, false)
+                    .float(brightness, Some(0.0), Some(100.0))
+                    .build(),
+            );
+            let device = Device {
+                id: id.to_string(),
+                r#type: DeviceType::Light,
+                manufacturer: "Signify Netherlands B.V.".to_string(),
+                model_id: "LCT007".to_string(),
+                product_name: "Hue color lamp".to_string(),
+                name: id.to_string(),
+                properties: HashMap::from([("brightness".to_string(), brightness_property)]),
+                external_id: None,
+                address: None,
+                controller_id: None,
+                room: None,
+                tags: Vec::new(),
+            };
+            (id.to_string(), Arc::new(device))
+        };
+
+        StoreSnapshot {
+            devices: Arc::new(HashMap::from([lamp("a", 40.0), lamp("b", 10.0)])),
+            ..StoreSnapshot::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn control_device_action_evaluates_the_property_values() {
+        let mut scope = Scope::new();
+        let brightness_of_a = Expression::PropertyValue {
+            device_id: "a".to_string(),
+            property_id: "brightness".to_string(),
+        };
+        let action = ControlDeviceAction::new(
+            "b".to_string(),
+            HashMap::from([("brightness".to_string(), PropertyValueExpression::SetNumberValue(Payload::Expression(brightness_of_a)))]),
+        );
+
+        action.execute(&Context::builder().snapshot(snapshot_with_lamps()).build(), &mut scope).await;
+
+        let command_map = scope.get::<CommandMap>("command_map").unwrap();
+        assert_eq!(command_map["b"]["brightness"], PropertyValue::SetNumberValue(Number::Float(40.0)));
+    }
+
+    #[tokio::test]
+    async fn control_device_action_skips_property_values_that_fail_to_evaluate() {
+        let mut scope = Scope::new();
+        let action = ControlDeviceAction::new(
+            "b".to_string(),
+            HashMap::from([(
+                "brightness".to_string(),
+                PropertyValueExpression::SetNumberValue(Payload::Expression(Expression::Variable { name: "unknown".to_string() })),
+            )]),
+        );
+
+        action.execute(&Context::builder().snapshot(snapshot_with_lamps()).build(), &mut scope).await;
+
+        let command_map = scope.get::<CommandMap>("command_map").unwrap();
+        assert!(command_map["b"].is_empty());
+    }
+
+    #[tokio::test]
+    async fn set_variable_action_stores_the_evaluated_value() {
+        let mut scope = Scope::new();
+        scope.store("variables".to_string(), Variables::from([("counter".to_string(), Value::Boolean(true))]));
+
+        let action = SetVariableAction::new(
+            "copy".to_string(),
+            Expression::Not {
+                expression: Box::new(Expression::Variable { name: "counter".to_string() }),
+            },
+        );
+        action.execute(&Context::default(), &mut scope).await;
+
+        let variables = scope.get::<Variables>("variables").unwrap();
+        assert_eq!(variables.get("copy"), Some(&Value::Boolean(false)));
+    }
+
+    #[tokio::test]
+    async fn set_variable_action_ignores_failed_evaluations() {
+        let mut scope = Scope::new();
+
+        let action = SetVariableAction::new("copy".to_string(), Expression::Variable { name: "unknown".to_string() });
+        action.execute(&Context::default(), &mut scope).await;
+
+        assert!(scope.get::<Variables>("variables").is_none());
+    }
+
     #[test]
     fn deserialize_returns_error_if_type_is_missing() {
         let json = "{}";
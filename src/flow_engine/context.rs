@@ -1,13 +1,27 @@
-use crate::domain::GeoLocation;
+use crate::domain::{Calendar, Calendars, GeoLocation};
+use crate::flow_engine::action::CommandMap;
+use crate::flow_engine::{SolarEvent, Value, solar};
+use crate::flow_registry::FlowRegistry;
+use crate::history::PropertyHistory;
 use crate::store::StoreSnapshot;
 use chrono::{DateTime, Local};
-use sunrise::{Coordinates, SolarDay, SolarEvent};
+use std::collections::HashMap;
+use std::sync::Arc;
+use sunrise::{Coordinates, SolarDay};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch::Receiver as WatchReceiver;
 
 #[derive(Default, Debug)]
 pub struct Context {
     snapshot: StoreSnapshot,
     now: DateTime<Local>,
     location: GeoLocation,
+    calendars: Arc<Calendars>,
+    flow_registry: Option<Arc<FlowRegistry>>,
+    parameters: HashMap<String, Value>,
+    trigger_id: Option<String>,                     // Of the trigger that started the run
+    commands: Option<UnboundedSender<CommandMap>>,  // Dispatches commands before the run ends
+    notifier: Option<WatchReceiver<StoreSnapshot>>, // Current state of the store, for branches that sleep
 }
 
 impl Context {
@@ -23,15 +37,94 @@ impl Context {
         self.now
     }
 
+    pub fn flow_registry(&self) -> Option<&FlowRegistry> {
+        self.flow_registry.as_deref()
+    }
+
+    pub fn history(&self) -> Option<&PropertyHistory> {
+        self.flow_registry().and_then(FlowRegistry::history)
+    }
+
+    pub fn calendar(&self, name: &str) -> Option<&Calendar> {
+        self.calendars.get(name)
+    }
+
+    pub fn parameter(&self, name: &str) -> Option<&Value> {
+        self.parameters.get(name)
+    }
+
+    pub fn trigger_id(&self) -> Option<&str> {
+        self.trigger_id.as_deref()
+    }
+
+    pub fn commands(&self) -> Option<&UnboundedSender<CommandMap>> {
+        self.commands.as_ref()
+    }
+
+    /// Returns a context with the current time and state of the store, for a branch that continues after a sleep. Keeps
+    /// the snapshot if there is no notifier, the changes that caused the run are not part of the current state.
+    pub fn refreshed(&self) -> Context {
+        Context {
+            snapshot: self.notifier.as_ref().map_or_else(|| self.snapshot.clone(), |notifier| notifier.borrow().without_changes()),
+            now: Local::now(),
+            ..self.for_trigger(self.trigger_id.clone())
+        }
+    }
+
+    /// Returns a context for a sub-flow, it shares everything with this context except for the parameters.
+    pub fn for_sub_flow(&self, parameters: HashMap<String, Value>) -> Context {
+        Context {
+            snapshot: self.snapshot.clone(),
+            now: self.now,
+            location: self.location.clone(),
+            calendars: self.calendars.clone(),
+            flow_registry: self.flow_registry.clone(),
+            parameters,
+            trigger_id: self.trigger_id.clone(),
+            commands: self.commands.clone(),
+            notifier: self.notifier.clone(),
+        }
+    }
+
+    /// Returns a context for a run started by the given trigger, it shares everything with this context except for the
+    /// trigger.
+    pub fn for_trigger(&self, trigger_id: Option<String>) -> Context {
+        Context {
+            snapshot: self.snapshot.clone(),
+            now: self.now,
+            location: self.location.clone(),
+            calendars: self.calendars.clone(),
+            flow_registry: self.flow_registry.clone(),
+            parameters: self.parameters.clone(),
+            trigger_id,
+            commands: self.commands.clone(),
+            notifier: self.notifier.clone(),
+        }
+    }
+
     pub fn sunrise(&self) -> DateTime<Local> {
-        self.solar_event(SolarEvent::Sunrise)
+        self.solar_event(sunrise::SolarEvent::Sunrise)
     }
 
     pub fn sunset(&self) -> DateTime<Local> {
-        self.solar_event(SolarEvent::Sunset)
+        self.solar_event(sunrise::SolarEvent::Sunset)
+    }
+
+    /// Returns the time of the event today, or `None` if the sun does not reach the elevation of the event today.
+    pub fn solar_event_time(&self, event: &SolarEvent) -> Option<DateTime<Local>> {
+        event.time_on(&self.location, self.now.date_naive()).map(|time| time.with_timezone(&Local))
     }
 
-    fn solar_event(&self, event: SolarEvent) -> DateTime<Local> {
+    pub fn is_golden_hour(&self) -> bool {
+        solar::is_golden_hour(&self.location, self.now.to_utc())
+    }
+
+    /// Returns the current elevation of the sun in degrees.
+    pub fn sun_elevation(&self) -> f64 {
+        solar::sun_elevation(&self.location, self.now.to_utc())
+    }
+
+    fn solar_event(&self, event: sunrise::SolarEvent) -> DateTime<Local> {
         let date = self.now.date_naive();
 
         // The expect is fine as GeoLocation is validated during deserialization
@@ -48,6 +141,11 @@ pub struct ContextBuilder {
     snapshot: Option<StoreSnapshot>,
     now: Option<DateTime<Local>>,
     location: Option<GeoLocation>,
+    calendars: Option<Arc<Calendars>>,
+    flow_registry: Option<Arc<FlowRegistry>>,
+    trigger_id: Option<String>,
+    commands: Option<UnboundedSender<CommandMap>>,
+    notifier: Option<WatchReceiver<StoreSnapshot>>,
 }
 
 impl ContextBuilder {
@@ -66,11 +164,42 @@ impl ContextBuilder {
         self
     }
 
+    pub fn calendars(mut self, calendars: Arc<Calendars>) -> Self {
+        self.calendars = Some(calendars);
+        self
+    }
+
+    pub fn flow_registry(mut self, flow_registry: Arc<FlowRegistry>) -> Self {
+        self.flow_registry = Some(flow_registry);
+        self
+    }
+
+    pub fn trigger_id(mut self, trigger_id: String) -> Self {
+        self.trigger_id = Some(trigger_id);
+        self
+    }
+
+    pub fn commands(mut self, commands: UnboundedSender<CommandMap>) -> Self {
+        self.commands = Some(commands);
+        self
+    }
+
+    pub fn notifier(mut self, notifier: WatchReceiver<StoreSnapshot>) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
     pub fn build(self) -> Context {
         Context {
             snapshot: self.snapshot.unwrap_or_default(),
             now: self.now.unwrap_or_else(Local::now),
             location: self.location.unwrap_or_default(),
+            calendars: self.calendars.unwrap_or_default(),
+            flow_registry: self.flow_registry,
+            parameters: HashMap::new(),
+            trigger_id: self.trigger_id,
+            commands: self.commands,
+            notifier: self.notifier,
         }
     }
 }
@@ -0,0 +1,61 @@
+use crate::flow_engine::scope::Scope;
+use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static NEXT_RUN_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Identifies a single run of a flow, including all of its resumptions after sleep nodes.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RunId(u64);
+
+impl RunId {
+    pub fn next() -> Self {
+        RunId(NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Display for RunId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A suspended flow run, created when the run reaches a sleep node. It captures the scope of the run so that the
+/// resumed run continues where it left off.
+#[derive(Debug)]
+pub struct FlowContinuation {
+    run_id: RunId,
+    node_id: String,
+    scope: Scope,
+    check_trigger: bool,
+    trigger_id: Option<String>, // Of the trigger that started the run
+}
+
+impl FlowContinuation {
+    pub fn new(run_id: RunId, node_id: String, scope: Scope, check_trigger: bool) -> Self {
+        FlowContinuation {
+            run_id,
+            node_id,
+            scope,
+            check_trigger,
+            trigger_id: None,
+        }
+    }
+
+    pub fn with_trigger_id(mut self, trigger_id: Option<String>) -> Self {
+        self.trigger_id = trigger_id;
+        self
+    }
+
+    pub fn run_id(&self) -> RunId {
+        self.run_id
+    }
+
+    pub fn node_id(&self) -> &str {
+        &self.node_id
+    }
+
+    pub fn into_parts(self) -> (RunId, String, Scope, bool, Option<String>) {
+        (self.run_id, self.node_id, self.scope, self.check_trigger, self.trigger_id)
+    }
+}
@@ -1,83 +1,423 @@
+use crate::flow_engine::action::CommandMap;
 use crate::flow_engine::context::Context;
-use crate::flow_engine::expression::{ExpressionError, evaluate};
-use crate::flow_engine::flow::{Flow, FlowNode, FlowNodeKind};
+use crate::flow_engine::continuation::{FlowContinuation, RunId};
+use crate::flow_engine::expression::{ExpressionError, Variables, evaluate, evaluate_with_variables};
+use crate::flow_engine::flow::{CallFlowNode, Flow, FlowLink, FlowNode, FlowNodeKind, TriggerKind, TriggerMode};
+use crate::flow_engine::flow_runs::{Admission, FlowRuns};
 use crate::flow_engine::scope::Scope;
-use crate::flow_engine::{SchedulerCommand, Value};
+use crate::flow_engine::{Expression, SchedulerCommand, Value};
+use crate::flow_registry::FlowRegistry;
 use ExecuteNodeResult::*;
+use futures::future::BoxFuture;
+use futures::stream::{FuturesUnordered, StreamExt};
 use std::any::Any;
 use std::collections::HashMap;
 use std::time::Duration;
 use thiserror::Error;
+use tokio::sync::Mutex;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::mpsc::error::SendError;
 use tokio::time::Instant;
 use tracing::{debug, error, info, instrument, trace, warn};
 
+/// Evaluates the triggers of the flow and admits the run, without executing any node yet. Returns `None` if the run may
+/// not start or resume. A new run starts at the start node of the flow, a continuation resumes a suspended run.
 #[instrument(fields(flow = flow.name()), skip_all)]
-pub async fn execute(flow: &Flow, node_id: Option<String>, context: &Context, tx: Sender<SchedulerCommand>) -> Result<FlowExecutionReport, FlowEngineError> {
-    debug!("⚖️ Evaluating trigger condition for flow...");
-    let result = evaluate(flow.trigger(), context);
-    match result {
-        Ok(Value::Boolean(true)) => debug!("⚖️ Evaluating trigger condition for flow... true"),
-        Ok(result) => {
-            debug!(result = ?result, "⚖️ Evaluating trigger condition for flow... false, skipping execution");
-            return Ok(FlowExecutionReport::empty());
+pub async fn start(flow: &Flow, continuation: Option<FlowContinuation>, context: &Context, tx: &Sender<SchedulerCommand>) -> Result<Option<FlowRun>, FlowEngineError> {
+    let runs = context.flow_registry().map(FlowRegistry::runs);
+    let (run_id, node_id, scope, check_trigger, trigger_id) = match continuation {
+        Some(continuation) => {
+            let (run_id, node_id, scope, check_trigger, trigger_id) = continuation.into_parts();
+            if runs.is_some_and(|runs| !runs.is_active(flow.id(), run_id)) {
+                debug!(run_id = %run_id, "⏹️ Run was cancelled, not resuming");
+                return Ok(None);
+            }
+            (run_id, Some(node_id), scope, check_trigger, trigger_id)
         }
-        Err(error) => {
-            warn!("⚖️ Evaluating trigger condition for flow... failed, {}", error);
-            return Err(FlowEngineError::FailedTriggerEvaluation(error));
+        // A new run of a scheduled flow comes with the id of its schedule
+        None => (RunId::next(), None, Scope::new(), true, context.trigger_id().map(str::to_string)),
+    };
+    let is_new_run = node_id.is_none();
+
+    let trigger_id = if check_trigger {
+        match firing_trigger(flow, trigger_id.as_deref(), is_new_run, context).map_err(FlowEngineError::FailedTriggerEvaluation) {
+            Ok(Some(trigger_id)) => Some(trigger_id),
+            result => {
+                if !is_new_run {
+                    finish_run(flow, run_id, runs, tx).await?;
+                }
+                return result.map(|_| None);
+            }
+        }
+    } else {
+        trigger_id
+    };
+
+    if is_new_run && let Some(runs) = runs {
+        match runs.admit(flow, run_id, trigger_id.as_deref()) {
+            Admission::Started => {}
+            Admission::Queued => {
+                info!(run_id = %run_id, mode = %flow.mode(), "⏸️ Flow is already running, queued run");
+                return Ok(None);
+            }
+            Admission::Rejected => {
+                info!(run_id = %run_id, mode = %flow.mode(), "⏹️ Flow is already running, skipping execution");
+                return Ok(None);
+            }
         }
     }
 
-    info!("▶️ Executing flow...");
-    let start = Instant::now();
+    Ok(Some(FlowRun {
+        run_id,
+        node_id,
+        scope,
+        context: context.for_trigger(trigger_id.clone()),
+        trigger_id,
+    }))
+}
 
-    let mut scope = Scope::new();
+/// A run that was admitted by `start`. It owns its context and scope, so it can execute on a task of its own.
+pub struct FlowRun {
+    run_id: RunId,
+    node_id: Option<String>, // Of the node to resume at, none for a new run
+    scope: Scope,
+    context: Context,
+    trigger_id: Option<String>,
+}
 
-    let start_node = if let Some(node_id) = node_id {
-        let node = flow.node_by_id(&node_id).ok_or_else(|| FlowEngineError::MissingProvidedStartNode(node_id))?;
-        // Passed node_id may point to an end node if it follows a sleep node
-        if matches!(node.kind(), FlowNodeKind::End) { None } else { Some(node) }
-    } else {
-        Some(flow.start_node())
-    };
+impl FlowRun {
+    pub fn run_id(&self) -> RunId {
+        self.run_id
+    }
 
-    let mut next_node = start_node;
-    while let Some(node) = next_node {
-        next_node = match execute_node(node, context, &mut scope).await? {
-            Next(node) => Some(node),
-            End => None,
-            Sleep { duration, next } => {
+    /// Executes the nodes of the run. When the run reaches a sleep node, the commands collected so far are returned in the
+    /// report and the rest of the scope is handed to the scheduler as a continuation of the run.
+    #[instrument(fields(flow = flow.name()), skip_all)]
+    pub async fn execute(self, flow: &Flow, tx: Sender<SchedulerCommand>) -> Result<FlowExecutionReport, FlowEngineError> {
+        let FlowRun {
+            run_id,
+            node_id,
+            scope,
+            context,
+            trigger_id,
+        } = self;
+        let context = &context;
+        let runs = context.flow_registry().map(FlowRegistry::runs);
+
+        info!(run_id = %run_id, trigger = trigger_id, "▶️ Executing flow...");
+        let start = Instant::now();
+
+        let start_node = match node_id {
+            Some(node_id) => flow.node_by_id(&node_id).ok_or(FlowEngineError::MissingProvidedStartNode(node_id)),
+            None => Ok(flow.start_node()),
+        };
+
+        let scope = Mutex::new(scope);
+        let result = match start_node {
+            Ok(start_node) => execute_path(flow, start_node, context, &scope, &tx, PathMode::Main)
+                .await
+                .map(|path_result| match path_result {
+                    PathResult::Suspended { duration, next, check_trigger } => Some((duration, next.id().to_string(), check_trigger)),
+                    _ => None,
+                }),
+            Err(error) => Err(error),
+        };
+        let mut scope = scope.into_inner();
+
+        match result {
+            // The run may have been cancelled while it was executing, in which case it is not resumed
+            Ok(Some((duration, node_id, check_trigger))) if runs.is_none_or(|runs| runs.is_active(flow.id(), run_id)) => {
+                // Commands collected before the sleep node are dispatched right away, not when the run resumes
+                let command_map = scope.remove::<CommandMap>("command_map");
+                let continuation = FlowContinuation::new(run_id, node_id, scope, check_trigger).with_trigger_id(trigger_id);
                 tx.send(SchedulerCommand::ScheduleOnce {
                     flow_id: flow.id().to_string(),
-                    node_id: next.id().to_string(),
                     delay: duration,
+                    continuation,
                 })
                 .await?;
-                None
+
+                scope = Scope::new();
+                if let Some(command_map) = command_map {
+                    scope.store("command_map".to_string(), command_map);
+                }
+            }
+            Ok(Some(_)) => debug!(run_id = %run_id, "⏹️ Run was cancelled, not suspending"),
+            Ok(None) => finish_run(flow, run_id, runs, &tx).await?,
+            Err(error) => {
+                finish_run(flow, run_id, runs, &tx).await?;
+                return Err(error);
+            }
+        }
+
+        let duration = Instant::now() - start;
+        info!(run_id = %run_id, duration = ?duration, "▶️ Executing flow... OK");
+
+        Ok(FlowExecutionReport { scope: scope.take(), duration })
+    }
+}
+
+/// Returns the id of the trigger the run is for, or `None` if the run may not start or resume. A run that already has a
+/// trigger, because it was scheduled or started before, is for that trigger as long as it still holds. A new run that
+/// is caused by a store change is for the first expression trigger that fires. In both cases, the condition of the flow
+/// must hold as well.
+fn firing_trigger(flow: &Flow, trigger_id: Option<&str>, is_new_run: bool, context: &Context) -> Result<Option<String>, ExpressionError> {
+    let trigger = match trigger_id.and_then(|trigger_id| flow.trigger_by_id(trigger_id)) {
+        Some(trigger) => match trigger.kind() {
+            TriggerKind::Schedule(_) => Some(trigger.id()),
+            TriggerKind::Expression { expression, .. } => evaluate_trigger(trigger.id(), expression, context)?.then_some(trigger.id()),
+        },
+        None => {
+            // All triggers are evaluated so edge-triggered ones keep track of their state, a failing trigger does not
+            // keep the others from firing
+            let mut fired = None;
+            let mut first_error = None;
+            for (trigger_id, expression, mode) in flow.trigger_expressions() {
+                let fires = match evaluate_trigger(trigger_id, expression, context) {
+                    Ok(result) if is_new_run => fires(flow, trigger_id, mode, result, context),
+                    Ok(result) => result,
+                    Err(error) => {
+                        first_error.get_or_insert(error);
+                        false
+                    }
+                };
+                if fires && fired.is_none() {
+                    fired = Some(trigger_id);
+                }
+            }
+            match (fired, first_error) {
+                (None, Some(error)) => return Err(error),
+                (fired, _) => fired,
             }
         }
+    };
+
+    match trigger {
+        Some(trigger_id) if evaluate_condition(flow.condition(), context)? => Ok(Some(trigger_id.to_string())),
+        _ => Ok(None),
     }
+}
 
-    let duration = Instant::now() - start;
-    info!(duration = ?duration, "▶️ Executing flow... OK");
+fn evaluate_trigger(trigger_id: &str, expression: &Expression, context: &Context) -> Result<bool, ExpressionError> {
+    debug!(trigger = trigger_id, expression = %expression, "⚖️ Evaluating trigger for flow...");
+    match evaluate(expression, context) {
+        Ok(Value::Boolean(true)) => {
+            debug!(trigger = trigger_id, "⚖️ Evaluating trigger for flow... true");
+            Ok(true)
+        }
+        Ok(result) => {
+            debug!(trigger = trigger_id, result = ?result, "⚖️ Evaluating trigger for flow... false");
+            Ok(false)
+        }
+        Err(error) => {
+            warn!(trigger = trigger_id, "⚖️ Evaluating trigger for flow... failed, {}", error);
+            Err(error)
+        }
+    }
+}
+
+fn evaluate_condition(condition: &Expression, context: &Context) -> Result<bool, ExpressionError> {
+    debug!(condition = %condition, "⚖️ Evaluating condition for flow...");
+    match evaluate(condition, context) {
+        Ok(Value::Boolean(true)) => {
+            debug!("⚖️ Evaluating condition for flow... true");
+            Ok(true)
+        }
+        Ok(result) => {
+            debug!(result = ?result, "⚖️ Evaluating condition for flow... false, skipping execution");
+            Ok(false)
+        }
+        Err(error) => {
+            warn!("⚖️ Evaluating condition for flow... failed, {}", error);
+            Err(error)
+        }
+    }
+}
+
+/// Edge-triggered expression triggers only fire when they change from false to true, their state is kept per trigger.
+fn fires(flow: &Flow, trigger_id: &str, mode: TriggerMode, result: bool, context: &Context) -> bool {
+    match (mode, context.flow_registry()) {
+        (TriggerMode::Edge, Some(flow_registry)) => {
+            let fires = flow_registry.trigger_states().rising_edge(&format!("{}:{}", flow.id(), trigger_id), result);
+            if result && !fires {
+                debug!(trigger = trigger_id, "⚖️ Trigger for flow did not change, not firing");
+            }
+            fires
+        }
+        _ => result,
+    }
+}
+
+/// Marks the run as ended and starts the next queued run of the flow, if any.
+async fn finish_run(flow: &Flow, run_id: RunId, runs: Option<&FlowRuns>, tx: &Sender<SchedulerCommand>) -> Result<(), FlowEngineError> {
+    if let Some(continuation) = runs.and_then(|runs| runs.finish(flow.id(), run_id)) {
+        debug!(run_id = %continuation.run_id(), "⏯️ Starting queued run");
+        tx.send(SchedulerCommand::ScheduleOnce {
+            flow_id: flow.id().to_string(),
+            delay: Duration::ZERO,
+            continuation,
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// Executes the nodes starting at `node` until an end node is reached. When executed as a branch of a fork node,
+/// the path also stops at the first join node. Sleep nodes suspend the run on the main path, in a branch they are awaited
+/// after the commands collected so far are dispatched and the branch continues with the current state of the store. A
+/// called flow cannot sleep.
+fn execute_path<'a: 'c, 'c>(
+    flow: &'a Flow,
+    node: &'a FlowNode,
+    context: &'c Context,
+    scope: &'c Mutex<Scope>,
+    tx: &'c Sender<SchedulerCommand>,
+    mode: PathMode,
+) -> BoxFuture<'c, Result<PathResult<'a>, FlowEngineError>> {
+    Box::pin(async move {
+        let mut current_node = node;
+        loop {
+            match current_node.kind() {
+                // A resumed path may start at an end node if it follows a sleep node
+                FlowNodeKind::End => return Ok(PathResult::Ended),
+                FlowNodeKind::Join(_) if mode == PathMode::Branch => return Ok(PathResult::Joined(current_node)),
+                _ => {}
+            }
+
+            current_node = match execute_node(current_node, context, scope).await? {
+                Next(node) => node,
+                // Flows with sleep nodes are rejected as called flows when they are loaded
+                Sleep { .. } if mode == PathMode::SubFlow => return Err(FlowEngineError::SleepInCalledFlow(current_node.id().to_owned())),
+                Sleep { duration, next, .. } if mode == PathMode::Branch => {
+                    dispatch_collected_commands(context, scope).await;
+                    debug!(duration = ?duration, "💤 Sleeping before continuing with node '{}'", next.id());
+                    tokio::time::sleep(duration).await;
+                    let context = context.refreshed();
+                    return execute_path(flow, next, &context, scope, tx, mode).await;
+                }
+                Sleep { duration, next, check_trigger } => return Ok(PathResult::Suspended { duration, next, check_trigger }),
+                Fork(branches) => match execute_fork(flow, current_node, branches, context, scope, tx).await? {
+                    Some(join_node) => join_node
+                        .outgoing_nodes()
+                        .first()
+                        .ok_or_else(|| FlowEngineError::MissingOutgoingNode(join_node.id().to_owned()))?
+                        .node(),
+                    None => return Ok(PathResult::Ended),
+                },
+                CallFlow { call_flow_node, next } => {
+                    execute_sub_flow(current_node, call_flow_node, context, scope, tx).await?;
+                    next
+                }
+            }
+        }
+    })
+}
+
+/// Executes the called flow from its start node, ignoring its trigger. The sub-flow runs with its own scope, the commands
+/// it collected are merged into the scope of the caller once it completes.
+async fn execute_sub_flow(node: &FlowNode, call_flow_node: &CallFlowNode, context: &Context, scope: &Mutex<Scope>, tx: &Sender<SchedulerCommand>) -> Result<(), FlowEngineError> {
+    let flow_id = call_flow_node.flow_id();
+    let sub_flow = context
+        .flow_registry()
+        .and_then(|registry| registry.by_id(flow_id))
+        .ok_or_else(|| FlowEngineError::UnknownSubFlow {
+            node_id: node.id().to_string(),
+            flow_id: flow_id.to_string(),
+        })?;
+
+    let mut parameters = HashMap::with_capacity(call_flow_node.parameters().len());
+    for (name, expression) in call_flow_node.parameters() {
+        let value = evaluate_with_variables(expression, context, scope.lock().await.get::<Variables>("variables")).map_err(|error| FlowEngineError::FailedParameterEvaluation {
+            node_id: node.id().to_string(),
+            parameter: name.clone(),
+            error,
+        })?;
+        parameters.insert(name.clone(), value);
+    }
+
+    info!(parameters = ?parameters, "↪️ Calling flow '{}'...", sub_flow.name());
+    let sub_flow_context = context.for_sub_flow(parameters);
+    let sub_flow_scope = Mutex::new(Scope::new());
+    execute_path(&sub_flow, sub_flow.start_node(), &sub_flow_context, &sub_flow_scope, tx, PathMode::SubFlow).await?;
+
+    if let Some(sub_flow_command_map) = sub_flow_scope.into_inner().remove::<CommandMap>("command_map") {
+        let mut scope = scope.lock().await;
+        let Some(command_map) = scope.ensure_entry_mut::<CommandMap, _>("command_map".to_string(), HashMap::new) else {
+            error!("🛑 Incorrect type for the command map");
+            return Ok(());
+        };
+
+        for (device_id, properties) in sub_flow_command_map {
+            command_map.entry(device_id).or_default().extend(properties);
+        }
+    }
+
+    info!("↪️ Calling flow '{}'... OK", sub_flow.name());
+    Ok(())
+}
+
+/// Runs all branches concurrently and returns the join node once the required number of branches arrived at it.
+/// Branches that are still running at that moment are cancelled. The commands of a branch are dispatched as soon as it
+/// completes, so they do not wait for branches that are sleeping.
+async fn execute_fork<'a: 'c, 'c>(
+    flow: &'a Flow,
+    fork_node: &'a FlowNode,
+    branches: Vec<&'a FlowNode>,
+    context: &'c Context,
+    scope: &'c Mutex<Scope>,
+    tx: &'c Sender<SchedulerCommand>,
+) -> Result<Option<&'a FlowNode>, FlowEngineError> {
+    info!("🔀 Forking {} branches at node '{}'", branches.len(), fork_node.id());
+    let mut running_branches = FuturesUnordered::from_iter(branches.into_iter().map(|branch| execute_path(flow, branch, context, scope, tx, PathMode::Branch)));
+
+    let mut arrived = 0;
+    while let Some(result) = running_branches.next().await {
+        if !running_branches.is_empty() {
+            dispatch_collected_commands(context, scope).await;
+        }
+        let PathResult::Joined(join_node) = result? else {
+            continue;
+        };
+
+        arrived += 1;
+        if matches!(join_node.kind(), FlowNodeKind::Join(required) if arrived >= *required) {
+            info!(cancelled_branches = running_branches.len(), "🔀 Joined {} branch(es) at node '{}'", arrived, join_node.id());
+            return Ok(Some(join_node));
+        }
+    }
+
+    warn!("🔀 Branches of fork node '{}' completed without reaching a join node", fork_node.id());
+    Ok(None)
+}
+
+/// Hands the commands collected so far to the dispatcher of the context, if there is one. Otherwise they stay in the scope
+/// until the run suspends or ends.
+async fn dispatch_collected_commands(context: &Context, scope: &Mutex<Scope>) {
+    let Some(commands) = context.commands() else {
+        return;
+    };
 
-    Ok(FlowExecutionReport { scope: scope.take(), duration })
+    if let Some(command_map) = scope.lock().await.remove::<CommandMap>("command_map")
+        && commands.send(command_map).is_err()
+    {
+        warn!("⚠️ Unable to dispatch the commands, dispatcher is gone");
+    }
 }
 
 #[instrument(fields(node = node.id()), skip_all)]
-async fn execute_node<'a>(node: &'a FlowNode, context: &Context, scope: &mut Scope) -> Result<ExecuteNodeResult<'a>, FlowEngineError> {
+async fn execute_node<'a>(node: &'a FlowNode, context: &Context, scope: &Mutex<Scope>) -> Result<ExecuteNodeResult<'a>, FlowEngineError> {
     trace!("{:?}", node);
 
     let next_flow_link = match node.kind() {
         FlowNodeKind::Action(action_flow_node) => {
             info!("Executing action {}", action_flow_node.action().kind());
-            action_flow_node.action().execute(context, scope).await;
+            let mut scope = scope.lock().await;
+            action_flow_node.action().execute(context, &mut scope).await;
             node.outgoing_nodes().first()
         }
         FlowNodeKind::Conditional(expression) => {
-            debug!(?expression, "⚖️ Evaluating conditional node '{}'...", node.id());
-            let result = evaluate(expression, context);
+            debug!(%expression, "⚖️ Evaluating conditional node '{}'...", node.id());
+            let result = evaluate_with_variables(expression, context, scope.lock().await.get::<Variables>("variables"));
             match result {
                 Ok(value) => {
                     let flow_link = node.outgoing_nodes().iter().find(|link| *link.value() == value);
@@ -101,6 +441,13 @@ async fn execute_node<'a>(node: &'a FlowNode, context: &Context, scope: &mut Sco
                 }
             }
         }
+        FlowNodeKind::Fork => {
+            let branches: Vec<&FlowNode> = node.outgoing_nodes().iter().map(FlowLink::node).collect();
+            if branches.is_empty() {
+                return Err(FlowEngineError::MissingOutgoingNode(node.id().to_owned()));
+            }
+            return Ok(Fork(branches));
+        }
         _ => node.outgoing_nodes().first(),
     };
 
@@ -108,23 +455,35 @@ async fn execute_node<'a>(node: &'a FlowNode, context: &Context, scope: &mut Sco
 
     debug!("Next node: {}", next_node.id());
 
-    if let FlowNodeKind::Sleep(duration) = node.kind() {
-        return Ok(Sleep {
-            duration: *duration,
+    match node.kind() {
+        FlowNodeKind::Sleep(sleep_flow_node) => Ok(Sleep {
+            duration: sleep_flow_node.duration(),
             next: next_node,
-        });
-    }
-
-    match next_node.kind() {
-        FlowNodeKind::End => Ok(End),
+            check_trigger: sleep_flow_node.check_trigger(),
+        }),
+        FlowNodeKind::CallFlow(call_flow_node) => Ok(CallFlow { call_flow_node, next: next_node }),
         _ => Ok(Next(next_node)),
     }
 }
 
 enum ExecuteNodeResult<'a> {
     Next(&'a FlowNode),
-    End,
-    Sleep { duration: Duration, next: &'a FlowNode },
+    Sleep { duration: Duration, next: &'a FlowNode, check_trigger: bool },
+    Fork(Vec<&'a FlowNode>),
+    CallFlow { call_flow_node: &'a CallFlowNode, next: &'a FlowNode },
+}
+
+enum PathResult<'a> {
+    Ended,
+    Joined(&'a FlowNode),
+    Suspended { duration: Duration, next: &'a FlowNode, check_trigger: bool },
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum PathMode {
+    Main,
+    Branch,
+    SubFlow,
 }
 
 #[derive(Error, Debug)]
@@ -141,6 +500,12 @@ pub enum FlowEngineError {
     FailedScheduleSleepCommand(#[from] SendError<SchedulerCommand>),
     #[error("missing provided start node '{0}'")]
     MissingProvidedStartNode(String),
+    #[error("node '{node_id}' calls unknown flow '{flow_id}'")]
+    UnknownSubFlow { node_id: String, flow_id: String },
+    #[error("sleep node '{0}' is part of a called flow, which cannot sleep")]
+    SleepInCalledFlow(String),
+    #[error("evaluation of parameter '{parameter}' in node '{node_id}' failed: {error}")]
+    FailedParameterEvaluation { node_id: String, parameter: String, error: ExpressionError },
 }
 
 #[derive(Debug)]
@@ -150,18 +515,6 @@ pub struct FlowExecutionReport {
 }
 
 impl FlowExecutionReport {
-    #[cfg(test)]
-    pub fn new(scope: HashMap<String, Box<dyn Any + Send + Sync>>, duration: Duration) -> Self {
-        FlowExecutionReport { scope, duration }
-    }
-
-    pub fn empty() -> Self {
-        FlowExecutionReport {
-            scope: HashMap::with_capacity(0),
-            duration: Duration::ZERO,
-        }
-    }
-
     pub fn scope(&self) -> &HashMap<String, Box<dyn Any + Send + Sync>> {
         &self.scope
     }
@@ -179,12 +532,89 @@ impl FlowExecutionReport {
 mod tests {
     use super::*;
     use crate::domain::Number;
-    use crate::flow_engine::Expression::Literal;
-    use crate::flow_engine::action::LogAction;
-    use crate::flow_engine::flow::{ActionFlowNode, FlowLink, FlowNodeKind};
+    use crate::domain::Time;
+    use crate::domain::device::{Device, DeviceType};
+    use crate::domain::property::{BooleanProperty, Property, PropertyType};
+    use crate::flow_engine::Expression::{EqualTo, Literal, Parameter, PropertyValue, Temporal, TriggeredBy, Variable};
+    use crate::flow_engine::Schedule;
+    use crate::flow_engine::action::{ControlDeviceAction, LogAction, SetVariableAction};
+    use crate::flow_engine::context::ContextBuilder;
+    use crate::flow_engine::expression::TemporalExpression;
+    use crate::flow_engine::flow::{ActionFlowNode, CallFlowNode, FlowLink, FlowMode, FlowNodeKind, SleepFlowNode, Trigger};
+    use crate::flow_engine::property_value::Payload;
+    use crate::flow_engine::property_value::PropertyValueExpression::SetBooleanValue;
+    use crate::store::StoreSnapshot;
+    use chrono::{Local, TimeZone};
     use std::sync::Arc;
     use test_log::test;
-    use tokio::sync::mpsc;
+    use tokio::sync::{mpsc, watch};
+
+    fn context_with_device(device_id: &str) -> ContextBuilder {
+        Context::builder().snapshot(device_snapshot(device_id, HashMap::new()))
+    }
+
+    fn device_snapshot(device_id: &str, properties: HashMap<String, Box<dyn Property>>) -> StoreSnapshot {
+        let device = Device {
+            id: device_id.to_string(),
+            r#type: DeviceType::Light,
+            manufacturer: "Signify Netherlands B.V.".to_string(),
+            model_id: "LCT007".to_string(),
+            product_name: "Hue color lamp".to_string(),
+            name: "Lamp".to_string(),
+            properties,
+            external_id: None,
+            address: None,
+            controller_id: None,
+            room: None,
+            tags: Vec::new(),
+        };
+        StoreSnapshot {
+            devices: Arc::new(HashMap::from([(device.id.clone(), Arc::new(device))])),
+            changes: Arc::default(),
+            last_changed: Arc::default(),
+        }
+    }
+
+    fn lamp_snapshot(on: bool) -> StoreSnapshot {
+        let on_property: Box<dyn Property> = Box::new(BooleanProperty::new("on".to_string(), PropertyType::On, false, None, on));
+        device_snapshot("device", HashMap::from([("on".to_string(), on_property)]))
+    }
+
+    fn control_device_node(id: &str, property_id: &str, next: Arc<FlowNode>) -> Arc<FlowNode> {
+        let action = ControlDeviceAction::new("device".to_string(), HashMap::from([(property_id.to_string(), SetBooleanValue(Payload::Literal(true)))]));
+        Arc::new(FlowNode::new(
+            id.to_string(),
+            vec![FlowLink::new(next, Value::None)],
+            FlowNodeKind::Action(ActionFlowNode::new(Box::new(action))),
+        ))
+    }
+
+    fn fork_join_flow(branches: Vec<Arc<FlowNode>>) -> Flow {
+        let fork_node = Arc::new(FlowNode::new(
+            "forkNode".to_string(),
+            branches.into_iter().map(|branch| FlowLink::new(branch, Value::None)).collect(),
+            FlowNodeKind::Fork,
+        ));
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![FlowLink::new(fork_node, Value::None)], FlowNodeKind::Start));
+
+        Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new()).unwrap()
+    }
+
+    fn expression_trigger(id: &str, expression: Expression, mode: TriggerMode) -> Trigger {
+        Trigger::new(id.to_string(), TriggerKind::Expression { expression, mode })
+    }
+
+    /// Starts the run and executes it right away, returns `None` if the run did not start.
+    async fn execute(flow: &Flow, continuation: Option<FlowContinuation>, context: &Context, tx: Sender<SchedulerCommand>) -> Result<Option<FlowExecutionReport>, FlowEngineError> {
+        match start(flow, continuation, context, &tx).await? {
+            Some(run) => run.execute(flow, tx).await.map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn continuation(node_id: &str, scope: Scope, check_trigger: bool) -> FlowContinuation {
+        FlowContinuation::new(RunId::next(), node_id.to_string(), scope, check_trigger)
+    }
 
     #[test(tokio::test)]
     async fn executes_a_flow_with_one_action_node() {
@@ -198,7 +628,7 @@ mod tests {
         );
 
         let start_node = FlowNode::new("startNode".to_string(), vec![FlowLink::new(Arc::new(log_node), Value::None)], FlowNodeKind::Start);
-        let flow = Flow::new("id".to_string(), "flow".to_string(), None, None, Arc::new(start_node), HashMap::new()).unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
 
         let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
         let result = execute(&flow, None, &Context::default(), scheduler_tx).await;
@@ -208,38 +638,29 @@ mod tests {
     #[test(tokio::test)]
     async fn skips_execution_if_the_trigger_returns_false() {
         let start_node = FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start);
-        let flow = Flow::new(
-            "id".to_string(),
-            "flow".to_string(),
-            None,
-            Some(Literal { value: Value::Boolean(false) }),
-            Arc::new(start_node),
-            HashMap::new(),
-        )
-        .unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), Arc::new(start_node), HashMap::new())
+            .unwrap()
+            .with_triggers(vec![expression_trigger("expression", Literal { value: Value::Boolean(false) }, TriggerMode::Level)]);
 
         let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
         let result = execute(&flow, None, &Context::default(), scheduler_tx).await;
-        assert!(result.is_ok());
-        let result = result.unwrap();
-        assert!(result.scope.is_empty());
-        assert_eq!(result.duration, Duration::ZERO);
+        assert!(result.unwrap().is_none());
     }
 
     #[test(tokio::test)]
     async fn fails_if_the_start_node_id_cannot_be_found() {
         let start_node = FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start);
-        let flow = Flow::new("id".to_string(), "flow".to_string(), None, None, Arc::new(start_node), HashMap::new()).unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
 
         let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
-        let result = execute(&flow, Some("unknown".to_string()), &Context::default(), scheduler_tx).await;
+        let result = execute(&flow, Some(continuation("unknown", Scope::new(), false)), &Context::default(), scheduler_tx).await;
         assert!(matches!(result, Err(FlowEngineError::MissingProvidedStartNode(_))));
     }
 
     #[test(tokio::test)]
     async fn fails_if_an_outgoing_node_is_missing() {
         let start_node = FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start);
-        let flow = Flow::new("id".to_string(), "flow".to_string(), None, None, Arc::new(start_node), HashMap::new()).unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
 
         let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
         let result = execute(&flow, None, &Context::default(), scheduler_tx).await;
@@ -253,25 +674,102 @@ mod tests {
         let sleep_node = FlowNode::new(
             "sleep_node".to_string(),
             vec![FlowLink::new(Arc::new(end_node), Value::None)],
-            FlowNodeKind::Sleep(Duration::from_secs(42)),
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(42), false)),
         );
 
         let start_node = FlowNode::new("startNode".to_string(), vec![FlowLink::new(Arc::new(sleep_node), Value::None)], FlowNodeKind::Start);
-        let flow = Flow::new("id".to_string(), "flow".to_string(), None, None, Arc::new(start_node), HashMap::new()).unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
 
         let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
 
         execute(&flow, None, &Context::default(), scheduler_tx).await.unwrap();
         let received_command = scheduler_rx.recv().await;
-        if let Some(SchedulerCommand::ScheduleOnce { flow_id, node_id, delay }) = received_command {
+        if let Some(SchedulerCommand::ScheduleOnce { flow_id, delay, continuation }) = received_command {
             assert_eq!(flow_id, "id");
-            assert_eq!(node_id, "end_node");
+            assert_eq!(continuation.node_id(), "end_node");
             assert_eq!(delay, Duration::from_secs(42));
         } else {
             panic!("Expected ScheduleOnce command");
         }
     }
 
+    #[test(tokio::test)]
+    async fn captures_the_scope_in_the_continuation_but_returns_the_collected_commands_for_a_sleep_node() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(end_node, Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(42), true)),
+        ));
+        let set_variable_node = Arc::new(FlowNode::new(
+            "setVariableNode".to_string(),
+            vec![FlowLink::new(control_device_node("beforeSleepNode", "before_sleep", sleep_node), Value::None)],
+            FlowNodeKind::Action(ActionFlowNode::new(Box::new(SetVariableAction::new(
+                "motion".to_string(),
+                Literal { value: Value::Boolean(true) },
+            )))),
+        ));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(set_variable_node, Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new()).unwrap();
+
+        let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(&flow, None, &context_with_device("device").build(), scheduler_tx).await.unwrap().unwrap();
+
+        let command_map = result.take_from_scope::<CommandMap>("command_map").unwrap();
+        assert!(command_map["device"].contains_key("before_sleep"));
+
+        match scheduler_rx.recv().await {
+            Some(SchedulerCommand::ScheduleOnce { continuation, .. }) => {
+                assert_eq!(continuation.node_id(), "endNode");
+                let (_, _, scope, check_trigger, _) = continuation.into_parts();
+                assert!(check_trigger);
+                assert!(scope.get::<CommandMap>("command_map").is_none());
+                assert_eq!(scope.get::<Variables>("variables"), Some(&Variables::from([("motion".to_string(), Value::Boolean(true))])));
+            }
+            _ => panic!("Expected ScheduleOnce command"),
+        }
+    }
+
+    #[test(tokio::test)]
+    async fn resumes_execution_with_the_scope_of_the_continuation() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let conditional_node = Arc::new(FlowNode::new(
+            "conditionalNode".to_string(),
+            vec![
+                FlowLink::new(control_device_node("motionNode", "motion", end_node.clone()), Value::Boolean(true)),
+                FlowLink::new(end_node, Value::Boolean(false)),
+            ],
+            FlowNodeKind::Conditional(Variable { name: "motion".to_string() }),
+        ));
+        let nodes_by_id = HashMap::from([(conditional_node.id().to_string(), conditional_node.clone())]);
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(conditional_node, Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, nodes_by_id).unwrap();
+
+        let mut scope = Scope::new();
+        scope.store("variables".to_string(), Variables::from([("motion".to_string(), Value::Boolean(true))]));
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(
+            &flow,
+            Some(continuation("conditionalNode", scope, false)),
+            &context_with_device("device").build(),
+            scheduler_tx,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let command_map = result.take_from_scope::<CommandMap>("command_map").unwrap();
+        assert!(command_map["device"].contains_key("motion"));
+    }
+
     #[test(tokio::test)]
     async fn resumes_execution_at_the_specified_node_id() {
         let end_node = Arc::new(FlowNode::new("end_node".to_string(), vec![], FlowNodeKind::End));
@@ -279,7 +777,7 @@ mod tests {
         let sleep_node = Arc::new(FlowNode::new(
             "sleepNode".to_string(),
             vec![FlowLink::new(end_node.clone(), Value::None)],
-            FlowNodeKind::Sleep(Duration::from_secs(42)),
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(42), false)),
         ));
 
         let start_node = Arc::new(FlowNode::new(
@@ -292,16 +790,495 @@ mod tests {
             (sleep_node.id().to_string(), sleep_node.clone()),
             (end_node.id().to_string(), end_node.clone()),
         ]);
-        let flow = Flow::new("id".to_string(), "flow".to_string(), None, None, start_node, nodes_by_id).unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, nodes_by_id).unwrap();
 
         let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
-        let result = execute(&flow, Some("end_node".to_string()), &Context::default(), scheduler_tx).await.unwrap();
+        let result = execute(&flow, Some(continuation("end_node", Scope::new(), false)), &Context::default(), scheduler_tx)
+            .await
+            .unwrap()
+            .unwrap();
 
         // Ensure that nothing was scheduled
         assert!(scheduler_rx.try_recv().is_err(), "Expected no scheduler commands to be sent");
         assert!(result.scope.is_empty());
     }
 
+    fn flow_with_false_trigger_and_resume_node() -> Flow {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let resume_node = control_device_node("resumeNode", "resume", end_node);
+        let nodes_by_id = HashMap::from([(resume_node.id().to_string(), resume_node.clone())]);
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![FlowLink::new(resume_node, Value::None)], FlowNodeKind::Start));
+
+        Flow::new("id".to_string(), "flow".to_string(), start_node, nodes_by_id)
+            .unwrap()
+            .with_triggers(vec![expression_trigger("expression", Literal { value: Value::Boolean(false) }, TriggerMode::Level)])
+    }
+
+    #[test(tokio::test)]
+    async fn resumes_execution_without_checking_the_trigger() {
+        let flow = flow_with_false_trigger_and_resume_node();
+
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(
+            &flow,
+            Some(continuation("resumeNode", Scope::new(), false)),
+            &context_with_device("device").build(),
+            scheduler_tx,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert!(result.take_from_scope::<CommandMap>("command_map").is_some());
+    }
+
+    #[test(tokio::test)]
+    async fn skips_a_resumed_execution_if_the_trigger_is_checked_and_returns_false() {
+        let flow = flow_with_false_trigger_and_resume_node();
+
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(
+            &flow,
+            Some(continuation("resumeNode", Scope::new(), true)),
+            &context_with_device("device").build(),
+            scheduler_tx,
+        )
+        .await
+        .unwrap();
+
+        assert!(result.is_none());
+    }
+
+    fn registry_with_sleep_flow(mode: FlowMode) -> Arc<FlowRegistry> {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let after_sleep_node = control_device_node("afterSleepNode", "after_sleep", end_node);
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(after_sleep_node.clone(), Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(42), false)),
+        ));
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![FlowLink::new(sleep_node, Value::None)], FlowNodeKind::Start));
+        let nodes_by_id = HashMap::from([
+            (start_node.id().to_string(), start_node.clone()),
+            (after_sleep_node.id().to_string(), after_sleep_node.clone()),
+        ]);
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, nodes_by_id).unwrap().with_mode(mode);
+
+        Arc::new(FlowRegistry::new(vec![flow]))
+    }
+
+    fn receive_continuation(scheduler_rx: &mut mpsc::Receiver<SchedulerCommand>) -> (Duration, FlowContinuation) {
+        match scheduler_rx.try_recv() {
+            Ok(SchedulerCommand::ScheduleOnce { delay, continuation, .. }) => (delay, continuation),
+            _ => panic!("Expected ScheduleOnce command"),
+        }
+    }
+
+    #[test(tokio::test)]
+    async fn skips_a_new_run_in_single_mode_while_the_flow_is_sleeping() {
+        let registry = registry_with_sleep_flow(FlowMode::Single);
+        let flow = registry.by_id("id").unwrap();
+        let context = context_with_device("device").flow_registry(registry.clone()).build();
+        let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+
+        execute(&flow, None, &context, scheduler_tx.clone()).await.unwrap();
+        let (_, continuation) = receive_continuation(&mut scheduler_rx);
+
+        execute(&flow, None, &context, scheduler_tx.clone()).await.unwrap();
+        assert!(scheduler_rx.try_recv().is_err(), "Expected the second run to be skipped");
+
+        let result = execute(&flow, Some(continuation), &context, scheduler_tx.clone()).await.unwrap().unwrap();
+        assert!(result.take_from_scope::<CommandMap>("command_map").is_some());
+
+        execute(&flow, None, &context, scheduler_tx).await.unwrap();
+        assert!(scheduler_rx.try_recv().is_ok(), "Expected a new run once the previous run ended");
+    }
+
+    #[test(tokio::test)]
+    async fn cancels_the_sleeping_run_in_restart_mode() {
+        let registry = registry_with_sleep_flow(FlowMode::Restart);
+        let flow = registry.by_id("id").unwrap();
+        let context = context_with_device("device").flow_registry(registry.clone()).build();
+        let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+
+        execute(&flow, None, &context, scheduler_tx.clone()).await.unwrap();
+        let (_, first_continuation) = receive_continuation(&mut scheduler_rx);
+        execute(&flow, None, &context, scheduler_tx.clone()).await.unwrap();
+        let (_, second_continuation) = receive_continuation(&mut scheduler_rx);
+
+        let result = execute(&flow, Some(first_continuation), &context, scheduler_tx.clone()).await.unwrap();
+        assert!(result.is_none(), "Expected the first run to be cancelled");
+
+        let result = execute(&flow, Some(second_continuation), &context, scheduler_tx).await.unwrap().unwrap();
+        assert!(result.take_from_scope::<CommandMap>("command_map").is_some());
+    }
+
+    #[test(tokio::test)]
+    async fn starts_a_queued_run_once_the_sleeping_run_ended_in_queued_mode() {
+        let registry = registry_with_sleep_flow(FlowMode::Queued { max: None });
+        let flow = registry.by_id("id").unwrap();
+        let context = context_with_device("device").flow_registry(registry.clone()).build();
+        let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+
+        execute(&flow, None, &context, scheduler_tx.clone()).await.unwrap();
+        let (_, first_continuation) = receive_continuation(&mut scheduler_rx);
+        execute(&flow, None, &context, scheduler_tx.clone()).await.unwrap();
+        assert!(scheduler_rx.try_recv().is_err(), "Expected the second run to be queued");
+
+        execute(&flow, Some(first_continuation), &context, scheduler_tx).await.unwrap();
+        let (delay, queued_continuation) = receive_continuation(&mut scheduler_rx);
+        assert_eq!(delay, Duration::ZERO);
+        assert_eq!(queued_continuation.node_id(), "startNode");
+    }
+
+    fn registry_with_morning_flow(trigger_mode: TriggerMode) -> Arc<FlowRegistry> {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(control_device_node("triggeredNode", "triggered", end_node), Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let trigger = Temporal {
+            expression: TemporalExpression::IsBeforeTime {
+                time: Time { hour: 12, minute: 0, second: 0 },
+            },
+        };
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new())
+            .unwrap()
+            .with_triggers(vec![expression_trigger("morning", trigger, trigger_mode)]);
+
+        Arc::new(FlowRegistry::new(vec![flow]))
+    }
+
+    async fn execute_at(registry: &Arc<FlowRegistry>, hour: u32) -> bool {
+        let flow = registry.by_id("id").unwrap();
+        let context = context_with_device("device")
+            .flow_registry(registry.clone())
+            .now(Local.with_ymd_and_hms(2025, 10, 17, hour, 0, 0).unwrap())
+            .build();
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+
+        let result = execute(&flow, None, &context, scheduler_tx).await.unwrap();
+        result.and_then(|result| result.take_from_scope::<CommandMap>("command_map")).is_some()
+    }
+
+    #[test(tokio::test)]
+    async fn fires_an_edge_triggered_flow_only_when_the_trigger_changes_to_true() {
+        let registry = registry_with_morning_flow(TriggerMode::Edge);
+
+        assert!(!execute_at(&registry, 10).await, "Expected the first evaluation to not fire");
+        assert!(!execute_at(&registry, 14).await);
+        assert!(execute_at(&registry, 10).await, "Expected the flow to fire when the trigger changed to true");
+        assert!(!execute_at(&registry, 11).await, "Expected the flow to not fire while the trigger stays true");
+    }
+
+    #[test(tokio::test)]
+    async fn fires_a_level_triggered_flow_whenever_the_trigger_is_true() {
+        let registry = registry_with_morning_flow(TriggerMode::Level);
+
+        assert!(execute_at(&registry, 10).await);
+        assert!(execute_at(&registry, 11).await);
+        assert!(!execute_at(&registry, 14).await);
+    }
+
+    fn registry_with_evening_or_morning_flow(condition: Expression) -> Arc<FlowRegistry> {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let conditional_node = Arc::new(FlowNode::new(
+            "conditionalNode".to_string(),
+            vec![
+                FlowLink::new(control_device_node("eveningNode", "evening", end_node.clone()), Value::Boolean(true)),
+                FlowLink::new(control_device_node("otherNode", "other", end_node), Value::Boolean(false)),
+            ],
+            FlowNodeKind::Conditional(EqualTo {
+                lhs: Box::new(TriggeredBy),
+                rhs: Box::new(Literal {
+                    value: Value::String("evening".to_string()),
+                }),
+            }),
+        ));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(conditional_node, Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let morning = Temporal {
+            expression: TemporalExpression::IsBeforeTime {
+                time: Time { hour: 12, minute: 0, second: 0 },
+            },
+        };
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new())
+            .unwrap()
+            .with_triggers(vec![
+                Trigger::new(
+                    "evening".to_string(),
+                    TriggerKind::Schedule(Schedule::Cron {
+                        expression: "0 0 20 * * *".to_string(),
+                        jitter: Duration::ZERO,
+                    }),
+                ),
+                expression_trigger("morning", morning, TriggerMode::Level),
+            ])
+            .with_condition(condition);
+
+        Arc::new(FlowRegistry::new(vec![flow]))
+    }
+
+    async fn executed_properties(registry: &Arc<FlowRegistry>, hour: u32, trigger_id: Option<&str>) -> Vec<String> {
+        let flow = registry.by_id("id").unwrap();
+        let mut builder = context_with_device("device")
+            .flow_registry(registry.clone())
+            .now(Local.with_ymd_and_hms(2025, 10, 17, hour, 0, 0).unwrap());
+        if let Some(trigger_id) = trigger_id {
+            builder = builder.trigger_id(trigger_id.to_string());
+        }
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+
+        let result = execute(&flow, None, &builder.build(), scheduler_tx).await.unwrap();
+        result
+            .and_then(|result| result.take_from_scope::<CommandMap>("command_map"))
+            .map(|command_map| command_map["device"].keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    #[test(tokio::test)]
+    async fn executes_a_flow_for_the_trigger_that_fired() {
+        let registry = registry_with_evening_or_morning_flow(Literal { value: Value::Boolean(true) });
+
+        assert_eq!(executed_properties(&registry, 20, Some("evening")).await, vec!["evening"]);
+        assert_eq!(executed_properties(&registry, 10, None).await, vec!["other"]);
+        assert!(executed_properties(&registry, 20, None).await.is_empty(), "Expected schedules to not fire on store changes");
+    }
+
+    #[test(tokio::test)]
+    async fn skips_execution_if_the_condition_of_the_flow_is_false() {
+        let registry = registry_with_evening_or_morning_flow(Literal { value: Value::Boolean(false) });
+
+        assert!(executed_properties(&registry, 20, Some("evening")).await.is_empty());
+        assert!(executed_properties(&registry, 10, None).await.is_empty());
+    }
+
+    #[test(tokio::test)]
+    async fn executes_all_branches_of_a_fork_node_before_continuing_after_the_join_node() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let after_join_node = control_device_node("afterJoinNode", "after_join", end_node);
+        let join_node = Arc::new(FlowNode::new(
+            "joinNode".to_string(),
+            vec![FlowLink::new(after_join_node, Value::None)],
+            FlowNodeKind::Join(2),
+        ));
+
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(control_device_node("slowNode", "slow", join_node.clone()), Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_millis(10), false)),
+        ));
+        let flow = fork_join_flow(vec![control_device_node("fastNode", "fast", join_node), sleep_node]);
+
+        let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(&flow, None, &context_with_device("device").build(), scheduler_tx).await.unwrap().unwrap();
+
+        assert!(scheduler_rx.try_recv().is_err(), "Expected sleep nodes in branches to not be scheduled");
+        let command_map = result.take_from_scope::<CommandMap>("command_map").unwrap();
+        let mut properties = command_map["device"].keys().cloned().collect::<Vec<_>>();
+        properties.sort();
+        assert_eq!(properties, vec!["after_join", "fast", "slow"]);
+    }
+
+    #[test(tokio::test)]
+    async fn continues_after_the_join_node_once_the_required_number_of_branches_arrived() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let after_join_node = control_device_node("afterJoinNode", "after_join", end_node);
+        let join_node = Arc::new(FlowNode::new(
+            "joinNode".to_string(),
+            vec![FlowLink::new(after_join_node, Value::None)],
+            FlowNodeKind::Join(1),
+        ));
+
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(control_device_node("slowNode", "slow", join_node.clone()), Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(3600), false)),
+        ));
+        let flow = fork_join_flow(vec![control_device_node("fastNode", "fast", join_node), sleep_node]);
+
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(&flow, None, &context_with_device("device").build(), scheduler_tx).await.unwrap().unwrap();
+
+        let command_map = result.take_from_scope::<CommandMap>("command_map").unwrap();
+        let mut properties = command_map["device"].keys().cloned().collect::<Vec<_>>();
+        properties.sort();
+        assert_eq!(properties, vec!["after_join", "fast"]);
+    }
+
+    #[test(tokio::test)]
+    async fn dispatches_the_commands_before_and_beside_a_sleeping_branch_without_waiting_for_it() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let join_node = Arc::new(FlowNode::new("joinNode".to_string(), vec![FlowLink::new(end_node, Value::None)], FlowNodeKind::Join(2)));
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(control_device_node("slowNode", "slow", join_node.clone()), Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(3600), false)),
+        ));
+        let flow = fork_join_flow(vec![
+            control_device_node("beforeSleepNode", "before_sleep", sleep_node),
+            control_device_node("fastNode", "fast", join_node),
+        ]);
+
+        let (commands_tx, mut commands_rx) = mpsc::unbounded_channel();
+        let context = context_with_device("device").commands(commands_tx).build();
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let run = tokio::spawn(async move { execute(&flow, None, &context, scheduler_tx).await });
+
+        let mut properties = Vec::new();
+        while properties.len() < 2 {
+            let command_map = tokio::time::timeout(Duration::from_secs(1), commands_rx.recv()).await.unwrap().unwrap();
+            properties.extend(command_map["device"].keys().cloned());
+        }
+        properties.sort();
+        assert_eq!(properties, vec!["before_sleep", "fast"]);
+        assert!(!run.is_finished(), "Expected the branch to still be sleeping");
+        run.abort();
+    }
+
+    #[test(tokio::test)]
+    async fn continues_a_sleeping_branch_with_the_current_state_of_the_store() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let join_node = Arc::new(FlowNode::new("joinNode".to_string(), vec![FlowLink::new(end_node, Value::None)], FlowNodeKind::Join(1)));
+        let conditional_node = Arc::new(FlowNode::new(
+            "conditionalNode".to_string(),
+            vec![
+                FlowLink::new(control_device_node("switchedOnNode", "switched_on", join_node.clone()), Value::Boolean(true)),
+                FlowLink::new(join_node, Value::Boolean(false)),
+            ],
+            FlowNodeKind::Conditional(EqualTo {
+                lhs: Box::new(PropertyValue {
+                    device_id: "device".to_string(),
+                    property_id: "on".to_string(),
+                }),
+                rhs: Box::new(Literal { value: Value::Boolean(true) }),
+            }),
+        ));
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(conditional_node, Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_millis(50), false)),
+        ));
+        let flow = fork_join_flow(vec![sleep_node]);
+
+        let (notifier_tx, notifier_rx) = watch::channel(lamp_snapshot(false));
+        let context = Context::builder().snapshot(lamp_snapshot(false)).notifier(notifier_rx).build();
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let run = tokio::spawn(async move { execute(&flow, None, &context, scheduler_tx).await });
+        notifier_tx.send(lamp_snapshot(true)).unwrap();
+
+        let result = run.await.unwrap().unwrap().unwrap();
+        let command_map = result.take_from_scope::<CommandMap>("command_map").unwrap();
+        assert!(command_map["device"].contains_key("switched_on"), "Expected the branch to see the lamp switched on");
+    }
+
+    #[test(tokio::test)]
+    async fn executes_a_called_flow_with_parameters_and_merges_its_commands() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let conditional_node = Arc::new(FlowNode::new(
+            "conditionalNode".to_string(),
+            vec![
+                FlowLink::new(control_device_node("subNode", "sub", end_node.clone()), Value::Boolean(true)),
+                FlowLink::new(end_node.clone(), Value::Boolean(false)),
+            ],
+            FlowNodeKind::Conditional(EqualTo {
+                lhs: Box::new(Parameter { name: "level".to_string() }),
+                rhs: Box::new(Literal {
+                    value: Value::Number(Number::PositiveInt(30)),
+                }),
+            }),
+        ));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(conditional_node, Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let sub_flow = Flow::new("subFlowId".to_string(), "subFlow".to_string(), start_node, HashMap::new()).unwrap();
+
+        let parameters = HashMap::from([(
+            "level".to_string(),
+            Literal {
+                value: Value::Number(Number::PositiveInt(30)),
+            },
+        )]);
+        let call_flow_node = Arc::new(FlowNode::new(
+            "callFlowNode".to_string(),
+            vec![FlowLink::new(control_device_node("mainNode", "main", end_node), Value::None)],
+            FlowNodeKind::CallFlow(CallFlowNode::new("subFlowId".to_string(), parameters)),
+        ));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(call_flow_node, Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new()).unwrap();
+
+        let context = context_with_device("device").flow_registry(Arc::new(FlowRegistry::new(vec![sub_flow]))).build();
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(&flow, None, &context, scheduler_tx).await.unwrap().unwrap();
+
+        let command_map = result.take_from_scope::<CommandMap>("command_map").unwrap();
+        let mut properties = command_map["device"].keys().cloned().collect::<Vec<_>>();
+        properties.sort();
+        assert_eq!(properties, vec!["main", "sub"]);
+    }
+
+    #[test(tokio::test)]
+    async fn fails_if_a_called_flow_is_unknown() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let call_flow_node = Arc::new(FlowNode::new(
+            "callFlowNode".to_string(),
+            vec![FlowLink::new(end_node, Value::None)],
+            FlowNodeKind::CallFlow(CallFlowNode::new("unknown".to_string(), HashMap::new())),
+        ));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(call_flow_node, Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new()).unwrap();
+
+        let context = Context::builder().flow_registry(Arc::new(FlowRegistry::new(vec![]))).build();
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = execute(&flow, None, &context, scheduler_tx).await;
+
+        assert!(matches!(result, Err(FlowEngineError::UnknownSubFlow { .. })));
+    }
+
+    #[test(tokio::test)]
+    async fn fails_if_a_called_flow_sleeps() {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(end_node.clone(), Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(3600), false)),
+        ));
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![FlowLink::new(sleep_node, Value::None)], FlowNodeKind::Start));
+        let sub_flow = Flow::new("subFlowId".to_string(), "subFlow".to_string(), start_node, HashMap::new()).unwrap();
+
+        let call_flow_node = Arc::new(FlowNode::new(
+            "callFlowNode".to_string(),
+            vec![FlowLink::new(end_node, Value::None)],
+            FlowNodeKind::CallFlow(CallFlowNode::new("subFlowId".to_string(), HashMap::new())),
+        ));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(call_flow_node, Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new()).unwrap();
+
+        let context = Context::builder().flow_registry(Arc::new(FlowRegistry::new(vec![sub_flow]))).build();
+        let (scheduler_tx, _scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
+        let result = tokio::time::timeout(Duration::from_secs(1), execute(&flow, None, &context, scheduler_tx)).await.unwrap();
+
+        assert!(matches!(result, Err(FlowEngineError::SleepInCalledFlow(node_id)) if node_id == "sleepNode"));
+    }
+
     #[test(tokio::test)]
     async fn executes_a_conditional_node() {
         let end_node_true = Arc::new(FlowNode::new("end_node_true".to_string(), vec![], FlowNodeKind::End));
@@ -327,10 +1304,10 @@ mod tests {
             (end_node_true.id().to_string(), end_node_true.clone()),
             (end_node_false.id().to_string(), end_node_false.clone()),
         ]);
-        let flow = Flow::new("id".to_string(), "flow".to_string(), None, None, start_node, nodes_by_id).unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, nodes_by_id).unwrap();
 
         let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
-        let result = execute(&flow, None, &Context::default(), scheduler_tx).await.unwrap();
+        let result = execute(&flow, None, &Context::default(), scheduler_tx).await.unwrap().unwrap();
 
         assert!(scheduler_rx.try_recv().is_err(), "Expected no scheduler commands to be sent");
         assert!(result.scope.is_empty());
@@ -364,7 +1341,7 @@ mod tests {
             (end_node_true.id().to_string(), end_node_true.clone()),
             (end_node_false.id().to_string(), end_node_false.clone()),
         ]);
-        let flow = Flow::new("id".to_string(), "flow".to_string(), None, None, start_node, nodes_by_id).unwrap();
+        let flow = Flow::new("id".to_string(), "flow".to_string(), start_node, nodes_by_id).unwrap();
 
         let (scheduler_tx, mut scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
         let result = execute(&flow, None, &Context::default(), scheduler_tx).await;
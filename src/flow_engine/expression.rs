@@ -1,97 +1,514 @@
-use crate::domain::property::{BooleanProperty, NumberProperty, PropertyType};
-use crate::domain::{Number, Time, WeekdayCondition};
+use crate::domain::color::{Color, ColorConversionError};
+use crate::domain::device::{Device, DeviceType};
+use crate::domain::property::{BooleanProperty, ColorProperty, NumberProperty, Property, PropertyType};
+use crate::domain::{Date, Number, NumberError, Time, WeekdayCondition};
 use crate::extensions::date_time_ext::ToWeekday;
-use crate::flow_engine::Context;
 use crate::flow_engine::expression::ExpressionError::UnknownProperty;
-use chrono::NaiveTime;
+use crate::flow_engine::property_value::FromValue;
+use crate::flow_engine::{Context, SolarEvent};
+use crate::store::PropertyChange;
+use chrono::{Datelike, NaiveDate, TimeDelta};
+use regex::Regex;
 use serde::Deserialize;
 use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
 use thiserror::Error;
 use tracing::warn;
 
+/// Deserialized from an object by the derived implementation, the flow loader also accepts the textual syntax.
 #[derive(PartialEq, Deserialize, Debug)]
-#[serde(tag = "type", rename_all = "camelCase")]
+#[serde(remote = "Self", tag = "type", rename_all = "camelCase", rename_all_fields = "camelCase")]
 pub enum Expression {
     // Comparison
-    GreaterThanOrEqualTo { lhs: Box<Expression>, rhs: Box<Expression> },
-    GreaterThan { lhs: Box<Expression>, rhs: Box<Expression> },
-    LessThan { lhs: Box<Expression>, rhs: Box<Expression> },
-    LessThanOrEqualTo { lhs: Box<Expression>, rhs: Box<Expression> },
+    GreaterThanOrEqualTo {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    GreaterThan {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    LessThan {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    LessThanOrEqualTo {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
 
     // Equality
-    EqualTo { lhs: Box<Expression>, rhs: Box<Expression> },
-    NotEqualTo { lhs: Box<Expression>, rhs: Box<Expression> },
+    EqualTo {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    NotEqualTo {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
 
     // Logic
-    And { lhs: Box<Expression>, rhs: Box<Expression> },
-    Or { lhs: Box<Expression>, rhs: Box<Expression> },
-    Not { expression: Box<Expression> },
+    And {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Or {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Not {
+        expression: Box<Expression>,
+    },
+
+    // Arithmetic
+    Add {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Subtract {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Multiply {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Divide {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Modulo {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Min {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Max {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Clamp {
+        expression: Box<Expression>,
+        min: Box<Expression>,
+        max: Box<Expression>,
+    },
+    Round {
+        expression: Box<Expression>,
+    },
+    Abs {
+        expression: Box<Expression>,
+    },
+
+    // String
+    Contains {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    StartsWith {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Matches {
+        expression: Box<Expression>,
+        pattern: Pattern,
+    },
+
+    // Color
+    ColorDistance {
+        lhs: Box<Expression>,
+        rhs: Box<Expression>,
+    },
+    Hue {
+        expression: Box<Expression>,
+    },
+    Saturation {
+        expression: Box<Expression>,
+    },
 
     // Literal
-    Literal { value: Value },
+    Literal {
+        value: Value,
+    },
 
     // Property
-    PropertyValue { device_id: String, property_id: String },
+    PropertyValue {
+        device_id: String,
+        property_id: String,
+    },
+    PropertyChanged(PropertyChangedExpression),
+    UnchangedFor(PropertyDurationExpression),
+    ChangedWithin(PropertyDurationExpression),
+    ValueAgo(PropertyDurationExpression),
+    Aggregate(AggregateExpression),
+
+    // Parameter passed by a calling flow
+    Parameter {
+        name: String,
+    },
+
+    // Variable set by a previous node of the running flow
+    Variable {
+        name: String,
+    },
+
+    // Id of the trigger that started the running flow
+    TriggeredBy,
 
     // Temporal
-    Temporal { expression: TemporalExpression },
+    Temporal {
+        expression: TemporalExpression,
+    },
 }
 
 #[derive(Eq, PartialEq, Hash, Debug, Clone)]
 pub enum Value {
     Boolean(bool),
     Number(Number),
+    String(String),
+    Color(Color),
     None,
 }
 
+/// A regular expression of a `matches` expression, it is compiled when the flow is loaded.
+#[derive(Clone, Debug)]
+pub struct Pattern(pub Regex);
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
 #[derive(PartialEq, Deserialize, Debug)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum TemporalExpression {
     IsToday { when: WeekdayCondition },
     IsBeforeTime { time: Time },
     IsAfterTime { time: Time },
-    HasSunRisen, // Now >= sunrise
-    HasSunSet,   // Now >= sunset
-    IsDaytime,   // Now between sunrise and sunset
-    IsNighttime, // Now < sunrise or now > sunset
+    IsBetweenTimes { from: Time, to: Time }, // From inclusive, to exclusive, wraps around midnight if to is before from
+    IsBetweenDates { from: Date, to: Date }, // Both inclusive, recurring if either date has no year
+    IsDate { date: Date },
+    IsCalendarDay { calendar: String },      // Today is one of the days of the named calendar
+    HasSunRisen,                             // Now >= sunrise
+    HasSunSet,                               // Now >= sunset
+    IsDaytime,                               // Now between sunrise and sunset
+    IsNighttime,                             // Now < sunrise or now > sunset
+    IsAfterSolarEvent { event: SolarEvent }, // Now >= the event today, false if the sun does not reach its elevation today
+    IsGoldenHour,                            // The sun is low above the horizon, in the morning or the evening
+    SunElevation,                            // The elevation of the sun in degrees, negative below the horizon
+}
+
+/// Is true when the store change that is being handled changed the property, optionally from and to a given value.
+#[derive(PartialEq, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertyChangedExpression {
+    pub device_id: String,
+    pub property_id: String,
+    pub from: Option<Value>,
+    pub to: Option<Value>,
+}
+
+impl PropertyChangedExpression {
+    fn matches(&self, change: &PropertyChange) -> bool {
+        change.device_id == self.device_id
+            && change.property_id == self.property_id
+            && self.from.as_ref().is_none_or(|from| *from == change.previous_value)
+            && self.to.as_ref().is_none_or(|to| *to == change.value)
+    }
+}
+
+/// Compares the time since the property last changed to a duration: `unchangedFor` is true if the property did not
+/// change for at least the duration, `changedWithin` if it did change within the duration. As the passing of time alone
+/// changes their outcome, a trigger with them is evaluated again once the duration passed after the property changed.
+/// `valueAgo` is the value the property
+/// had the duration before now according to the property history, or `none` if it had no value yet.
+#[derive(PartialEq, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PropertyDurationExpression {
+    pub device_id: String,
+    pub property_id: String,
+    #[serde(with = "humantime_serde")]
+    pub duration: Duration,
+}
+
+impl PropertyDurationExpression {
+    fn time_since_last_change(&self, context: &Context) -> Result<Duration, ExpressionError> {
+        let last_changed = context.snapshot().last_changed(&self.device_id, &self.property_id).ok_or_else(|| UnknownProperty {
+            device_id: self.device_id.clone(),
+            property_id: self.property_id.clone(),
+        })?;
+        Ok((context.now() - last_changed).to_std().unwrap_or(Duration::ZERO)) // A change after now happened just now
+    }
+
+    fn value_ago(&self, context: &Context) -> Result<Value, ExpressionError> {
+        let history = context.history().ok_or(ExpressionError::HistoryUnavailable)?;
+        let Some(at) = TimeDelta::from_std(self.duration).ok().and_then(|duration| context.now().checked_sub_signed(duration)) else {
+            return Ok(Value::None);
+        };
+        Ok(history.query(&self.device_id, &self.property_id, at, at).pop().map_or(Value::None, |entry| entry.value))
+    }
+}
+
+/// Aggregates a property over a group of devices: `any` and `all` are true if the property is true for any or all of the
+/// devices, `count` is the number of devices for which it is true, `average`, `min` and `max` aggregate a number
+/// property. Devices without the property are left out, as are devices without a value for `average`, `min` and `max`.
+#[derive(PartialEq, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregateExpression {
+    pub function: AggregateFunction,
+    pub devices: DeviceSelector,
+    pub property_id: String,
+}
+
+#[derive(Copy, Clone, PartialEq, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum AggregateFunction {
+    Any,
+    All,
+    Count,
+    Average,
+    Min,
+    Max,
+}
+
+/// Selects the devices that match all its criteria, a selector without criteria selects all devices.
+#[derive(PartialEq, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceSelector {
+    pub ids: Option<Vec<String>>,
+    pub device_type: Option<DeviceType>,
+    pub room: Option<String>,
+    pub tag: Option<String>,
+}
+
+impl DeviceSelector {
+    fn matches(&self, device: &Device) -> bool {
+        self.ids.as_ref().is_none_or(|ids| ids.contains(&device.id))
+            && self.device_type.as_ref().is_none_or(|device_type| *device_type == device.r#type)
+            && self.room.as_ref().is_none_or(|room| device.room.as_ref() == Some(room))
+            && self.tag.as_ref().is_none_or(|tag| device.tags.contains(tag))
+    }
+}
+
+impl AggregateExpression {
+    fn evaluate(&self, context: &Context) -> Result<Value, ExpressionError> {
+        let values = context
+            .snapshot()
+            .devices
+            .values()
+            .filter(|device| self.devices.matches(device))
+            .filter_map(|device| device.properties.get(&self.property_id))
+            .map(|property| Value::try_from(property.as_ref()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match self.function {
+            AggregateFunction::Any | AggregateFunction::All | AggregateFunction::Count => {
+                let booleans = values.iter().map(aggregated::<bool>).collect::<Result<Vec<_>, _>>()?;
+                Ok(match self.function {
+                    AggregateFunction::Any => Value::Boolean(booleans.iter().any(|value| *value)),
+                    AggregateFunction::All => Value::Boolean(booleans.iter().all(|value| *value)),
+                    _ => Value::Number(Number::PositiveInt(booleans.iter().filter(|value| **value).count() as u64)),
+                })
+            }
+            AggregateFunction::Average | AggregateFunction::Min | AggregateFunction::Max => {
+                let numbers = values
+                    .iter()
+                    .filter(|value| **value != Value::None)
+                    .map(aggregated::<Number>)
+                    .collect::<Result<Vec<_>, _>>()?;
+                let result = match self.function {
+                    AggregateFunction::Average if !numbers.is_empty() => {
+                        let sum: f64 = numbers.iter().filter_map(Number::as_f64).sum();
+                        Some(Number::Float(sum / numbers.len() as f64))
+                    }
+                    AggregateFunction::Min => numbers.into_iter().reduce(|a, b| if b < a { b } else { a }),
+                    AggregateFunction::Max => numbers.into_iter().reduce(|a, b| if b > a { b } else { a }),
+                    _ => None,
+                };
+                Ok(result.map(Value::Number).unwrap_or(Value::None)) // None if no device has a value
+            }
+        }
+    }
+}
+
+fn aggregated<T: FromValue>(value: &Value) -> Result<T, ExpressionError> {
+    T::from_value(value).ok_or_else(|| ExpressionError::ValueTypeMismatch {
+        expected: T::TYPE_NAME,
+        actual: format!("{:?}", value),
+    })
+}
+
+/// Variables of a flow run, set by `setVariable` actions.
+pub type Variables = HashMap<String, Value>;
+
+impl Expression {
+    /// Returns the device and property ids of all properties this expression reads.
+    pub fn property_dependencies(&self) -> HashSet<(String, String)> {
+        let mut dependencies = HashSet::new();
+        self.visit(&mut |expression| match expression {
+            Expression::PropertyValue { device_id, property_id }
+            | Expression::PropertyChanged(PropertyChangedExpression { device_id, property_id, .. })
+            | Expression::UnchangedFor(PropertyDurationExpression { device_id, property_id, .. })
+            | Expression::ChangedWithin(PropertyDurationExpression { device_id, property_id, .. })
+            | Expression::ValueAgo(PropertyDurationExpression { device_id, property_id, .. }) => {
+                dependencies.insert((device_id.clone(), property_id.clone()));
+            }
+            _ => {}
+        });
+        dependencies
+    }
+
+    /// Returns the device and property ids of the properties for which the time since their last change is read, with the
+    /// duration after a change at which the outcome of this expression may change.
+    pub fn timed_dependencies(&self) -> HashSet<(String, String, Duration)> {
+        let mut dependencies = HashSet::new();
+        self.visit(&mut |expression| match expression {
+            Expression::UnchangedFor(PropertyDurationExpression { device_id, property_id, duration })
+            | Expression::ChangedWithin(PropertyDurationExpression { device_id, property_id, duration }) => {
+                dependencies.insert((device_id.clone(), property_id.clone(), *duration));
+            }
+            _ => {}
+        });
+        dependencies
+    }
+
+    /// Returns whether this expression reads properties of a group of devices, these are not part of its property
+    /// dependencies as the devices in the group are only known when it is evaluated.
+    pub fn depends_on_device_groups(&self) -> bool {
+        let mut depends_on_device_groups = false;
+        self.visit(&mut |expression| depends_on_device_groups |= matches!(expression, Expression::Aggregate(_)));
+        depends_on_device_groups
+    }
+
+    /// Returns whether this expression reacts to property changes rather than to the state of the store.
+    pub fn depends_on_changes(&self) -> bool {
+        let mut depends_on_changes = false;
+        self.visit(&mut |expression| depends_on_changes |= matches!(expression, Expression::PropertyChanged(_)));
+        depends_on_changes
+    }
+
+    fn visit(&self, visitor: &mut impl FnMut(&Expression)) {
+        use Expression::*;
+
+        visitor(self);
+        match self {
+            GreaterThanOrEqualTo { lhs, rhs }
+            | GreaterThan { lhs, rhs }
+            | LessThan { lhs, rhs }
+            | LessThanOrEqualTo { lhs, rhs }
+            | EqualTo { lhs, rhs }
+            | NotEqualTo { lhs, rhs }
+            | And { lhs, rhs }
+            | Or { lhs, rhs }
+            | Add { lhs, rhs }
+            | Subtract { lhs, rhs }
+            | Multiply { lhs, rhs }
+            | Divide { lhs, rhs }
+            | Modulo { lhs, rhs }
+            | Min { lhs, rhs }
+            | Max { lhs, rhs }
+            | Contains { lhs, rhs }
+            | StartsWith { lhs, rhs }
+            | ColorDistance { lhs, rhs } => {
+                lhs.visit(visitor);
+                rhs.visit(visitor);
+            }
+            Not { expression } | Round { expression } | Abs { expression } | Matches { expression, .. } | Hue { expression } | Saturation { expression } => {
+                expression.visit(visitor)
+            }
+            Clamp { expression, min, max } => {
+                expression.visit(visitor);
+                min.visit(visitor);
+                max.visit(visitor);
+            }
+            Literal { .. }
+            | PropertyValue { .. }
+            | PropertyChanged(_)
+            | UnchangedFor(_)
+            | ChangedWithin(_)
+            | ValueAgo(_)
+            | Aggregate(_)
+            | Parameter { .. }
+            | Variable { .. }
+            | TriggeredBy
+            | Temporal { .. } => {}
+        }
+    }
+}
+
+impl TryFrom<&dyn Property> for Value {
+    type Error = ExpressionError;
+
+    fn try_from(property: &dyn Property) -> Result<Self, Self::Error> {
+        match property.property_type() {
+            PropertyType::Brightness => {
+                let number_property = property.as_any().downcast_ref::<NumberProperty>().unwrap();
+                Ok(number_property.value().map(Value::Number).unwrap_or(Value::None))
+            }
+            PropertyType::Color => {
+                let color_property = property.as_any().downcast_ref::<ColorProperty>().unwrap();
+                Ok(Value::Color(Color::CIE_xyY {
+                    xy: color_property.xy().clone(),
+                    brightness: 1.0, // The brightness is a property of its own
+                }))
+            }
+            PropertyType::ColorTemperature => {
+                let number_property = property.as_any().downcast_ref::<NumberProperty>().unwrap();
+                Ok(number_property.value().map(Value::Number).unwrap_or(Value::None))
+            }
+            PropertyType::On => {
+                let value = property.as_any().downcast_ref::<BooleanProperty>().unwrap();
+                Ok(Value::Boolean(value.value()))
+            }
+        }
+    }
 }
 
 pub fn evaluate(expression: &Expression, context: &Context) -> Result<Value, ExpressionError> {
+    evaluate_with_variables(expression, context, None)
+}
+
+pub fn evaluate_with_variables(expression: &Expression, context: &Context, variables: Option<&Variables>) -> Result<Value, ExpressionError> {
     use Expression::*;
 
     match expression {
         // Comparison
-        GreaterThanOrEqualTo { lhs, rhs } => compare(lhs, rhs, |o| o != Ordering::Less, context),
-        GreaterThan { lhs, rhs } => compare(lhs, rhs, |o| o == Ordering::Greater, context),
-        LessThan { lhs, rhs } => compare(lhs, rhs, |o| o == Ordering::Less, context),
-        LessThanOrEqualTo { lhs, rhs } => compare(lhs, rhs, |o| o != Ordering::Greater, context),
+        GreaterThanOrEqualTo { lhs, rhs } => compare(lhs, rhs, |o| o != Ordering::Less, context, variables),
+        GreaterThan { lhs, rhs } => compare(lhs, rhs, |o| o == Ordering::Greater, context, variables),
+        LessThan { lhs, rhs } => compare(lhs, rhs, |o| o == Ordering::Less, context, variables),
+        LessThanOrEqualTo { lhs, rhs } => compare(lhs, rhs, |o| o != Ordering::Greater, context, variables),
 
         // Equality
-        EqualTo { lhs, rhs } => match (evaluate(lhs, context)?, evaluate(rhs, context)?) {
+        EqualTo { lhs, rhs } => match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(a.eq(&b))),
             (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a == b)),
+            (Value::String(a), Value::String(b)) => Ok(Value::Boolean(a == b)),
             (Value::None, Value::None) => Ok(Value::Boolean(true)),
             _ => Err(ExpressionError::OperandTypeMismatch {
                 operand: "EqualTo",
-                expected: "Boolean|Number",
+                expected: "Boolean|Number|String",
                 actual_lhs: format!("{:?}", lhs),
                 actual_rhs: format!("{:?}", rhs),
             }),
         },
-        NotEqualTo { lhs, rhs } => match (evaluate(lhs, context)?, evaluate(rhs, context)?) {
+        NotEqualTo { lhs, rhs } => match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
             (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(!a.eq(&b))),
             (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a != b)),
+            (Value::String(a), Value::String(b)) => Ok(Value::Boolean(a != b)),
             (Value::None, Value::None) => Ok(Value::Boolean(false)),
             _ => Err(ExpressionError::OperandTypeMismatch {
                 operand: "NotEqualTo",
-                expected: "Boolean|Number",
+                expected: "Boolean|Number|String",
                 actual_lhs: format!("{:?}", lhs),
                 actual_rhs: format!("{:?}", rhs),
             }),
         },
 
         // Logic
-        And { lhs, rhs } => match (evaluate(lhs, context)?, evaluate(rhs, context)?) {
+        And { lhs, rhs } => match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
             (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a && b)),
             _ => Err(ExpressionError::OperandTypeMismatch {
                 operand: "And",
@@ -100,7 +517,7 @@ pub fn evaluate(expression: &Expression, context: &Context) -> Result<Value, Exp
                 actual_rhs: format!("{:?}", rhs),
             }),
         },
-        Or { lhs, rhs } => match (evaluate(lhs, context)?, evaluate(rhs, context)?) {
+        Or { lhs, rhs } => match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
             (Value::Boolean(a), Value::Boolean(b)) => Ok(Value::Boolean(a || b)),
             _ => Err(ExpressionError::OperandTypeMismatch {
                 operand: "Or",
@@ -109,7 +526,7 @@ pub fn evaluate(expression: &Expression, context: &Context) -> Result<Value, Exp
                 actual_rhs: format!("{:?}", rhs),
             }),
         },
-        Not { expression } => match evaluate(expression, context)? {
+        Not { expression } => match evaluate_with_variables(expression, context, variables)? {
             Value::Boolean(b) => Ok(Value::Boolean(!b)),
             _ => Err(ExpressionError::UnaryOperandTypeMismatch {
                 operand: "Not",
@@ -118,6 +535,64 @@ pub fn evaluate(expression: &Expression, context: &Context) -> Result<Value, Exp
             }),
         },
 
+        // Arithmetic
+        Add { lhs, rhs } => arithmetic(lhs, rhs, "Add", |a, b| Ok(a + b), context, variables),
+        Subtract { lhs, rhs } => arithmetic(lhs, rhs, "Subtract", |a, b| Ok(a - b), context, variables),
+        Multiply { lhs, rhs } => arithmetic(lhs, rhs, "Multiply", |a, b| Ok(a * b), context, variables),
+        Divide { lhs, rhs } => arithmetic(lhs, rhs, "Divide", |a, b| a / b, context, variables),
+        Modulo { lhs, rhs } => arithmetic(lhs, rhs, "Modulo", |a, b| a % b, context, variables),
+        Min { lhs, rhs } => arithmetic(lhs, rhs, "Min", |a, b| Ok(if b < a { b } else { a }), context, variables),
+        Max { lhs, rhs } => arithmetic(lhs, rhs, "Max", |a, b| Ok(if b > a { b } else { a }), context, variables),
+        Clamp { expression, min, max } => match (
+            evaluate_with_variables(expression, context, variables)?,
+            evaluate_with_variables(min, context, variables)?,
+            evaluate_with_variables(max, context, variables)?,
+        ) {
+            (Value::Number(_), Value::Number(min_value), Value::Number(max_value)) if min_value > max_value => Err(ExpressionError::InvalidClampRange {
+                min: format!("{:?}", min),
+                max: format!("{:?}", max),
+            }),
+            (Value::Number(value), Value::Number(min_value), Value::Number(max_value)) => Ok(Value::Number(if value < min_value {
+                min_value
+            } else if value > max_value {
+                max_value
+            } else {
+                value
+            })),
+            _ => Err(ExpressionError::UnaryOperandTypeMismatch {
+                operand: "Clamp",
+                expected: "Number",
+                actual: format!("{:?}", (expression, min, max)),
+            }),
+        },
+        Round { expression } => unary_arithmetic(expression, "Round", Number::round, context, variables),
+        Abs { expression } => unary_arithmetic(expression, "Abs", Number::abs, context, variables),
+
+        // String
+        Contains { lhs, rhs } => string_predicate(lhs, rhs, "Contains", |a, b| a.contains(b), context, variables),
+        StartsWith { lhs, rhs } => string_predicate(lhs, rhs, "StartsWith", |a, b| a.starts_with(b), context, variables),
+        Matches { expression, pattern } => match evaluate_with_variables(expression, context, variables)? {
+            Value::String(value) => Ok(Value::Boolean(pattern.0.is_match(&value))),
+            _ => Err(ExpressionError::UnaryOperandTypeMismatch {
+                operand: "Matches",
+                expected: "String",
+                actual: format!("{:?}", expression),
+            }),
+        },
+
+        // Color
+        ColorDistance { lhs, rhs } => match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
+            (Value::Color(a), Value::Color(b)) => a.distance(&b).map(|distance| Value::Number(Number::Float(distance))).map_err(invalid_color),
+            _ => Err(ExpressionError::OperandTypeMismatch {
+                operand: "ColorDistance",
+                expected: "Color",
+                actual_lhs: format!("{:?}", lhs),
+                actual_rhs: format!("{:?}", rhs),
+            }),
+        },
+        Hue { expression } => color_component(expression, "Hue", |(hue, _)| hue, context, variables),
+        Saturation { expression } => color_component(expression, "Saturation", |(_, saturation)| saturation * 100.0, context, variables),
+
         // Literal
         Literal { value } => Ok(value.clone()),
 
@@ -136,19 +611,23 @@ pub fn evaluate(expression: &Expression, context: &Context) -> Result<Value, Exp
                 });
             };
 
-            match property.property_type() {
-                PropertyType::Brightness => {
-                    let number_property = property.as_any().downcast_ref::<NumberProperty>().unwrap();
-                    Ok(number_property.value().map(Value::Number).unwrap_or(Value::None))
-                }
-                PropertyType::Color => Err(ExpressionError::UnsupportedPropertyType(property.property_type())),
-                PropertyType::ColorTemperature => Err(ExpressionError::UnsupportedPropertyType(property.property_type())),
-                PropertyType::On => {
-                    let value = property.as_any().downcast_ref::<BooleanProperty>().unwrap();
-                    Ok(Value::Boolean(value.value()))
-                }
-            }
+            Value::try_from(property.as_ref())
         }
+        PropertyChanged(expression) => Ok(Value::Boolean(context.snapshot().changes.iter().any(|change| expression.matches(change)))),
+        UnchangedFor(expression) => Ok(Value::Boolean(expression.time_since_last_change(context)? >= expression.duration)),
+        ChangedWithin(expression) => Ok(Value::Boolean(expression.time_since_last_change(context)? <= expression.duration)),
+        ValueAgo(expression) => expression.value_ago(context),
+        Aggregate(expression) => expression.evaluate(context),
+
+        // Parameter
+        Parameter { name } => context.parameter(name).cloned().ok_or_else(|| ExpressionError::UnknownParameter(name.clone())),
+
+        // Variable
+        Variable { name } => variables
+            .and_then(|variables| variables.get(name))
+            .cloned()
+            .ok_or_else(|| ExpressionError::UnknownVariable(name.clone())),
+        TriggeredBy => Ok(context.trigger_id().map_or(Value::None, |id| Value::String(id.to_string()))),
 
         // Temporal
         Temporal { expression } => {
@@ -160,16 +639,22 @@ pub fn evaluate(expression: &Expression, context: &Context) -> Result<Value, Exp
                     let matches = included_days.contains(&now.to_weekday());
                     Ok(Value::Boolean(matches))
                 }
-                TemporalExpression::IsBeforeTime { time } => Ok(Value::Boolean(
-                    NaiveTime::from_hms_opt(time.hour as u32, time.minute as u32, 0)
-                        .map(|target| now.time() < target)
-                        .unwrap_or(false),
-                )),
-                TemporalExpression::IsAfterTime { time } => Ok(Value::Boolean(
-                    NaiveTime::from_hms_opt(time.hour as u32, time.minute as u32, 0)
-                        .map(|target| now.time() > target)
-                        .unwrap_or(false),
-                )),
+                TemporalExpression::IsBeforeTime { time } => Ok(Value::Boolean(time.to_naive_time().map(|target| now.time() < target).unwrap_or(false))),
+                TemporalExpression::IsAfterTime { time } => Ok(Value::Boolean(time.to_naive_time().map(|target| now.time() > target).unwrap_or(false))),
+                TemporalExpression::IsBetweenTimes { from, to } => {
+                    let is_between = match (from.to_naive_time(), to.to_naive_time()) {
+                        (Some(from), Some(to)) if from <= to => now.time() >= from && now.time() < to,
+                        (Some(from), Some(to)) => now.time() >= from || now.time() < to,
+                        _ => false,
+                    };
+                    Ok(Value::Boolean(is_between))
+                }
+                TemporalExpression::IsBetweenDates { from, to } => Ok(Value::Boolean(is_between_dates(from, to, now.date_naive()))),
+                TemporalExpression::IsDate { date } => Ok(Value::Boolean(date.falls_on(now.date_naive()))),
+                TemporalExpression::IsCalendarDay { calendar } => {
+                    let calendar = context.calendar(calendar).ok_or_else(|| ExpressionError::UnknownCalendar(calendar.clone()))?;
+                    Ok(Value::Boolean(calendar.includes(now.date_naive())))
+                }
                 TemporalExpression::HasSunRisen => Ok(Value::Boolean(now.time() >= context.sunrise().time())),
                 TemporalExpression::HasSunSet => Ok(Value::Boolean(now.time() >= context.sunset().time())),
                 TemporalExpression::IsDaytime => {
@@ -180,13 +665,33 @@ pub fn evaluate(expression: &Expression, context: &Context) -> Result<Value, Exp
                     let is_nighttime = now.time() < context.sunrise().time() || now.time() >= context.sunset().time();
                     Ok(Value::Boolean(is_nighttime))
                 }
+                TemporalExpression::IsAfterSolarEvent { event } => {
+                    let is_after = context.solar_event_time(event).is_some_and(|time| now >= time);
+                    Ok(Value::Boolean(is_after))
+                }
+                TemporalExpression::IsGoldenHour => Ok(Value::Boolean(context.is_golden_hour())),
+                TemporalExpression::SunElevation => Ok(Value::Number(Number::Float(context.sun_elevation()))),
             }
         }
     }
 }
 
-fn compare(lhs: &Expression, rhs: &Expression, cmp: fn(Ordering) -> bool, context: &Context) -> Result<Value, ExpressionError> {
-    match (evaluate(lhs, context)?, evaluate(rhs, context)?) {
+/// Absolute dates are compared as is, recurring dates by their month and day so that a range like 1 December to 6
+/// January wraps around the new year.
+fn is_between_dates(from: &Date, to: &Date, today: NaiveDate) -> bool {
+    if let (Some(from_year), Some(to_year)) = (from.year, to.year) {
+        let from = NaiveDate::from_ymd_opt(from_year, from.month as u32, from.day as u32);
+        let to = NaiveDate::from_ymd_opt(to_year, to.month as u32, to.day as u32);
+        return matches!((from, to), (Some(from), Some(to)) if from <= today && today <= to);
+    }
+
+    let today = (today.month() as u8, today.day() as u8);
+    let (from, to) = ((from.month, from.day), (to.month, to.day));
+    if from <= to { from <= today && today <= to } else { today >= from || today <= to }
+}
+
+fn compare(lhs: &Expression, rhs: &Expression, cmp: fn(Ordering) -> bool, context: &Context, variables: Option<&Variables>) -> Result<Value, ExpressionError> {
+    match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
         (Value::Number(a), Value::Number(b)) => Ok(Value::Boolean(cmp(a.partial_cmp(&b).ok_or_else(|| ExpressionError::ComparisonFailed {
             actual_lhs: format!("{:?}", lhs),
             actual_rhs: format!("{:?}", rhs),
@@ -200,6 +705,91 @@ fn compare(lhs: &Expression, rhs: &Expression, cmp: fn(Ordering) -> bool, contex
     }
 }
 
+fn arithmetic(
+    lhs: &Expression,
+    rhs: &Expression,
+    operand: &'static str,
+    operation: fn(Number, Number) -> Result<Number, NumberError>,
+    context: &Context,
+    variables: Option<&Variables>,
+) -> Result<Value, ExpressionError> {
+    match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
+        (Value::Number(a), Value::Number(b)) => operation(a, b).map(Value::Number).map_err(|err| match err {
+            NumberError::DivisionByZero => ExpressionError::DivisionByZero {
+                actual_lhs: format!("{:?}", lhs),
+                actual_rhs: format!("{:?}", rhs),
+            },
+        }),
+        _ => Err(ExpressionError::OperandTypeMismatch {
+            operand,
+            expected: "Number",
+            actual_lhs: format!("{:?}", lhs),
+            actual_rhs: format!("{:?}", rhs),
+        }),
+    }
+}
+
+fn unary_arithmetic(
+    expression: &Expression,
+    operand: &'static str,
+    operation: fn(Number) -> Number,
+    context: &Context,
+    variables: Option<&Variables>,
+) -> Result<Value, ExpressionError> {
+    match evaluate_with_variables(expression, context, variables)? {
+        Value::Number(number) => Ok(Value::Number(operation(number))),
+        _ => Err(ExpressionError::UnaryOperandTypeMismatch {
+            operand,
+            expected: "Number",
+            actual: format!("{:?}", expression),
+        }),
+    }
+}
+
+fn string_predicate(
+    lhs: &Expression,
+    rhs: &Expression,
+    operand: &'static str,
+    predicate: fn(&str, &str) -> bool,
+    context: &Context,
+    variables: Option<&Variables>,
+) -> Result<Value, ExpressionError> {
+    match (evaluate_with_variables(lhs, context, variables)?, evaluate_with_variables(rhs, context, variables)?) {
+        (Value::String(a), Value::String(b)) => Ok(Value::Boolean(predicate(&a, &b))),
+        _ => Err(ExpressionError::OperandTypeMismatch {
+            operand,
+            expected: "String",
+            actual_lhs: format!("{:?}", lhs),
+            actual_rhs: format!("{:?}", rhs),
+        }),
+    }
+}
+
+/// Evaluates the hue in degrees or the saturation in percent of a color.
+fn color_component(
+    expression: &Expression,
+    operand: &'static str,
+    component: fn((f64, f64)) -> f64,
+    context: &Context,
+    variables: Option<&Variables>,
+) -> Result<Value, ExpressionError> {
+    match evaluate_with_variables(expression, context, variables)? {
+        Value::Color(color) => color
+            .hue_saturation()
+            .map(|hue_saturation| Value::Number(Number::Float(component(hue_saturation))))
+            .map_err(invalid_color),
+        _ => Err(ExpressionError::UnaryOperandTypeMismatch {
+            operand,
+            expected: "Color",
+            actual: format!("{:?}", expression),
+        }),
+    }
+}
+
+fn invalid_color(error: ColorConversionError) -> ExpressionError {
+    ExpressionError::InvalidColor(error.to_string())
+}
+
 #[derive(Error, PartialEq, Debug)]
 pub enum ExpressionError {
     #[error("operand type mismatch for operand {operand}, expected {expected}, but got {actual_lhs} and {actual_rhs}")]
@@ -215,10 +805,24 @@ pub enum ExpressionError {
     UnknownDevice(String),
     #[error("unknown property '{property_id}' for device '{device_id}'")]
     UnknownProperty { device_id: String, property_id: String },
-    #[error("property type '{0:?}' is not supported as a property value")]
-    UnsupportedPropertyType(PropertyType),
     #[error("unable to compare given Numbers {actual_lhs} and {actual_rhs}")]
     ComparisonFailed { actual_lhs: String, actual_rhs: String },
+    #[error("division by zero, dividing {actual_lhs} by {actual_rhs}")]
+    DivisionByZero { actual_lhs: String, actual_rhs: String },
+    #[error("invalid range for Clamp, minimum {min} is greater than maximum {max}")]
+    InvalidClampRange { min: String, max: String },
+    #[error("invalid color, {0}")]
+    InvalidColor(String),
+    #[error("value type mismatch, expected {expected} but got {actual}")]
+    ValueTypeMismatch { expected: &'static str, actual: String },
+    #[error("unknown parameter '{0}'")]
+    UnknownParameter(String),
+    #[error("unknown variable '{0}'")]
+    UnknownVariable(String),
+    #[error("unknown calendar '{0}'")]
+    UnknownCalendar(String),
+    #[error("the property history is not enabled")]
+    HistoryUnavailable,
 }
 
 #[cfg(test)]
@@ -227,13 +831,19 @@ mod tests {
     use crate::domain::Weekday::*;
     use crate::domain::device::{Device, DeviceType};
     use crate::domain::property::{CartesianCoordinate, ColorProperty, Gamut, Property, Unit};
-    use crate::domain::{GeoLocation, Weekday};
+    use crate::domain::{Calendar, Calendars, GeoLocation, Weekday};
     use crate::flow_engine::context::ContextBuilder;
     use crate::flow_engine::expression::Expression::*;
     use crate::flow_engine::expression::ExpressionError::{OperandTypeMismatch, UnaryOperandTypeMismatch};
-    use crate::flow_engine::expression::TemporalExpression::{HasSunRisen, HasSunSet, IsAfterTime, IsBeforeTime, IsDaytime, IsNighttime, IsToday};
+    use crate::flow_engine::expression::TemporalExpression::{
+        HasSunRisen, HasSunSet, IsAfterSolarEvent, IsAfterTime, IsBeforeTime, IsBetweenDates, IsBetweenTimes, IsCalendarDay, IsDate, IsDaytime, IsGoldenHour, IsNighttime, IsToday,
+        SunElevation,
+    };
+    use crate::flow_engine::solar::Twilight;
+    use crate::flow_registry::FlowRegistry;
+    use crate::history::{HistoryEntry, HistoryRetention, PropertyHistory};
     use crate::store::{DeviceMap, StoreSnapshot};
-    use chrono::{Local, TimeZone};
+    use chrono::{DateTime, Local, TimeDelta, TimeZone, Utc};
     use rstest::rstest;
     use std::collections::HashMap;
     use std::sync::Arc;
@@ -300,6 +910,8 @@ mod tests {
             external_id: None,
             address: None,
             controller_id: Some("hue"),
+            room: None,
+            tags: Vec::new(),
         }
     }
 
@@ -453,13 +1065,13 @@ mod tests {
     #[rstest]
     #[case(Value::Boolean(true), Value::Number(Number::PositiveInt(2)), OperandTypeMismatch {
                 operand: "EqualTo",
-                expected: "Boolean|Number",
+                expected: "Boolean|Number|String",
                 actual_lhs: "Literal { value: Boolean(true) }".to_string(),
                 actual_rhs: "Literal { value: Number(PositiveInt(2)) }".to_string(),
         })]
     #[case(Value::None, Value::Number(Number::PositiveInt(2)), OperandTypeMismatch {
                 operand: "EqualTo",
-                expected: "Boolean|Number",
+                expected: "Boolean|Number|String",
                 actual_lhs: "Literal { value: None }".to_string(),
                 actual_rhs: "Literal { value: Number(PositiveInt(2)) }".to_string(),
         })]
@@ -533,13 +1145,13 @@ mod tests {
     #[rstest]
     #[case(Value::Boolean(true), Value::Number(Number::PositiveInt(2)), OperandTypeMismatch {
                 operand: "NotEqualTo",
-                expected: "Boolean|Number",
+                expected: "Boolean|Number|String",
                 actual_lhs: "Literal { value: Boolean(true) }".to_string(),
                 actual_rhs: "Literal { value: Number(PositiveInt(2)) }".to_string(),
         })]
     #[case(Value::None, Value::Number(Number::PositiveInt(2)), OperandTypeMismatch {
                 operand: "NotEqualTo",
-                expected: "Boolean|Number",
+                expected: "Boolean|Number|String",
                 actual_lhs: "Literal { value: None }".to_string(),
                 actual_rhs: "Literal { value: Number(PositiveInt(2)) }".to_string(),
         })]
@@ -692,16 +1304,16 @@ mod tests {
     #[case::unknown_property("ab917a9a-a7d5-4853-9518-75909236a182", "unknown_property_id", Err(UnknownProperty { device_id: "ab917a9a-a7d5-4853-9518-75909236a182".to_string(), property_id: "unknown_property_id".to_string() }))]
     #[case::boolean("ab917a9a-a7d5-4853-9518-75909236a182", "on", Ok(Value::Boolean(true)))]
     #[case::number("ab917a9a-a7d5-4853-9518-75909236a182", "brightness", Ok(Value::Number(Number::Float(58.89))))]
-    #[case::color("ab917a9a-a7d5-4853-9518-75909236a182", "color", Err(ExpressionError::UnsupportedPropertyType(PropertyType::Color)))]
-    #[case::color_temperature(
-        "ab917a9a-a7d5-4853-9518-75909236a182",
-        "colorTemperature",
-        Err(ExpressionError::UnsupportedPropertyType(PropertyType::ColorTemperature))
-    )]
+    #[case::color("ab917a9a-a7d5-4853-9518-75909236a182", "color", Ok(Value::Color(Color::CIE_xyY { xy: CartesianCoordinate::new(0.4851, 0.4331), brightness: 1.0 })))]
+    #[case::color_temperature("ab917a9a-a7d5-4853-9518-75909236a182", "colorTemperature", Ok(Value::Number(Number::PositiveInt(6535))))]
     fn property_value(#[case] device_id: &str, #[case] property_id: &str, #[case] expected: Result<Value, ExpressionError>) {
         let device = device();
         let devices: DeviceMap = HashMap::from([(device.id.clone(), Arc::new(device))]);
-        let snapshot = StoreSnapshot { devices: Arc::new(devices) };
+        let snapshot = StoreSnapshot {
+            devices: Arc::new(devices),
+            changes: Arc::default(),
+            last_changed: Arc::default(),
+        };
 
         let result = evaluate(
             &PropertyValue {
@@ -714,6 +1326,467 @@ mod tests {
         assert_eq!(result, expected);
     }
 
+    #[rstest]
+    #[case::any_change(None, None, true)]
+    #[case::matching_from_and_to(Some(Value::Boolean(false)), Some(Value::Boolean(true)), true)]
+    #[case::matching_to(None, Some(Value::Boolean(true)), true)]
+    #[case::other_from(Some(Value::Boolean(true)), None, false)]
+    #[case::other_to(None, Some(Value::Boolean(false)), false)]
+    fn property_changed(#[case] from: Option<Value>, #[case] to: Option<Value>, #[case] expected: bool) {
+        let snapshot = StoreSnapshot {
+            devices: Arc::default(),
+            changes: Arc::new(vec![PropertyChange {
+                device_id: "device".to_string(),
+                property_id: "on".to_string(),
+                previous_value: Value::Boolean(false),
+                value: Value::Boolean(true),
+            }]),
+            last_changed: Arc::default(),
+        };
+
+        let result = evaluate(
+            &PropertyChanged(PropertyChangedExpression {
+                device_id: "device".to_string(),
+                property_id: "on".to_string(),
+                from,
+                to,
+            }),
+            &Context::builder().snapshot(snapshot).build(),
+        );
+
+        assert_eq!(result, Ok(Value::Boolean(expected)));
+    }
+
+    #[test]
+    fn property_changed_is_false_for_other_properties() {
+        let snapshot = StoreSnapshot {
+            devices: Arc::default(),
+            changes: Arc::new(vec![PropertyChange {
+                device_id: "device".to_string(),
+                property_id: "brightness".to_string(),
+                previous_value: Value::Number(Number::PositiveInt(10)),
+                value: Value::Number(Number::PositiveInt(20)),
+            }]),
+            last_changed: Arc::default(),
+        };
+        let expression = PropertyChanged(PropertyChangedExpression {
+            device_id: "device".to_string(),
+            property_id: "on".to_string(),
+            from: None,
+            to: None,
+        });
+
+        assert_eq!(evaluate(&expression, &Context::builder().snapshot(snapshot).build()), Ok(Value::Boolean(false)));
+    }
+
+    fn grouped_device(id: &str, room: &str, tags: &[&str], on: bool, brightness: u64) -> Device {
+        let on_property: Box<dyn Property> = Box::new(BooleanProperty::new("on".to_string(), PropertyType::On, false, None, on));
+        let brightness_property: Box<dyn Property> = Box::new(
+            NumberProperty::builder("brightness".to_string(), PropertyType::Brightness, false)
+                .positive_int(brightness, Some(0), Some(100))
+                .build(),
+        );
+
+        Device {
+            id: id.to_string(),
+            room: Some(room.to_string()),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            properties: HashMap::from([(on_property.name().to_string(), on_property), (brightness_property.name().to_string(), brightness_property)]),
+            ..device()
+        }
+    }
+
+    fn grouped_devices_context() -> Context {
+        let devices: DeviceMap = [
+            grouped_device("ceiling", "Living room", &["ceiling"], true, 80),
+            grouped_device("reading", "Living room", &[], false, 20),
+            grouped_device("kitchen", "Kitchen", &["ceiling"], false, 50),
+        ]
+        .into_iter()
+        .map(|device| (device.id.clone(), Arc::new(device)))
+        .collect();
+        let snapshot = StoreSnapshot {
+            devices: Arc::new(devices),
+            changes: Arc::default(),
+            last_changed: Arc::default(),
+        };
+        Context::builder().snapshot(snapshot).build()
+    }
+
+    #[rstest]
+    #[case::any_in_room(AggregateFunction::Any, DeviceSelector { room: Some("Living room".to_string()), ..DeviceSelector::default() }, "on", Ok(Value::Boolean(true)))]
+    #[case::all_in_room(AggregateFunction::All, DeviceSelector { room: Some("Living room".to_string()), ..DeviceSelector::default() }, "on", Ok(Value::Boolean(false)))]
+    #[case::any_in_room_with_tag(AggregateFunction::Any, DeviceSelector { room: Some("Kitchen".to_string()), tag: Some("ceiling".to_string()), ..DeviceSelector::default() }, "on", Ok(Value::Boolean(false)))]
+    #[case::all_without_devices(AggregateFunction::All, DeviceSelector { room: Some("Attic".to_string()), ..DeviceSelector::default() }, "on", Ok(Value::Boolean(true)))]
+    #[case::count(AggregateFunction::Count, DeviceSelector::default(), "on", Ok(Value::Number(Number::PositiveInt(1))))]
+    #[case::average_by_tag(AggregateFunction::Average, DeviceSelector { tag: Some("ceiling".to_string()), ..DeviceSelector::default() }, "brightness", Ok(Value::Number(Number::Float(65.0))))]
+    #[case::min_by_ids(AggregateFunction::Min, DeviceSelector { ids: Some(vec!["kitchen".to_string(), "reading".to_string()]), ..DeviceSelector::default() }, "brightness", Ok(Value::Number(Number::PositiveInt(20))))]
+    #[case::max_by_type(AggregateFunction::Max, DeviceSelector { device_type: Some(DeviceType::Light), ..DeviceSelector::default() }, "brightness", Ok(Value::Number(Number::PositiveInt(80))))]
+    #[case::max_without_devices(AggregateFunction::Max, DeviceSelector { room: Some("Attic".to_string()), ..DeviceSelector::default() }, "brightness", Ok(Value::None))]
+    #[case::type_mismatch(AggregateFunction::Any, DeviceSelector { ids: Some(vec!["ceiling".to_string()]), ..DeviceSelector::default() }, "brightness", Err(ExpressionError::ValueTypeMismatch { expected: "Boolean", actual: "Number(PositiveInt(80))".to_string() }))]
+    fn aggregate(#[case] function: AggregateFunction, #[case] devices: DeviceSelector, #[case] property_id: &str, #[case] expected: Result<Value, ExpressionError>) {
+        let expression = Aggregate(AggregateExpression {
+            function,
+            devices,
+            property_id: property_id.to_string(),
+        });
+
+        assert_eq!(evaluate(&expression, &grouped_devices_context()), expected);
+    }
+
+    #[test]
+    fn depends_on_device_groups_if_the_expression_contains_an_aggregate() {
+        let aggregate = Aggregate(AggregateExpression {
+            function: AggregateFunction::Any,
+            devices: DeviceSelector::default(),
+            property_id: "on".to_string(),
+        });
+        let expression = Not { expression: Box::new(aggregate) };
+
+        assert!(expression.depends_on_device_groups());
+        assert!(expression.property_dependencies().is_empty());
+        assert!(!Literal { value: Value::Boolean(true) }.depends_on_device_groups());
+    }
+
+    #[test]
+    fn property_dependencies_include_all_properties_of_the_expression() {
+        let expression = And {
+            lhs: Box::new(PropertyChanged(PropertyChangedExpression {
+                device_id: "switch".to_string(),
+                property_id: "on".to_string(),
+                from: None,
+                to: None,
+            })),
+            rhs: Box::new(Not {
+                expression: Box::new(PropertyValue {
+                    device_id: "lamp".to_string(),
+                    property_id: "on".to_string(),
+                }),
+            }),
+        };
+
+        assert_eq!(
+            expression.property_dependencies(),
+            HashSet::from([("switch".to_string(), "on".to_string()), ("lamp".to_string(), "on".to_string())])
+        );
+        assert!(expression.depends_on_changes());
+        assert!(!Literal { value: Value::Boolean(true) }.depends_on_changes());
+    }
+
+    fn number(value: Number) -> Box<Expression> {
+        Box::new(Literal { value: Value::Number(value) })
+    }
+
+    #[rstest]
+    #[case::add(Add { lhs: number(Number::PositiveInt(3)), rhs: number(Number::Float(0.5)) }, Number::Float(3.5))]
+    #[case::subtract(Subtract { lhs: number(Number::PositiveInt(3)), rhs: number(Number::PositiveInt(5)) }, Number::NegativeInt(-2))]
+    #[case::multiply(Multiply { lhs: number(Number::PositiveInt(3)), rhs: number(Number::NegativeInt(-2)) }, Number::NegativeInt(-6))]
+    #[case::divide(Divide { lhs: number(Number::PositiveInt(7)), rhs: number(Number::PositiveInt(2)) }, Number::Float(3.5))]
+    #[case::modulo(Modulo { lhs: number(Number::PositiveInt(7)), rhs: number(Number::PositiveInt(2)) }, Number::PositiveInt(1))]
+    #[case::min(Min { lhs: number(Number::PositiveInt(7)), rhs: number(Number::Float(2.5)) }, Number::Float(2.5))]
+    #[case::max(Max { lhs: number(Number::PositiveInt(7)), rhs: number(Number::Float(2.5)) }, Number::PositiveInt(7))]
+    #[case::clamp_below(Clamp { expression: number(Number::PositiveInt(1)), min: number(Number::PositiveInt(2)), max: number(Number::PositiveInt(100)) }, Number::PositiveInt(2))]
+    #[case::clamp_within(Clamp { expression: number(Number::PositiveInt(42)), min: number(Number::PositiveInt(2)), max: number(Number::PositiveInt(100)) }, Number::PositiveInt(42))]
+    #[case::clamp_above(Clamp { expression: number(Number::Float(120.5)), min: number(Number::PositiveInt(2)), max: number(Number::PositiveInt(100)) }, Number::PositiveInt(100))]
+    #[case::round(Round { expression: number(Number::Float(58.89)) }, Number::PositiveInt(59))]
+    #[case::abs(Abs { expression: number(Number::NegativeInt(-42)) }, Number::PositiveInt(42))]
+    fn arithmetic(#[case] expression: Expression, #[case] expected: Number) {
+        let result = evaluate(&expression, &Context::default());
+
+        assert_eq!(result, Ok(Value::Number(expected)));
+    }
+
+    #[test]
+    fn arithmetic_on_a_property_value() {
+        let device = device();
+        let snapshot = StoreSnapshot {
+            devices: Arc::new(HashMap::from([(device.id.clone(), Arc::new(device))])),
+            changes: Arc::default(),
+            last_changed: Arc::default(),
+        };
+        let expression = Round {
+            expression: Box::new(Add {
+                lhs: Box::new(PropertyValue {
+                    device_id: "ab917a9a-a7d5-4853-9518-75909236a182".to_string(),
+                    property_id: "brightness".to_string(),
+                }),
+                rhs: number(Number::PositiveInt(20)),
+            }),
+        };
+
+        assert_eq!(
+            evaluate(&expression, &Context::builder().snapshot(snapshot).build()),
+            Ok(Value::Number(Number::PositiveInt(79)))
+        );
+    }
+
+    #[rstest]
+    #[case::divide(Divide { lhs: number(Number::PositiveInt(7)), rhs: number(Number::Float(0.0)) })]
+    #[case::modulo(Modulo { lhs: number(Number::PositiveInt(7)), rhs: number(Number::PositiveInt(0)) })]
+    fn division_by_zero(#[case] expression: Expression) {
+        let result = evaluate(&expression, &Context::default());
+
+        assert!(matches!(result, Err(ExpressionError::DivisionByZero { .. })));
+    }
+
+    #[test]
+    fn arithmetic_mismatch() {
+        let result = evaluate(
+            &Add {
+                lhs: number(Number::PositiveInt(7)),
+                rhs: Box::new(Literal { value: Value::Boolean(true) }),
+            },
+            &Context::default(),
+        );
+
+        assert_eq!(
+            result,
+            Err(OperandTypeMismatch {
+                operand: "Add",
+                expected: "Number",
+                actual_lhs: "Literal { value: Number(PositiveInt(7)) }".to_string(),
+                actual_rhs: "Literal { value: Boolean(true) }".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn clamp_fails_if_the_minimum_is_greater_than_the_maximum() {
+        let result = evaluate(
+            &Clamp {
+                expression: number(Number::PositiveInt(42)),
+                min: number(Number::PositiveInt(100)),
+                max: number(Number::PositiveInt(2)),
+            },
+            &Context::default(),
+        );
+
+        assert_eq!(
+            result,
+            Err(ExpressionError::InvalidClampRange {
+                min: "Literal { value: Number(PositiveInt(100)) }".to_string(),
+                max: "Literal { value: Number(PositiveInt(2)) }".to_string(),
+            })
+        );
+    }
+
+    fn string(value: &str) -> Box<Expression> {
+        Box::new(Literal {
+            value: Value::String(value.to_string()),
+        })
+    }
+
+    #[rstest]
+    #[case::equal_to(EqualTo { lhs: string("long_press"), rhs: string("long_press") }, true)]
+    #[case::not_equal_to(NotEqualTo { lhs: string("long_press"), rhs: string("short_press") }, true)]
+    #[case::contains(Contains { lhs: string("Living room lamp"), rhs: string("room") }, true)]
+    #[case::does_not_contain(Contains { lhs: string("Living room lamp"), rhs: string("Room") }, false)]
+    #[case::starts_with(StartsWith { lhs: string("long_press"), rhs: string("long") }, true)]
+    #[case::does_not_start_with(StartsWith { lhs: string("long_press"), rhs: string("press") }, false)]
+    #[case::matches(Matches { expression: string("long_release"), pattern: Pattern(Regex::new("^long_(press|release)$").unwrap()) }, true)]
+    #[case::does_not_match(Matches { expression: string("short_press"), pattern: Pattern(Regex::new("^long_").unwrap()) }, false)]
+    fn string_expressions(#[case] expression: Expression, #[case] expected: bool) {
+        assert_eq!(evaluate(&expression, &Context::default()), Ok(Value::Boolean(expected)));
+    }
+
+    #[test]
+    fn string_expressions_fail_for_other_types() {
+        let result = evaluate(
+            &Contains {
+                lhs: string("42"),
+                rhs: number(Number::PositiveInt(4)),
+            },
+            &Context::default(),
+        );
+
+        assert_eq!(
+            result,
+            Err(OperandTypeMismatch {
+                operand: "Contains",
+                expected: "String",
+                actual_lhs: "Literal { value: String(\"42\") }".to_string(),
+                actual_rhs: "Literal { value: Number(PositiveInt(4)) }".to_string(),
+            })
+        );
+    }
+
+    fn color(color: Color) -> Box<Expression> {
+        Box::new(Literal { value: Value::Color(color) })
+    }
+
+    #[test]
+    fn color_distance() {
+        let result = evaluate(
+            &ColorDistance {
+                lhs: color(Color::RGB(255, 0, 0)),
+                rhs: color(Color::Hex("#ff0000".to_string())),
+            },
+            &Context::default(),
+        );
+
+        assert_eq!(result, Ok(Value::Number(Number::Float(0.0))));
+    }
+
+    #[rstest]
+    #[case::hue(Hue { expression: color(Color::RGB(0, 0, 255)) }, 240.0)]
+    #[case::saturation(Saturation { expression: color(Color::RGB(255, 128, 128)) }, 49.80392156862745)]
+    fn color_components(#[case] expression: Expression, #[case] expected: f64) {
+        assert_eq!(evaluate(&expression, &Context::default()), Ok(Value::Number(Number::Float(expected))));
+    }
+
+    #[test]
+    fn color_expressions_fail_for_other_types() {
+        let result = evaluate(
+            &Hue {
+                expression: number(Number::PositiveInt(240)),
+            },
+            &Context::default(),
+        );
+
+        assert_eq!(
+            result,
+            Err(UnaryOperandTypeMismatch {
+                operand: "Hue",
+                expected: "Color",
+                actual: "Literal { value: Number(PositiveInt(240)) }".to_string(),
+            })
+        );
+    }
+
+    fn last_changed_snapshot(last_changed: DateTime<Local>) -> StoreSnapshot {
+        StoreSnapshot {
+            devices: Arc::default(),
+            changes: Arc::default(),
+            last_changed: Arc::new(HashMap::from([(("device".to_string(), "on".to_string()), last_changed)])),
+        }
+    }
+
+    fn property_duration(property_id: &str, duration: Duration) -> PropertyDurationExpression {
+        PropertyDurationExpression {
+            device_id: "device".to_string(),
+            property_id: property_id.to_string(),
+            duration,
+        }
+    }
+
+    #[rstest]
+    #[case::longer_unchanged(121, true, false)]
+    #[case::exactly_unchanged(120, true, true)]
+    #[case::recently_changed(119, false, true)]
+    fn unchanged_for_and_changed_within(#[case] minutes_since_change: i64, #[case] expected_unchanged_for: bool, #[case] expected_changed_within: bool) {
+        let now = Local.with_ymd_and_hms(2025, 10, 16, 12, 0, 0).unwrap();
+        let context = Context::builder()
+            .snapshot(last_changed_snapshot(now - TimeDelta::minutes(minutes_since_change)))
+            .now(now)
+            .build();
+        let two_hours = Duration::from_secs(2 * 3600);
+
+        assert_eq!(
+            evaluate(&UnchangedFor(property_duration("on", two_hours)), &context),
+            Ok(Value::Boolean(expected_unchanged_for))
+        );
+        assert_eq!(
+            evaluate(&ChangedWithin(property_duration("on", two_hours)), &context),
+            Ok(Value::Boolean(expected_changed_within))
+        );
+    }
+
+    #[test]
+    fn unchanged_for_fails_for_an_unknown_property() {
+        let now = Local::now();
+        let context = Context::builder().snapshot(last_changed_snapshot(now)).now(now).build();
+
+        assert_eq!(
+            evaluate(&UnchangedFor(property_duration("brightness", Duration::ZERO)), &context),
+            Err(UnknownProperty {
+                device_id: "device".to_string(),
+                property_id: "brightness".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn deserializes_a_property_duration_expression() {
+        let expression: Expression = serde_json::from_str(r#"{ "type": "unchangedFor", "deviceId": "device", "propertyId": "on", "duration": "2h" }"#).unwrap();
+
+        assert_eq!(expression, UnchangedFor(property_duration("on", Duration::from_secs(2 * 3600))));
+        assert_eq!(expression.property_dependencies(), HashSet::from([("device".to_string(), "on".to_string())]));
+        assert_eq!(
+            expression.timed_dependencies(),
+            HashSet::from([("device".to_string(), "on".to_string(), Duration::from_secs(2 * 3600))])
+        );
+    }
+
+    #[rstest]
+    #[case::before_the_first_entry(240, Value::None)]
+    #[case::at_an_entry(120, Value::Boolean(true))]
+    #[case::between_entries(90, Value::Boolean(true))]
+    #[case::after_the_last_entry(30, Value::Boolean(false))]
+    fn value_ago(#[case] minutes_ago: i64, #[case] expected: Value) {
+        let now = Local.with_ymd_and_hms(2025, 10, 16, 12, 0, 0).unwrap();
+        let history = PropertyHistory::new(HistoryRetention {
+            retention: Duration::from_secs(24 * 3600),
+            downsample_after: Duration::from_secs(24 * 3600),
+            downsample_interval: Duration::from_secs(3600),
+        });
+        for (minutes_before_now, value) in [(180, false), (120, true), (60, false)] {
+            let entry = HistoryEntry {
+                timestamp: now - TimeDelta::minutes(minutes_before_now),
+                value: Value::Boolean(value),
+            };
+            history.record("device", "on", entry);
+        }
+        let flow_registry = FlowRegistry::new(vec![]).with_history(Arc::new(history));
+        let context = Context::builder().flow_registry(Arc::new(flow_registry)).now(now).build();
+
+        let duration = Duration::from_secs(minutes_ago as u64 * 60);
+        assert_eq!(evaluate(&ValueAgo(property_duration("on", duration)), &context), Ok(expected));
+    }
+
+    #[test]
+    fn value_ago_fails_without_a_history() {
+        assert_eq!(
+            evaluate(&ValueAgo(property_duration("on", Duration::ZERO)), &Context::default()),
+            Err(ExpressionError::HistoryUnavailable)
+        );
+    }
+
+    #[rstest]
+    #[case::known_parameter("level", Ok(Value::Number(Number::PositiveInt(30))))]
+    #[case::unknown_parameter("unknown", Err(ExpressionError::UnknownParameter("unknown".to_string())))]
+    fn parameter(#[case] name: &str, #[case] expected: Result<Value, ExpressionError>) {
+        let context = Context::default().for_sub_flow(HashMap::from([("level".to_string(), Value::Number(Number::PositiveInt(30)))]));
+        let result = evaluate(&Parameter { name: name.to_string() }, &context);
+
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case::with_trigger(Some("frontDoor"), Value::String("frontDoor".to_string()))]
+    #[case::without_trigger(None, Value::None)]
+    fn triggered_by(#[case] trigger_id: Option<&str>, #[case] expected: Value) {
+        let context = Context::default().for_trigger(trigger_id.map(str::to_string));
+
+        assert_eq!(evaluate(&TriggeredBy, &context), Ok(expected));
+    }
+
+    #[rstest]
+    #[case::known_variable("counter", Ok(Value::Number(Number::PositiveInt(3))))]
+    #[case::unknown_variable("unknown", Err(ExpressionError::UnknownVariable("unknown".to_string())))]
+    fn variable(#[case] name: &str, #[case] expected: Result<Value, ExpressionError>) {
+        let variables: Variables = HashMap::from([("counter".to_string(), Value::Number(Number::PositiveInt(3)))]);
+        let result = evaluate_with_variables(&Variable { name: name.to_string() }, &Context::default(), Some(&variables));
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn variable_without_variables() {
+        let result = evaluate(&Variable { name: "counter".to_string() }, &Context::default());
+
+        assert_eq!(result, Err(ExpressionError::UnknownVariable("counter".to_string())));
+    }
+
     #[rstest]
     #[case(Monday, false)]
     #[case(Tuesday, false)]
@@ -737,11 +1810,11 @@ mod tests {
     }
 
     #[rstest]
-    #[case::midnight(Time { hour: 0, minute: 0 }, false)]
-    #[case::before_time(Time { hour: 11, minute: 59 }, false)]
-    #[case::same_time(Time { hour: 12, minute: 0 }, false)]
-    #[case::after_time(Time { hour: 12, minute: 1 }, true)]
-    #[case::before_midnight(Time { hour: 23, minute: 59 }, true)]
+    #[case::midnight(Time { hour: 0, minute: 0, second: 0 }, false)]
+    #[case::before_time(Time { hour: 11, minute: 59, second: 0 }, false)]
+    #[case::same_time(Time { hour: 12, minute: 0, second: 0 }, false)]
+    #[case::after_time(Time { hour: 12, minute: 1, second: 0 }, true)]
+    #[case::before_midnight(Time { hour: 23, minute: 59, second: 0 }, true)]
     fn is_before_time(#[case] time: Time, #[case] expected: bool) {
         let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap();
         let context = &context_with_location().now(fixed_date_time).build();
@@ -750,11 +1823,11 @@ mod tests {
     }
 
     #[rstest]
-    #[case::midnight(Time { hour: 0, minute: 0 }, true)]
-    #[case::before_time(Time { hour: 11, minute: 59 }, true)]
-    #[case::same_time(Time { hour: 12, minute: 0 }, false)]
-    #[case::after_time(Time { hour: 12, minute: 1 }, false)]
-    #[case::before_midnight(Time { hour: 23, minute: 59 }, false)]
+    #[case::midnight(Time { hour: 0, minute: 0, second: 0 }, true)]
+    #[case::before_time(Time { hour: 11, minute: 59, second: 0 }, true)]
+    #[case::same_time(Time { hour: 12, minute: 0, second: 0 }, false)]
+    #[case::after_time(Time { hour: 12, minute: 1, second: 0 }, false)]
+    #[case::before_midnight(Time { hour: 23, minute: 59, second: 0 }, false)]
     fn is_after_time(#[case] time: Time, #[case] expected: bool) {
         let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap();
         let context = &context_with_location().now(fixed_date_time).build();
@@ -763,9 +1836,102 @@ mod tests {
     }
 
     #[rstest]
-    #[case(Time { hour: 0, minute: 0 }, false)]
-    #[case(Time { hour: 14, minute: 0 }, true)]
-    #[case(Time { hour: 23, minute: 0 }, true)]
+    #[case::within_window((22, 30, 0), (23, 0, 0), (22, 45, 0), true)]
+    #[case::at_start((22, 30, 0), (23, 0, 0), (22, 30, 0), true)]
+    #[case::at_end((22, 30, 0), (23, 0, 0), (23, 0, 0), false)]
+    #[case::before_window((22, 30, 0), (23, 0, 0), (22, 29, 59), false)]
+    #[case::before_midnight_in_wrapping_window((22, 30, 0), (6, 15, 0), (23, 59, 59), true)]
+    #[case::after_midnight_in_wrapping_window((22, 30, 0), (6, 15, 0), (6, 14, 59), true)]
+    #[case::outside_wrapping_window((22, 30, 0), (6, 15, 0), (12, 0, 0), false)]
+    #[case::end_of_wrapping_window((22, 30, 0), (6, 15, 30), (6, 15, 30), false)]
+    fn is_between_times(#[case] from: (u8, u8, u8), #[case] to: (u8, u8, u8), #[case] now: (u32, u32, u32), #[case] expected: bool) {
+        let time = |(hour, minute, second)| Time { hour, minute, second };
+        let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, now.0, now.1, now.2).unwrap();
+        let expression = Temporal {
+            expression: IsBetweenTimes { from: time(from), to: time(to) },
+        };
+
+        let result = evaluate(&expression, &Context::builder().now(fixed_date_time).build());
+
+        assert_eq!(result, Ok(Value::Boolean(expected)));
+    }
+
+    #[rstest]
+    #[case::within_recurring_range((None, 6, 1), (None, 8, 31), (2000, 8, 4), true)]
+    #[case::outside_recurring_range((None, 6, 1), (None, 7, 31), (2000, 8, 4), false)]
+    #[case::start_of_wrapping_range((None, 12, 1), (None, 1, 6), (2000, 12, 1), true)]
+    #[case::end_of_wrapping_range((None, 12, 1), (None, 1, 6), (2001, 1, 6), true)]
+    #[case::outside_wrapping_range((None, 12, 1), (None, 1, 6), (2001, 1, 7), false)]
+    #[case::within_absolute_range((Some(2000), 12, 1), (Some(2001), 1, 6), (2001, 1, 2), true)]
+    #[case::other_year_of_absolute_range((Some(2000), 12, 1), (Some(2001), 1, 6), (2002, 1, 2), false)]
+    fn is_between_dates(#[case] from: (Option<i32>, u8, u8), #[case] to: (Option<i32>, u8, u8), #[case] today: (i32, u32, u32), #[case] expected: bool) {
+        let date = |(year, month, day)| Date { year, month, day };
+        let fixed_date_time = Local.with_ymd_and_hms(today.0, today.1, today.2, 12, 0, 0).unwrap();
+        let expression = Temporal {
+            expression: IsBetweenDates { from: date(from), to: date(to) },
+        };
+
+        let result = evaluate(&expression, &Context::builder().now(fixed_date_time).build());
+
+        assert_eq!(result, Ok(Value::Boolean(expected)));
+    }
+
+    #[rstest]
+    #[case::recurring(Date { year: None, month: 8, day: 4 }, true)]
+    #[case::absolute(Date { year: Some(2000), month: 8, day: 4 }, true)]
+    #[case::other_year(Date { year: Some(2001), month: 8, day: 4 }, false)]
+    #[case::other_day(Date { year: None, month: 8, day: 5 }, false)]
+    fn is_date(#[case] date: Date, #[case] expected: bool) {
+        let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap();
+
+        let result = evaluate(&Temporal { expression: IsDate { date } }, &Context::builder().now(fixed_date_time).build());
+
+        assert_eq!(result, Ok(Value::Boolean(expected)));
+    }
+
+    #[rstest]
+    #[case::in_calendar((2026, 12, 25), Ok(Value::Boolean(true)))]
+    #[case::recurring_in_calendar((2027, 1, 1), Ok(Value::Boolean(true)))]
+    #[case::not_in_calendar((2026, 12, 24), Ok(Value::Boolean(false)))]
+    fn is_calendar_day(#[case] today: (i32, u32, u32), #[case] expected: Result<Value, ExpressionError>) {
+        let calendar = Calendar::new(vec![
+            Date {
+                year: Some(2026),
+                month: 12,
+                day: 25,
+            },
+            Date { year: None, month: 1, day: 1 },
+        ]);
+        let calendars = Calendars::from([("holidays".to_string(), calendar)]);
+        let fixed_date_time = Local.with_ymd_and_hms(today.0, today.1, today.2, 12, 0, 0).unwrap();
+        let context = Context::builder().now(fixed_date_time).calendars(Arc::new(calendars)).build();
+
+        let result = evaluate(
+            &Temporal {
+                expression: IsCalendarDay { calendar: "holidays".to_string() },
+            },
+            &context,
+        );
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn is_calendar_day_fails_for_an_unknown_calendar() {
+        let result = evaluate(
+            &Temporal {
+                expression: IsCalendarDay { calendar: "holidays".to_string() },
+            },
+            &Context::default(),
+        );
+
+        assert_eq!(result, Err(ExpressionError::UnknownCalendar("holidays".to_string())));
+    }
+
+    #[rstest]
+    #[case(Time { hour: 0, minute: 0, second: 0 }, false)]
+    #[case(Time { hour: 14, minute: 0, second: 0 }, true)]
+    #[case(Time { hour: 23, minute: 0, second: 0 }, true)]
     fn has_sun_risen(#[case] time: Time, #[case] expected: bool) {
         // Sunrise at given location and date: 2000-08-04T06:09:31+02:00
         let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, time.hour as u32, time.minute as u32, 0).unwrap();
@@ -775,9 +1941,9 @@ mod tests {
     }
 
     #[rstest]
-    #[case(Time { hour: 0, minute: 0 }, false)]
-    #[case(Time { hour: 14, minute: 0 }, false)]
-    #[case(Time { hour: 23, minute: 0 }, true)]
+    #[case(Time { hour: 0, minute: 0, second: 0 }, false)]
+    #[case(Time { hour: 14, minute: 0, second: 0 }, false)]
+    #[case(Time { hour: 23, minute: 0, second: 0 }, true)]
     fn has_sun_set(#[case] time: Time, #[case] expected: bool) {
         // Sunset at given location and date: 2000-08-04T21:26:42+02:00
         let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, time.hour as u32, time.minute as u32, 0).unwrap();
@@ -787,9 +1953,9 @@ mod tests {
     }
 
     #[rstest]
-    #[case(Time { hour: 0, minute: 0 }, false)]
-    #[case(Time { hour: 14, minute: 0 }, true)]
-    #[case(Time { hour: 23, minute: 0 }, false)]
+    #[case(Time { hour: 0, minute: 0, second: 0 }, false)]
+    #[case(Time { hour: 14, minute: 0, second: 0 }, true)]
+    #[case(Time { hour: 23, minute: 0, second: 0 }, false)]
     fn is_daytime(#[case] time: Time, #[case] expected: bool) {
         // Sunrise and sunset at given location and date: 2000-08-04T06:09:31+02:00 and 2000-08-04T21:26:42+02:00
         let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, time.hour as u32, time.minute as u32, 0).unwrap();
@@ -799,9 +1965,9 @@ mod tests {
     }
 
     #[rstest]
-    #[case(Time { hour: 0, minute: 0 }, true)]
-    #[case(Time { hour: 14, minute: 0 }, false)]
-    #[case(Time { hour: 23, minute: 0 }, true)]
+    #[case(Time { hour: 0, minute: 0, second: 0 }, true)]
+    #[case(Time { hour: 14, minute: 0, second: 0 }, false)]
+    #[case(Time { hour: 23, minute: 0, second: 0 }, true)]
     fn is_nighttime(#[case] time: Time, #[case] expected: bool) {
         // Sunrise and sunset at given location and date: 2000-08-04T06:09:31+02:00 and 2000-08-04T21:26:42+02:00
         let fixed_date_time = Local.with_ymd_and_hms(2000, 8, 4, time.hour as u32, time.minute as u32, 0).unwrap();
@@ -809,4 +1975,45 @@ mod tests {
         let result = evaluate(&Temporal { expression: IsNighttime }, &context).unwrap();
         assert_eq!(result, Value::Boolean(expected));
     }
+
+    #[rstest]
+    #[case::before_civil_dusk(SolarEvent::Dusk(Twilight::Civil), (19, 30), false)]
+    #[case::after_civil_dusk(SolarEvent::Dusk(Twilight::Civil), (20, 30), true)]
+    #[case::after_setting_elevation(SolarEvent::Elevation { degrees: 10.0, rising: false }, (18, 30), true)]
+    #[case::unreached_elevation(SolarEvent::Elevation { degrees: 70.0, rising: true }, (21, 0), false)]
+    fn is_after_solar_event(#[case] event: SolarEvent, #[case] time: (u32, u32), #[case] expected: bool) {
+        // Sunset and civil dusk at given location and date: 2000-08-04T19:26:42Z and 2000-08-04T20:09Z
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, time.0, time.1, 0).unwrap().with_timezone(&Local);
+        let context = &context_with_location().now(now).build();
+        let result = evaluate(
+            &Temporal {
+                expression: IsAfterSolarEvent { event },
+            },
+            context,
+        )
+        .unwrap();
+        assert_eq!(result, Value::Boolean(expected));
+    }
+
+    #[rstest]
+    #[case((12, 0), false)]
+    #[case((19, 0), true)]
+    fn is_golden_hour(#[case] time: (u32, u32), #[case] expected: bool) {
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, time.0, time.1, 0).unwrap().with_timezone(&Local);
+        let context = &context_with_location().now(now).build();
+        let result = evaluate(&Temporal { expression: IsGoldenHour }, context).unwrap();
+        assert_eq!(result, Value::Boolean(expected));
+    }
+
+    #[test]
+    fn sun_elevation() {
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap().with_timezone(&Local);
+        let context = &context_with_location().now(now).build();
+        let result = evaluate(&Temporal { expression: SunElevation }, context).unwrap();
+        assert!(
+            matches!(result, Value::Number(Number::Float(elevation)) if (54.0..56.0).contains(&elevation)),
+            "{:?}",
+            result
+        );
+    }
 }
@@ -0,0 +1,238 @@
+use crate::domain::color::Color;
+use crate::domain::{Number, WeekdayCondition};
+use crate::flow_engine::expression::{
+    AggregateExpression, AggregateFunction, DeviceSelector, Expression, PropertyChangedExpression, PropertyDurationExpression, TemporalExpression,
+};
+use crate::flow_engine::{SolarEvent, Value};
+use humantime_serde::re::humantime::format_duration;
+use std::fmt::{Display, Formatter, Result};
+
+/// Renders an expression in the textual syntax the flow loader parses, with only the parentheses that are needed.
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        use Expression::*;
+
+        match self {
+            Or { lhs, rhs } => binary(f, self, lhs, "||", rhs),
+            And { lhs, rhs } => binary(f, self, lhs, "&&", rhs),
+            EqualTo { lhs, rhs } => binary(f, self, lhs, "==", rhs),
+            NotEqualTo { lhs, rhs } => binary(f, self, lhs, "!=", rhs),
+            GreaterThan { lhs, rhs } => binary(f, self, lhs, ">", rhs),
+            GreaterThanOrEqualTo { lhs, rhs } => binary(f, self, lhs, ">=", rhs),
+            LessThan { lhs, rhs } => binary(f, self, lhs, "<", rhs),
+            LessThanOrEqualTo { lhs, rhs } => binary(f, self, lhs, "<=", rhs),
+            Add { lhs, rhs } => binary(f, self, lhs, "+", rhs),
+            Subtract { lhs, rhs } => binary(f, self, lhs, "-", rhs),
+            Multiply { lhs, rhs } => binary(f, self, lhs, "*", rhs),
+            Divide { lhs, rhs } => binary(f, self, lhs, "/", rhs),
+            Modulo { lhs, rhs } => binary(f, self, lhs, "%", rhs),
+            Not { expression } => {
+                write!(f, "!")?;
+                operand(f, expression, precedence(self), false)
+            }
+            Min { lhs, rhs } => write!(f, "min({}, {})", lhs, rhs),
+            Max { lhs, rhs } => write!(f, "max({}, {})", lhs, rhs),
+            Clamp { expression, min, max } => write!(f, "clamp({}, {}, {})", expression, min, max),
+            Round { expression } => write!(f, "round({})", expression),
+            Abs { expression } => write!(f, "abs({})", expression),
+            Contains { lhs, rhs } => write!(f, "contains({}, {})", lhs, rhs),
+            StartsWith { lhs, rhs } => write!(f, "startsWith({}, {})", lhs, rhs),
+            ColorDistance { lhs, rhs } => write!(f, "colorDistance({}, {})", lhs, rhs),
+            Hue { expression } => write!(f, "hue({})", expression),
+            Saturation { expression } => write!(f, "saturation({})", expression),
+            Matches { expression, pattern } => write!(f, "matches({}, {})", expression, Quoted(pattern.0.as_str())),
+            Literal { value } => write!(f, "{}", LiteralValue(value)),
+            PropertyValue { device_id, property_id } => write!(f, "{}", Property(device_id, property_id)),
+            PropertyChanged(PropertyChangedExpression {
+                device_id,
+                property_id,
+                from: None,
+                to: None,
+            }) => write!(f, "changed({})", Property(device_id, property_id)),
+            PropertyChanged(PropertyChangedExpression { device_id, property_id, from, to }) => {
+                let wildcard = |value: &Option<Value>| value.as_ref().map_or("_".to_string(), |value| LiteralValue(value).to_string());
+                write!(f, "changed({}, {}, {})", Property(device_id, property_id), wildcard(from), wildcard(to))
+            }
+            UnchangedFor(expression) => write!(f, "unchangedFor({})", DurationArguments(expression)),
+            ChangedWithin(expression) => write!(f, "changedWithin({})", DurationArguments(expression)),
+            ValueAgo(expression) => write!(f, "valueAgo({})", DurationArguments(expression)),
+            Parameter { name } if is_identifier(name) => write!(f, "${}", name),
+            Parameter { name } => write!(f, "parameter({})", Quoted(name)),
+            Variable { name } if is_identifier(name) && !matches!(name.as_str(), "true" | "false" | "none" | "_") => write!(f, "{}", name),
+            Variable { name } => write!(f, "variable({})", Quoted(name)),
+            TriggeredBy => write!(f, "triggeredBy()"),
+            Temporal { expression } => write!(f, "{}", expression),
+            Aggregate(expression) => write!(f, "{}", expression),
+        }
+    }
+}
+
+impl Display for TemporalExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            TemporalExpression::IsToday { when: WeekdayCondition::Set(days) } => {
+                let days = days.iter().map(|day| Quoted(&day.to_string()).to_string()).collect::<Vec<_>>();
+                write!(f, "isToday({})", days.join(", "))
+            }
+            TemporalExpression::IsToday { when } => write!(f, "isToday({})", Quoted(&when.to_string())),
+            TemporalExpression::IsBeforeTime { time } => write!(f, "isBeforeTime(\"{}\")", time),
+            TemporalExpression::IsAfterTime { time } => write!(f, "isAfterTime(\"{}\")", time),
+            TemporalExpression::IsBetweenTimes { from, to } => write!(f, "isBetweenTimes(\"{}\", \"{}\")", from, to),
+            TemporalExpression::IsBetweenDates { from, to } => write!(f, "isBetweenDates(\"{}\", \"{}\")", from, to),
+            TemporalExpression::IsDate { date } => write!(f, "isDate(\"{}\")", date),
+            TemporalExpression::IsCalendarDay { calendar } => write!(f, "isCalendarDay({})", Quoted(calendar)),
+            TemporalExpression::HasSunRisen => write!(f, "hasSunRisen()"),
+            TemporalExpression::HasSunSet => write!(f, "hasSunSet()"),
+            TemporalExpression::IsDaytime => write!(f, "isDaytime()"),
+            TemporalExpression::IsNighttime => write!(f, "isNighttime()"),
+            TemporalExpression::IsAfterSolarEvent {
+                event: SolarEvent::Elevation { degrees, rising },
+            } => write!(f, "isAfterSunElevation({}, \"{}\")", degrees, if *rising { "rising" } else { "setting" }),
+            TemporalExpression::IsAfterSolarEvent { event } => write!(f, "isAfterSolarEvent(\"{}\")", event),
+            TemporalExpression::IsGoldenHour => write!(f, "isGoldenHour()"),
+            TemporalExpression::SunElevation => write!(f, "sunElevation()"),
+        }
+    }
+}
+
+/// Binding strength of the operator of an expression, function calls and operands bind the strongest.
+fn precedence(expression: &Expression) -> u8 {
+    use Expression::*;
+
+    match expression {
+        Or { .. } => 1,
+        And { .. } => 2,
+        EqualTo { .. } | NotEqualTo { .. } => 3,
+        GreaterThan { .. } | GreaterThanOrEqualTo { .. } | LessThan { .. } | LessThanOrEqualTo { .. } => 4,
+        Add { .. } | Subtract { .. } => 5,
+        Multiply { .. } | Divide { .. } | Modulo { .. } => 6,
+        Not { .. } => 7,
+        _ => 8,
+    }
+}
+
+/// Operators are left associative, so an operand on the right with the same precedence needs parentheses.
+fn binary(f: &mut Formatter<'_>, expression: &Expression, lhs: &Expression, operator: &str, rhs: &Expression) -> Result {
+    operand(f, lhs, precedence(expression), false)?;
+    write!(f, " {} ", operator)?;
+    operand(f, rhs, precedence(expression), true)
+}
+
+fn operand(f: &mut Formatter<'_>, operand: &Expression, parent_precedence: u8, is_rhs: bool) -> Result {
+    let operand_precedence = precedence(operand);
+    if operand_precedence < parent_precedence || (is_rhs && operand_precedence == parent_precedence) {
+        write!(f, "({})", operand)
+    } else {
+        write!(f, "{}", operand)
+    }
+}
+
+struct LiteralValue<'a>(&'a Value);
+
+impl Display for LiteralValue<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.0 {
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Number(Number::Float(value)) => write!(f, "{:?}", value), // Keeps the fraction of whole numbers, like 1.0
+            Value::Number(number) => write!(f, "{}", number),
+            Value::String(value) => write!(f, "{}", Quoted(value)),
+            Value::Color(Color::Hex(value)) => write!(f, "color({})", Quoted(value)),
+            Value::Color(Color::RGB(r, g, b)) => write!(f, "rgb({}, {}, {})", r, g, b),
+            Value::Color(Color::CIE_xyY { xy, brightness }) => write!(f, "xyY({:?}, {:?}, {:?})", xy.x(), xy.y(), brightness),
+            Value::None => write!(f, "none"),
+        }
+    }
+}
+
+impl Display for AggregateExpression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let function = match self.function {
+            AggregateFunction::Any => "any",
+            AggregateFunction::All => "all",
+            AggregateFunction::Count => "count",
+            AggregateFunction::Average => "average",
+            AggregateFunction::Min => "min",
+            AggregateFunction::Max => "max",
+        };
+        write!(f, "{}({}, ", function, Selector(&self.devices))?;
+        if is_identifier(&self.property_id) {
+            write!(f, "{})", self.property_id)
+        } else {
+            write!(f, "{})", Quoted(&self.property_id))
+        }
+    }
+}
+
+/// A device selector, its criteria are joined by `&&` and a selector without criteria selects all devices.
+struct Selector<'a>(&'a DeviceSelector);
+
+impl Display for Selector<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let DeviceSelector { ids, device_type, room, tag } = self.0;
+        let mut terms = Vec::new();
+        if let Some(ids) = ids {
+            terms.push(format!("devices({})", ids.iter().map(|id| Quoted(id).to_string()).collect::<Vec<_>>().join(", ")));
+        }
+        if let Some(device_type) = device_type {
+            terms.push(format!("deviceType({})", Quoted(&format!("{:?}", device_type))));
+        }
+        if let Some(room) = room {
+            terms.push(format!("room({})", Quoted(room)));
+        }
+        if let Some(tag) = tag {
+            terms.push(format!("tag({})", Quoted(tag)));
+        }
+
+        if terms.is_empty() {
+            write!(f, "allDevices()")
+        } else {
+            write!(f, "{}", terms.join(" && "))
+        }
+    }
+}
+
+/// A property reference, ids are quoted unless all their segments are identifiers.
+struct Property<'a>(&'a str, &'a str);
+
+impl Display for Property<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let Property(device_id, property_id) = self;
+        if device_id.split('.').all(is_identifier) {
+            write!(f, "{}", device_id)?;
+        } else {
+            write!(f, "{}", Quoted(device_id))?;
+        }
+        if is_identifier(property_id) {
+            write!(f, ".{}", property_id)
+        } else {
+            write!(f, ".{}", Quoted(property_id))
+        }
+    }
+}
+
+struct DurationArguments<'a>(&'a PropertyDurationExpression);
+
+impl Display for DurationArguments<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        let expression = self.0;
+        write!(
+            f,
+            "{}, \"{}\"",
+            Property(&expression.device_id, &expression.property_id),
+            format_duration(expression.duration)
+        )
+    }
+}
+
+struct Quoted<'a>(&'a str);
+
+impl Display for Quoted<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "\"{}\"", self.0.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut characters = text.chars();
+    characters.next().is_some_and(|character| character.is_alphabetic() || character == '_') && characters.all(|character| character.is_alphanumeric() || character == '_')
+}
@@ -2,7 +2,7 @@ use crate::flow_engine::Expression::Literal;
 use crate::flow_engine::action::Action;
 use crate::flow_engine::{Expression, Schedule, Value};
 use std::collections::HashMap;
-use std::fmt::Debug;
+use std::fmt::{Debug, Display, Formatter};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -10,34 +10,52 @@ use std::time::Duration;
 pub struct Flow {
     id: String,
     name: String,
-    schedule: Option<Schedule>,
-    trigger: Expression,
+    triggers: Vec<Trigger>,
+    condition: Expression, // Must hold for a trigger to start a run
     start_node: Arc<FlowNode>,
     nodes_by_id: HashMap<String, Arc<FlowNode>>,
+    mode: FlowMode,
 }
 
 impl Flow {
-    pub fn new(
-        id: String,
-        name: String,
-        schedule: Option<Schedule>,
-        trigger: Option<Expression>,
-        start_node: Arc<FlowNode>,
-        nodes_by_id: HashMap<String, Arc<FlowNode>>,
-    ) -> Result<Self, String> {
+    /// Creates a flow that is triggered by every store change, until other triggers are set. A flow without triggers only
+    /// runs when another flow calls it.
+    pub fn new(id: String, name: String, start_node: Arc<FlowNode>, nodes_by_id: HashMap<String, Arc<FlowNode>>) -> Result<Self, String> {
         match start_node.kind {
             FlowNodeKind::Start => Ok(Flow {
                 id,
                 name,
-                schedule,
-                trigger: trigger.unwrap_or(Literal { value: Value::Boolean(true) }),
+                triggers: vec![Trigger::new(
+                    "expression".to_string(),
+                    TriggerKind::Expression {
+                        expression: Literal { value: Value::Boolean(true) },
+                        mode: TriggerMode::Level,
+                    },
+                )],
+                condition: Literal { value: Value::Boolean(true) },
                 start_node,
                 nodes_by_id,
+                mode: FlowMode::default(),
             }),
             _ => Err("start_node must be of type FlowNodeKind::Start".to_string()),
         }
     }
 
+    pub fn with_mode(mut self, mode: FlowMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    pub fn with_triggers(mut self, triggers: Vec<Trigger>) -> Self {
+        self.triggers = triggers;
+        self
+    }
+
+    pub fn with_condition(mut self, condition: Expression) -> Self {
+        self.condition = condition;
+        self
+    }
+
     pub fn id(&self) -> &str {
         &self.id
     }
@@ -46,21 +64,127 @@ impl Flow {
         &self.name
     }
 
+    pub fn mode(&self) -> FlowMode {
+        self.mode
+    }
+
     pub fn start_node(&self) -> &FlowNode {
         &self.start_node
     }
 
-    pub fn trigger(&self) -> &Expression {
-        &self.trigger
+    pub fn triggers(&self) -> &[Trigger] {
+        &self.triggers
     }
 
-    pub fn schedule(&self) -> Option<Schedule> {
-        self.schedule.clone()
+    pub fn trigger_by_id(&self, id: &str) -> Option<&Trigger> {
+        self.triggers.iter().find(|trigger| trigger.id == id)
+    }
+
+    pub fn condition(&self) -> &Expression {
+        &self.condition
+    }
+
+    /// Returns the schedules of the flow with the ids of their triggers.
+    pub fn schedules(&self) -> impl Iterator<Item = (&str, &Schedule)> {
+        self.triggers.iter().filter_map(|trigger| match &trigger.kind {
+            TriggerKind::Schedule(schedule) => Some((trigger.id.as_str(), schedule)),
+            _ => None,
+        })
+    }
+
+    /// Returns the expressions of the reactive triggers of the flow with the ids and modes of their triggers.
+    pub fn trigger_expressions(&self) -> impl Iterator<Item = (&str, &Expression, TriggerMode)> {
+        self.triggers.iter().filter_map(|trigger| match &trigger.kind {
+            TriggerKind::Expression { expression, mode } => Some((trigger.id.as_str(), expression, *mode)),
+            _ => None,
+        })
     }
 
     pub fn node_by_id(&self, id: &str) -> Option<&FlowNode> {
         self.nodes_by_id.get(id).map(|node| node.as_ref())
     }
+
+    /// Returns the ids of the flows that are called by this flow.
+    pub fn called_flow_ids(&self) -> Vec<&str> {
+        self.nodes_by_id
+            .values()
+            .filter_map(|node| match node.kind() {
+                FlowNodeKind::CallFlow(call_flow_node) => Some(call_flow_node.flow_id()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns the id of the first sleep node of this flow, by id, or `None` if the flow never sleeps.
+    pub fn sleep_node_id(&self) -> Option<&str> {
+        self.nodes_by_id
+            .values()
+            .filter(|node| matches!(node.kind(), FlowNodeKind::Sleep(_)))
+            .map(|node| node.id())
+            .min()
+    }
+}
+
+/// Determines what happens when a flow is triggered while a previous run of it has not yet ended.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FlowMode {
+    Single,                          // Ignores new runs
+    Restart,                         // Cancels the running run and starts a new one
+    Queued { max: Option<usize> },   // Starts new runs once the previous ones ended, `max` includes the running run
+    Parallel { max: Option<usize> }, // Starts new runs next to the running ones
+}
+
+impl Default for FlowMode {
+    fn default() -> Self {
+        FlowMode::Parallel { max: None }
+    }
+}
+
+impl Display for FlowMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FlowMode::Single => write!(f, "single"),
+            FlowMode::Restart => write!(f, "restart"),
+            FlowMode::Queued { max: Some(max) } => write!(f, "queued, max {}", max),
+            FlowMode::Queued { max: None } => write!(f, "queued"),
+            FlowMode::Parallel { max: Some(max) } => write!(f, "parallel, max {}", max),
+            FlowMode::Parallel { max: None } => write!(f, "parallel"),
+        }
+    }
+}
+
+/// Starts runs of a flow, the id identifies the trigger within the flow and is available to the run.
+#[derive(PartialEq, Debug)]
+pub struct Trigger {
+    id: String,
+    kind: TriggerKind,
+}
+
+impl Trigger {
+    pub fn new(id: String, kind: TriggerKind) -> Self {
+        Trigger { id, kind }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn kind(&self) -> &TriggerKind {
+        &self.kind
+    }
+}
+
+#[derive(PartialEq, Debug)]
+pub enum TriggerKind {
+    Schedule(Schedule),
+    Expression { expression: Expression, mode: TriggerMode }, // Evaluated on store changes
+}
+
+/// Determines when the trigger of a reactive flow fires.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TriggerMode {
+    Edge,  // Fires when the trigger changes from false to true
+    Level, // Fires for every store change while the trigger is true
 }
 
 #[derive(Debug)]
@@ -114,7 +238,10 @@ pub enum FlowNodeKind {
     End,
     Conditional(Expression),
     Action(ActionFlowNode),
-    Sleep(Duration),
+    Sleep(SleepFlowNode),
+    Fork,
+    Join(usize), // Number of branches that must arrive before continuing
+    CallFlow(CallFlowNode),
 }
 
 #[derive(Debug)]
@@ -131,3 +258,43 @@ impl ActionFlowNode {
         self.action.as_ref()
     }
 }
+
+#[derive(Debug)]
+pub struct SleepFlowNode {
+    duration: Duration,
+    check_trigger: bool, // Whether the trigger is evaluated again when the run resumes
+}
+
+impl SleepFlowNode {
+    pub fn new(duration: Duration, check_trigger: bool) -> Self {
+        SleepFlowNode { duration, check_trigger }
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.duration
+    }
+
+    pub fn check_trigger(&self) -> bool {
+        self.check_trigger
+    }
+}
+
+#[derive(Debug)]
+pub struct CallFlowNode {
+    flow_id: String,
+    parameters: HashMap<String, Expression>,
+}
+
+impl CallFlowNode {
+    pub fn new(flow_id: String, parameters: HashMap<String, Expression>) -> Self {
+        CallFlowNode { flow_id, parameters }
+    }
+
+    pub fn flow_id(&self) -> &str {
+        &self.flow_id
+    }
+
+    pub fn parameters(&self) -> &HashMap<String, Expression> {
+        &self.parameters
+    }
+}
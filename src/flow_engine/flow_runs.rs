@@ -0,0 +1,304 @@
+use crate::flow_engine::FlowContinuation;
+use crate::flow_engine::continuation::RunId;
+use crate::flow_engine::flow::{Flow, FlowMode};
+use crate::flow_engine::scope::Scope;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use tokio::task::AbortHandle;
+use tracing::debug;
+
+/// Keeps track of the active runs of each flow to enforce its `FlowMode`. A run is active from the moment it starts
+/// until it reaches an end node, including the time it is suspended by a sleep node.
+#[derive(Debug, Default)]
+pub struct FlowRuns {
+    flows: Mutex<HashMap<String, FlowRunState>>,
+}
+
+#[derive(Debug, Default)]
+struct FlowRunState {
+    active: HashMap<RunId, ActiveRun>,
+    queued: VecDeque<FlowContinuation>,
+}
+
+#[derive(Debug, Default)]
+struct ActiveRun {
+    timer: Option<AbortHandle>, // Of the pending resumption, if suspended
+    task: Option<AbortHandle>,  // Executing the run, until it suspends or ends
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Admission {
+    Started,
+    Queued,
+    Rejected,
+}
+
+impl FlowRuns {
+    pub fn new() -> Self {
+        FlowRuns::default()
+    }
+
+    /// Decides whether a new run of the flow, started by the given trigger, may start according to the mode of the flow.
+    /// In restart mode, all active runs are cancelled.
+    pub fn admit(&self, flow: &Flow, run_id: RunId, trigger_id: Option<&str>) -> Admission {
+        let mut flows = self.flows.lock().unwrap();
+        let state = flows.entry(flow.id().to_string()).or_default();
+
+        let admission = match flow.mode() {
+            FlowMode::Single if !state.active.is_empty() => Admission::Rejected,
+            FlowMode::Restart => {
+                for (cancelled_run_id, active_run) in state.active.drain() {
+                    debug!(run_id = %cancelled_run_id, "⏹️ Cancelling run of flow '{}'", flow.name());
+                    active_run.abort();
+                }
+                Admission::Started
+            }
+            FlowMode::Queued { max } if !state.active.is_empty() => {
+                if max.is_some_and(|max| state.active.len() + state.queued.len() >= max) {
+                    Admission::Rejected
+                } else {
+                    let continuation = FlowContinuation::new(run_id, flow.start_node().id().to_string(), Scope::new(), false).with_trigger_id(trigger_id.map(str::to_string));
+                    state.queued.push_back(continuation);
+                    Admission::Queued
+                }
+            }
+            FlowMode::Parallel { max: Some(max) } if state.active.len() >= max => Admission::Rejected,
+            _ => Admission::Started,
+        };
+
+        if admission == Admission::Started {
+            state.active.insert(run_id, ActiveRun::default());
+        }
+        admission
+    }
+
+    pub fn is_active(&self, flow_id: &str, run_id: RunId) -> bool {
+        let flows = self.flows.lock().unwrap();
+        flows.get(flow_id).is_some_and(|state| state.active.contains_key(&run_id))
+    }
+
+    /// Registers the task that executes the run, so it can be aborted when the run is cancelled. Aborts the task right
+    /// away if the run was cancelled in the meantime.
+    pub fn execute(&self, flow_id: &str, run_id: RunId, task: AbortHandle) {
+        let mut flows = self.flows.lock().unwrap();
+        match flows.get_mut(flow_id).and_then(|state| state.active.get_mut(&run_id)) {
+            Some(active_run) => active_run.task = Some(task),
+            None => task.abort(),
+        }
+    }
+
+    /// Registers the timer that resumes a suspended run, so it can be aborted when the run is cancelled. Aborts the
+    /// timer right away if the run was cancelled in the meantime.
+    pub fn suspend(&self, flow_id: &str, run_id: RunId, timer: AbortHandle) {
+        let mut flows = self.flows.lock().unwrap();
+        match flows.get_mut(flow_id).and_then(|state| state.active.get_mut(&run_id)) {
+            Some(active_run) => active_run.timer = Some(timer),
+            None => timer.abort(),
+        }
+    }
+
+    /// Called when the timer of a suspended run fires, returns whether the run should resume.
+    pub fn resume(&self, flow_id: &str, run_id: RunId) -> bool {
+        let mut flows = self.flows.lock().unwrap();
+        match flows.get_mut(flow_id).and_then(|state| state.active.get_mut(&run_id)) {
+            Some(active_run) => {
+                active_run.timer = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Marks the run as ended. Returns the continuation of the next queued run if this was the last active run, the
+    /// queued run is active from then on.
+    pub fn finish(&self, flow_id: &str, run_id: RunId) -> Option<FlowContinuation> {
+        let mut flows = self.flows.lock().unwrap();
+        let state = flows.get_mut(flow_id)?;
+        state.active.remove(&run_id)?;
+        state.start_next_queued()
+    }
+
+    /// Cancels an active or queued run, the timer of a suspended run and the task of an executing run are aborted.
+    /// Returns the id of the flow of the run and, like `finish`, the continuation of the next queued run if this was the
+    /// last active run.
+    pub fn cancel(&self, run_id: RunId) -> Option<(String, Option<FlowContinuation>)> {
+        let mut flows = self.flows.lock().unwrap();
+        let (flow_id, state) = flows
+            .iter_mut()
+            .find(|(_, state)| state.active.contains_key(&run_id) || state.queued.iter().any(|queued| queued.run_id() == run_id))?;
+
+        let next = match state.active.remove(&run_id) {
+            Some(active_run) => {
+                active_run.abort();
+                state.start_next_queued()
+            }
+            None => {
+                state.queued.retain(|queued| queued.run_id() != run_id);
+                None
+            }
+        };
+        Some((flow_id.clone(), next))
+    }
+}
+
+impl FlowRunState {
+    /// Makes the next queued run active once no runs are active anymore.
+    fn start_next_queued(&mut self) -> Option<FlowContinuation> {
+        if !self.active.is_empty() {
+            return None;
+        }
+
+        let next = self.queued.pop_front()?;
+        self.active.insert(next.run_id(), ActiveRun::default());
+        Some(next)
+    }
+}
+
+impl ActiveRun {
+    fn abort(self) {
+        for handle in [self.timer, self.task].into_iter().flatten() {
+            handle.abort();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_engine::flow::{FlowNode, FlowNodeKind};
+    use pretty_assertions::assert_eq;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn flow(mode: FlowMode) -> Flow {
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start));
+        Flow::new("id".to_string(), "flow".to_string(), start_node, HashMap::new()).unwrap().with_mode(mode)
+    }
+
+    #[test]
+    fn single_mode_rejects_runs_while_a_run_is_active() {
+        let runs = FlowRuns::new();
+        let flow = flow(FlowMode::Single);
+        let first = RunId::next();
+
+        assert_eq!(runs.admit(&flow, first, None), Admission::Started);
+        assert_eq!(runs.admit(&flow, RunId::next(), None), Admission::Rejected);
+
+        assert!(runs.finish("id", first).is_none());
+        assert_eq!(runs.admit(&flow, RunId::next(), None), Admission::Started);
+    }
+
+    #[tokio::test]
+    async fn restart_mode_cancels_the_active_run_and_its_timer() {
+        let runs = FlowRuns::new();
+        let flow = flow(FlowMode::Restart);
+        let first = RunId::next();
+        let timer = tokio::spawn(tokio::time::sleep(Duration::from_secs(3600)));
+
+        assert_eq!(runs.admit(&flow, first, None), Admission::Started);
+        runs.suspend("id", first, timer.abort_handle());
+        let second = RunId::next();
+        assert_eq!(runs.admit(&flow, second, None), Admission::Started);
+
+        assert!(timer.await.unwrap_err().is_cancelled());
+        assert!(!runs.is_active("id", first));
+        assert!(!runs.resume("id", first));
+        assert!(runs.is_active("id", second));
+    }
+
+    #[tokio::test]
+    async fn restart_mode_aborts_the_task_of_the_executing_run() {
+        let runs = FlowRuns::new();
+        let flow = flow(FlowMode::Restart);
+        let first = RunId::next();
+        let task = tokio::spawn(tokio::time::sleep(Duration::from_secs(3600)));
+
+        assert_eq!(runs.admit(&flow, first, None), Admission::Started);
+        runs.execute("id", first, task.abort_handle());
+        assert_eq!(runs.admit(&flow, RunId::next(), None), Admission::Started);
+
+        assert!(task.await.unwrap_err().is_cancelled());
+        assert!(!runs.is_active("id", first));
+    }
+
+    #[tokio::test]
+    async fn execute_aborts_the_task_of_a_cancelled_run() {
+        let runs = FlowRuns::new();
+        let task = tokio::spawn(tokio::time::sleep(Duration::from_secs(3600)));
+
+        runs.execute("id", RunId::next(), task.abort_handle());
+
+        assert!(task.await.unwrap_err().is_cancelled());
+    }
+
+    #[test]
+    fn queued_mode_starts_the_next_run_once_the_active_run_finished() {
+        let runs = FlowRuns::new();
+        let flow = flow(FlowMode::Queued { max: Some(2) });
+        let first = RunId::next();
+        let second = RunId::next();
+
+        assert_eq!(runs.admit(&flow, first, None), Admission::Started);
+        assert_eq!(runs.admit(&flow, second, Some("frontDoor")), Admission::Queued);
+        assert_eq!(runs.admit(&flow, RunId::next(), None), Admission::Rejected);
+
+        let next = runs.finish("id", first).unwrap();
+        assert!(runs.is_active("id", second));
+        let (run_id, node_id, _, _, trigger_id) = next.into_parts();
+        assert_eq!(run_id, second);
+        assert_eq!(node_id, "startNode");
+        assert_eq!(trigger_id.as_deref(), Some("frontDoor"));
+        assert!(runs.finish("id", second).is_none());
+    }
+
+    #[test]
+    fn parallel_mode_rejects_runs_above_the_maximum() {
+        let runs = FlowRuns::new();
+        let flow = flow(FlowMode::Parallel { max: Some(2) });
+
+        assert_eq!(runs.admit(&flow, RunId::next(), None), Admission::Started);
+        assert_eq!(runs.admit(&flow, RunId::next(), None), Admission::Started);
+        assert_eq!(runs.admit(&flow, RunId::next(), None), Admission::Rejected);
+    }
+
+    #[test]
+    fn finishing_a_cancelled_run_does_not_start_a_queued_run() {
+        let runs = FlowRuns::new();
+
+        assert!(runs.finish("id", RunId::next()).is_none());
+    }
+
+    #[tokio::test]
+    async fn cancel_aborts_the_timer_of_a_suspended_run_and_starts_the_next_queued_run() {
+        let runs = FlowRuns::new();
+        let flow = flow(FlowMode::Queued { max: None });
+        let (first, second) = (RunId::next(), RunId::next());
+        runs.admit(&flow, first, None);
+        runs.admit(&flow, second, None);
+        let timer = tokio::spawn(tokio::time::sleep(Duration::from_secs(3600)));
+        runs.suspend("id", first, timer.abort_handle());
+
+        let (flow_id, next) = runs.cancel(first).unwrap();
+
+        assert_eq!(flow_id, "id");
+        assert_eq!(next.map(|next| next.run_id()), Some(second));
+        assert!(timer.await.unwrap_err().is_cancelled());
+        assert!(!runs.is_active("id", first));
+        assert!(runs.is_active("id", second));
+    }
+
+    #[test]
+    fn cancel_removes_a_queued_run() {
+        let runs = FlowRuns::new();
+        let flow = flow(FlowMode::Queued { max: None });
+        let (first, second) = (RunId::next(), RunId::next());
+        runs.admit(&flow, first, None);
+        runs.admit(&flow, second, None);
+
+        let (_, next) = runs.cancel(second).unwrap();
+
+        assert!(next.is_none());
+        assert!(runs.finish("id", first).is_none());
+        assert!(runs.cancel(second).is_none());
+    }
+}
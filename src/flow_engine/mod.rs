@@ -1,18 +1,25 @@
 pub mod action;
 mod action_registry;
 mod context;
+mod continuation;
 mod engine;
-mod expression;
+pub mod expression;
+mod expression_printer;
 pub mod flow;
+mod flow_runs;
 pub mod property_value;
 mod schedule;
 pub mod scheduler;
 mod scope;
+pub mod solar;
+mod trigger_states;
 
-pub use context::Context;
-pub use engine::FlowEngineError;
-pub use engine::FlowExecutionReport;
-pub use engine::execute;
-pub use expression::{Expression, Value};
-pub use schedule::Schedule;
+pub use context::{Context, ContextBuilder};
+pub use continuation::{FlowContinuation, RunId};
+pub use engine::start;
+pub use expression::{Expression, Value, Variables};
+pub use flow_runs::FlowRuns;
+pub use schedule::{DayCondition, Schedule, TimeWindow};
 pub use scheduler::{SchedulerCommand, scheduler};
+pub use solar::SolarEvent;
+pub use trigger_states::TriggerStates;
@@ -1,5 +1,7 @@
 use crate::domain::Number;
 use crate::domain::color::Color;
+use crate::flow_engine::expression::{ExpressionError, evaluate_with_variables};
+use crate::flow_engine::{Context, Expression, Value, Variables};
 
 #[derive(Clone, PartialEq, Debug)]
 pub enum PropertyValue {
@@ -10,3 +12,121 @@ pub enum PropertyValue {
     DecrementNumberValue(Number),
     SetColor(Color),
 }
+
+/// A property value of a `controlDevice` action, it is evaluated into a `PropertyValue` when the action executes.
+#[derive(PartialEq, Debug)]
+pub enum PropertyValueExpression {
+    SetBooleanValue(Payload<bool>),
+    ToggleBooleanValue,
+    SetNumberValue(Payload<Number>),
+    IncrementNumberValue(Payload<Number>),
+    DecrementNumberValue(Payload<Number>),
+    SetColor(Payload<Color>),
+}
+
+#[derive(PartialEq, Debug)]
+pub enum Payload<T> {
+    Literal(T),
+    Expression(Expression),
+}
+
+impl PropertyValueExpression {
+    pub fn evaluate(&self, context: &Context, variables: Option<&Variables>) -> Result<PropertyValue, ExpressionError> {
+        match self {
+            PropertyValueExpression::SetBooleanValue(payload) => payload.evaluate(context, variables).map(PropertyValue::SetBooleanValue),
+            PropertyValueExpression::ToggleBooleanValue => Ok(PropertyValue::ToggleBooleanValue),
+            PropertyValueExpression::SetNumberValue(payload) => payload.evaluate(context, variables).map(PropertyValue::SetNumberValue),
+            PropertyValueExpression::IncrementNumberValue(payload) => payload.evaluate(context, variables).map(PropertyValue::IncrementNumberValue),
+            PropertyValueExpression::DecrementNumberValue(payload) => payload.evaluate(context, variables).map(PropertyValue::DecrementNumberValue),
+            PropertyValueExpression::SetColor(payload) => payload.evaluate(context, variables).map(PropertyValue::SetColor),
+        }
+    }
+}
+
+impl<T: FromValue + Clone> Payload<T> {
+    fn evaluate(&self, context: &Context, variables: Option<&Variables>) -> Result<T, ExpressionError> {
+        match self {
+            Payload::Literal(value) => Ok(value.clone()),
+            Payload::Expression(expression) => {
+                let value = evaluate_with_variables(expression, context, variables)?;
+                T::from_value(&value).ok_or_else(|| ExpressionError::ValueTypeMismatch {
+                    expected: T::TYPE_NAME,
+                    actual: format!("{:?}", value),
+                })
+            }
+        }
+    }
+}
+
+/// Converts the value an expression evaluated to into the type of a payload.
+pub trait FromValue: Sized {
+    const TYPE_NAME: &'static str;
+
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for bool {
+    const TYPE_NAME: &'static str = "Boolean";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Boolean(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Number {
+    const TYPE_NAME: &'static str = "Number";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Color {
+    const TYPE_NAME: &'static str = "Color";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Color(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn evaluates_the_expression_of_a_payload() {
+        let property_value = PropertyValueExpression::IncrementNumberValue(Payload::Expression(Expression::Variable { name: "step".to_string() }));
+        let variables = Variables::from([("step".to_string(), Value::Number(Number::PositiveInt(10)))]);
+
+        let result = property_value.evaluate(&Context::default(), Some(&variables));
+
+        assert_eq!(result, Ok(PropertyValue::IncrementNumberValue(Number::PositiveInt(10))));
+    }
+
+    #[test]
+    fn evaluate_fails_if_the_expression_does_not_match_the_payload_type() {
+        let property_value = PropertyValueExpression::SetBooleanValue(Payload::Expression(Expression::Literal {
+            value: Value::Number(Number::PositiveInt(10)),
+        }));
+
+        let result = property_value.evaluate(&Context::default(), None);
+
+        assert_eq!(
+            result,
+            Err(ExpressionError::ValueTypeMismatch {
+                expected: "Boolean",
+                actual: "Number(PositiveInt(10))".to_string(),
+            })
+        );
+    }
+}
@@ -1,72 +1,194 @@
-use crate::domain::{GeoLocation, WeekdayCondition};
+use crate::domain::{Calendars, GeoLocation, Time, WeekdayCondition};
 use crate::extensions::date_time_ext::ToWeekday;
-use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use crate::flow_engine::SolarEvent;
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use humantime_serde::re::humantime::format_duration;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
 use std::fmt::{Display, Formatter};
-use std::ops::Add;
 use std::str::FromStr;
-use sunrise::{Coordinates, SolarDay};
+use std::sync::Arc;
 
+/// A sun event schedule stops looking for a day it runs on after this many days, as its calendars may not have any
+/// upcoming days or the sun may not reach the elevation of its event.
+const MAX_DAYS_WITHOUT_EVENT: usize = 4 * 366;
+
+/// A schedule of when a flow runs. The jitter of each kind moves every occurrence by a random offset of up to the
+/// jitter, earlier or later, zero for no jitter.
 #[derive(Clone, PartialEq, Debug)]
 pub enum Schedule {
-    Cron(String),
-    Sunrise { when: WeekdayCondition, offset: i64 },
-    Sunset { when: WeekdayCondition, offset: i64 },
+    Cron {
+        expression: String,
+        jitter: std::time::Duration,
+    },
+    Sun {
+        event: SolarEvent,
+        when: DayCondition,
+        offset: i64,
+        jitter: std::time::Duration,
+    },
+    Interval {
+        every: std::time::Duration,
+        window: Option<TimeWindow>, // Aligned to the Unix epoch, or to the start of the window
+        jitter: std::time::Duration,
+    },
+    At {
+        datetime: NaiveDateTime, // Once, in local time
+        jitter: std::time::Duration,
+    },
+}
+
+/// The time of day an interval schedule runs in, from inclusive and to exclusive. It wraps around midnight if to is
+/// before from.
+#[derive(Clone, PartialEq, Deserialize, Debug)]
+pub struct TimeWindow {
+    pub from: Time,
+    pub to: Time,
+}
+
+/// The days a sun event schedule runs on: the days that match the weekdays or are in one of the included calendars,
+/// except for the days in one of the excluded calendars.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DayCondition {
+    pub weekdays: WeekdayCondition,
+    pub include: Vec<String>,
+    pub exclude: Vec<String>,
+}
+
+impl DayCondition {
+    pub fn includes<Z: TimeZone>(&self, date: &DateTime<Z>, calendars: &Calendars) -> bool {
+        let in_calendar = |names: &[String]| names.iter().any(|name| calendars.get(name).is_some_and(|calendar| calendar.includes(date.date_naive())));
+        (self.weekdays.included_days().contains(&date.to_weekday()) || in_calendar(&self.include)) && !in_calendar(&self.exclude)
+    }
+}
+
+impl From<WeekdayCondition> for DayCondition {
+    fn from(weekdays: WeekdayCondition) -> Self {
+        DayCondition {
+            weekdays,
+            include: Vec::new(),
+            exclude: Vec::new(),
+        }
+    }
+}
+
+impl Display for DayCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let mut days = Vec::new();
+        if self.weekdays != WeekdayCondition::Set(Vec::new()) {
+            days.push(self.weekdays.to_string());
+        }
+        days.extend(self.include.iter().map(|calendar| format!("calendar {}", calendar)));
+        write!(f, "{}", days.join(" or "))?;
+
+        for calendar in &self.exclude {
+            write!(f, " except calendar {}", calendar)?;
+        }
+        Ok(())
+    }
 }
 
 impl Display for Schedule {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
-            Schedule::Cron(expression) => {
-                write!(f, "cron '{}'", expression)
-            }
-            Schedule::Sunrise { when, offset } => {
-                if offset == &0 {
-                    write!(f, "sunrise on {}", when)
-                } else {
-                    write!(f, "sunrise on {}, offset {}m", when, offset)
-                }
+            Schedule::Cron { expression, .. } => {
+                write!(f, "cron '{}'", expression)?;
             }
-            Schedule::Sunset { when, offset } => {
+            Schedule::Sun { event, when, offset, .. } => {
                 if offset == &0 {
-                    write!(f, "sunset on {}", when)
+                    write!(f, "{} on {}", event, when)?;
                 } else {
-                    write!(f, "sunset on {}, offset {}m", when, offset)
+                    write!(f, "{} on {}, offset {}m", event, when, offset)?;
                 }
             }
+            Schedule::Interval { every, window: None, .. } => write!(f, "every {}", format_duration(*every))?,
+            Schedule::Interval { every, window: Some(window), .. } => write!(f, "every {} between {} and {}", format_duration(*every), window.from, window.to)?,
+            Schedule::At { datetime, .. } => write!(f, "once at {}", datetime)?,
         }
-    }
-}
 
-#[derive(Debug)]
-enum SolarEvent {
-    Sunrise,
-    Sunset,
+        if !self.jitter().is_zero() {
+            write!(f, ", jitter ±{}", format_duration(self.jitter()))?;
+        }
+        Ok(())
+    }
 }
 
 impl Schedule {
     /// Returns an iterator which will return each `DateTime` that matches the schedule starting at the specified date and time.
-    pub fn after<Z>(&self, from: DateTime<Z>, location: GeoLocation) -> ScheduleIterator<Z>
+    pub fn after<Z>(&self, from: DateTime<Z>, location: GeoLocation, calendars: Arc<Calendars>) -> ScheduleIterator<Z>
     where
         Z: TimeZone,
     {
+        self.after_with_rng(from, location, calendars, StdRng::from_os_rng())
+    }
+
+    /// Like `after`, with the random number generator for the jitter, a seeded one makes the jitter deterministic.
+    pub fn after_with_rng<Z>(&self, from: DateTime<Z>, location: GeoLocation, calendars: Arc<Calendars>, rng: StdRng) -> ScheduleIterator<Z>
+    where
+        Z: TimeZone,
+    {
+        let mut jitter = Jitter::new(self.jitter(), rng);
         match self {
-            Schedule::Cron(expression) => {
+            Schedule::Cron { expression, .. } => {
                 let schedule = cron::Schedule::from_str(expression).unwrap_or_else(|_| panic!("invalid cron expression '{}'", expression));
                 let iterator = schedule.after_owned(from);
-                ScheduleIterator::Cron(iterator)
+                ScheduleIterator::Cron(iterator, jitter)
+            }
+            Schedule::Sun { event, when, offset, .. } => ScheduleIterator::SunEvent(SunEventIterator::new(location, calendars, from, when.clone(), *offset, jitter, event.clone())),
+            Schedule::Interval { every, window, .. } => ScheduleIterator::Interval(IntervalIterator::new(from, *every, window.clone(), jitter)),
+            Schedule::At { datetime, .. } => {
+                let at = from.timezone().from_local_datetime(datetime).earliest();
+                ScheduleIterator::Once(at.filter(|at| *at > from).map(|at| jitter.apply(at)))
             }
-            Schedule::Sunrise { when, offset } => ScheduleIterator::SunEvent(SunEventIterator::new(location, from, when.clone(), *offset, SolarEvent::Sunrise)),
-            Schedule::Sunset { when, offset } => ScheduleIterator::SunEvent(SunEventIterator::new(location, from, when.clone(), *offset, SolarEvent::Sunset)),
         }
     }
 
     /// Returns an iterator which will return each `DateTime` that matches the schedule starting at the current time.
-    pub fn upcoming<Z>(&self, timezone: Z, location: GeoLocation) -> ScheduleIterator<Z>
+    pub fn upcoming<Z>(&self, timezone: Z, location: GeoLocation, calendars: Arc<Calendars>) -> ScheduleIterator<Z>
     where
         Z: TimeZone,
     {
         let now = Utc::now().with_timezone(&timezone);
-        self.after(now, location)
+        self.after(now, location, calendars)
+    }
+
+    /// Returns the names of the calendars the schedule refers to.
+    pub fn calendars(&self) -> Vec<&str> {
+        match self {
+            Schedule::Cron { .. } | Schedule::Interval { .. } | Schedule::At { .. } => Vec::new(),
+            Schedule::Sun { when, .. } => when.include.iter().chain(&when.exclude).map(String::as_str).collect(),
+        }
+    }
+
+    pub fn jitter(&self) -> std::time::Duration {
+        match self {
+            Schedule::Cron { jitter, .. } | Schedule::Sun { jitter, .. } | Schedule::Interval { jitter, .. } | Schedule::At { jitter, .. } => *jitter,
+        }
+    }
+}
+
+/// Moves each occurrence of a schedule by a random offset of up to the maximum, earlier or later.
+pub struct Jitter {
+    max: i64, // In milliseconds
+    rng: StdRng,
+}
+
+impl Jitter {
+    fn new(max: std::time::Duration, rng: StdRng) -> Self {
+        Self {
+            max: max.as_millis().try_into().unwrap_or(i64::MAX),
+            rng,
+        }
+    }
+
+    fn apply<Z: TimeZone>(&mut self, time: DateTime<Z>) -> DateTime<Z> {
+        if self.max == 0 {
+            return time;
+        }
+
+        let offset = Duration::milliseconds(self.rng.random_range(-self.max..=self.max));
+        time.clone().checked_add_signed(offset).unwrap_or(time)
     }
 }
 
@@ -74,8 +196,10 @@ pub enum ScheduleIterator<Z>
 where
     Z: TimeZone,
 {
-    Cron(cron::OwnedScheduleIterator<Z>),
+    Cron(cron::OwnedScheduleIterator<Z>, Jitter),
     SunEvent(SunEventIterator<Z>),
+    Interval(IntervalIterator<Z>),
+    Once(Option<DateTime<Z>>), // Empty once the time has been returned or if it has passed
 }
 
 impl<Z> Iterator for ScheduleIterator<Z>
@@ -86,8 +210,10 @@ where
 
     fn next(&mut self) -> Option<Self::Item> {
         match self {
-            ScheduleIterator::Cron(iterator) => iterator.next(),
+            ScheduleIterator::Cron(iterator, jitter) => iterator.next().map(|time| jitter.apply(time)),
             ScheduleIterator::SunEvent(iter) => iter.next(),
+            ScheduleIterator::Interval(iter) => iter.next(),
+            ScheduleIterator::Once(at) => at.take(),
         }
     }
 }
@@ -96,11 +222,12 @@ pub struct SunEventIterator<Z>
 where
     Z: TimeZone,
 {
-    coordinates: Coordinates,
-    altitude: f64,
+    location: GeoLocation,
+    calendars: Arc<Calendars>,
     current: DateTime<Z>,
-    when: WeekdayCondition,
+    when: DayCondition,
     offset: i64,
+    jitter: Jitter,
     solar_event: SolarEvent,
 }
 
@@ -108,32 +235,22 @@ impl<Z> SunEventIterator<Z>
 where
     Z: TimeZone,
 {
-    fn new(location: GeoLocation, current: DateTime<Z>, when: WeekdayCondition, offset: i64, solar_event: SolarEvent) -> Self {
-        let coordinates = Coordinates::new(location.latitude, location.longitude).expect("valid coordinates");
+    fn new(location: GeoLocation, calendars: Arc<Calendars>, current: DateTime<Z>, when: DayCondition, offset: i64, jitter: Jitter, solar_event: SolarEvent) -> Self {
         Self {
-            coordinates,
-            altitude: location.altitude,
+            location,
+            calendars,
             current,
             when,
             offset,
+            jitter,
             solar_event,
         }
     }
 
-    fn event_time(&self, date: NaiveDate) -> DateTime<Z>
-    where
-        Z: TimeZone,
-    {
-        let event = match self.solar_event {
-            SolarEvent::Sunrise => sunrise::SolarEvent::Sunrise,
-            SolarEvent::Sunset => sunrise::SolarEvent::Sunset,
-        };
-
-        SolarDay::new(self.coordinates, date)
-            .with_altitude(self.altitude)
-            .event_time(event)
-            .with_timezone(&self.current.timezone())
-            .add(Duration::minutes(self.offset))
+    /// Returns the time of the event on the day of the given date, if the sun reaches the elevation of the event that day.
+    fn event_time(&self, date: &DateTime<Z>) -> Option<DateTime<Z>> {
+        let time = self.solar_event.time_on(&self.location, date.date_naive())?;
+        Some(time.with_timezone(&date.timezone()) + Duration::minutes(self.offset))
     }
 }
 
@@ -144,15 +261,84 @@ where
     type Item = DateTime<Z>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
+        for _ in 0..MAX_DAYS_WITHOUT_EVENT {
             let date = self.current.clone();
             self.current += Duration::days(1);
-            if !self.when.included_days().contains(&date.to_weekday()) {
-                continue;
+            if self.when.includes(&date, &self.calendars)
+                && let Some(time) = self.event_time(&date)
+            {
+                return Some(self.jitter.apply(time));
             }
-            let event_time = self.event_time(date.date_naive());
-            return Some(event_time);
         }
+        None
+    }
+}
+
+pub struct IntervalIterator<Z>
+where
+    Z: TimeZone,
+{
+    current: DateTime<Z>,
+    every: Duration,
+    window: Option<TimeWindow>,
+    jitter: Jitter,
+}
+
+impl<Z> IntervalIterator<Z>
+where
+    Z: TimeZone,
+{
+    fn new(current: DateTime<Z>, every: std::time::Duration, window: Option<TimeWindow>, jitter: Jitter) -> Self {
+        Self {
+            current,
+            every: Duration::from_std(every).unwrap_or(Duration::MAX),
+            window,
+            jitter,
+        }
+    }
+
+    /// Returns the first multiple of the interval since the Unix epoch after the current time.
+    fn next_since_epoch(&self) -> Option<DateTime<Z>> {
+        let every = self.every.num_milliseconds();
+        let next = (self.current.timestamp_millis().div_euclid(every) + 1).checked_mul(every)?;
+        self.current.timezone().timestamp_millis_opt(next).single()
+    }
+
+    /// Returns the first time after the current time that is a multiple of the interval since the start of a window,
+    /// and before the end of that window. The window that started yesterday may still be open.
+    fn next_in_window(&self, window: &TimeWindow) -> Option<DateTime<Z>> {
+        let (from, to) = (window.from.to_naive_time()?, window.to.to_naive_time()?);
+        let length = if to > from { to - from } else { to - from + Duration::days(1) };
+        let every = self.every.num_milliseconds();
+
+        let yesterday = self.current.date_naive().pred_opt()?;
+        yesterday.iter_days().take(4).find_map(|date| {
+            let start = self.current.timezone().from_local_datetime(&date.and_time(from)).earliest()?;
+            let elapsed = (self.current.clone() - start.clone()).num_milliseconds();
+            let count = if elapsed < 0 { 0 } else { elapsed / every + 1 };
+            let next = start.clone().checked_add_signed(Duration::milliseconds(count.checked_mul(every)?))?;
+            (next < start + length).then_some(next)
+        })
+    }
+}
+
+impl<Z> Iterator for IntervalIterator<Z>
+where
+    Z: TimeZone,
+{
+    type Item = DateTime<Z>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.every < Duration::milliseconds(1) {
+            return None; // Not a valid interval
+        }
+
+        let next = match &self.window {
+            Some(window) => self.next_in_window(window),
+            None => self.next_since_epoch(),
+        }?;
+        self.current = next.clone(); // The next occurrence follows the one without jitter
+        Some(self.jitter.apply(next))
     }
 }
 
@@ -160,14 +346,20 @@ where
 mod tests {
     use super::*;
     use crate::domain::Weekday::*;
+    use crate::domain::{Calendar, Date};
     use chrono::{Datelike, NaiveDate};
     use pretty_assertions::assert_eq;
+    use rand::SeedableRng;
+    use rstest::rstest;
 
     #[test]
     fn test_schedule_with_cron() {
-        let schedule = Schedule::Cron("0 0 20 * * *".to_string());
+        let schedule = Schedule::Cron {
+            expression: "0 0 20 * * *".to_string(),
+            jitter: std::time::Duration::ZERO,
+        };
         let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap();
-        let upcoming = schedule.after(now, location()).take(5).collect::<Vec<_>>();
+        let upcoming = schedule.after(now, location(), Arc::default()).take(5).collect::<Vec<_>>();
 
         // Notice that the nanoseconds are passed, that's done to ensure that the occurence is exactly at 20:00:00Z on the hour without subsecond drift
         let first = NaiveDate::from_ymd_opt(2000, 8, 4).and_then(|dt| dt.and_hms_nano_opt(20, 0, 0, 0)).unwrap().and_utc();
@@ -180,13 +372,15 @@ mod tests {
 
     #[test]
     fn test_schedule_with_sunrise_event() {
-        let schedule = Schedule::Sunrise {
-            when: WeekdayCondition::Range { start: Wednesday, end: Friday },
+        let schedule = Schedule::Sun {
+            event: SolarEvent::Sunrise,
+            when: WeekdayCondition::Range { start: Wednesday, end: Friday }.into(),
             offset: 0,
+            jitter: std::time::Duration::ZERO,
         };
 
         let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap(); // A Friday
-        let upcoming = schedule.after(now, location()).take(5).collect::<Vec<_>>();
+        let upcoming = schedule.after(now, location(), Arc::default()).take(5).collect::<Vec<_>>();
 
         assert_eq!(upcoming[0], Utc.with_ymd_and_hms(2000, 08, 04, 04, 10, 14).unwrap());
         assert_eq!(upcoming[1], Utc.with_ymd_and_hms(2000, 08, 09, 04, 18, 11).unwrap());
@@ -203,13 +397,15 @@ mod tests {
 
     #[test]
     fn test_schedule_with_sunset_event() {
-        let schedule = Schedule::Sunset {
-            when: WeekdayCondition::Range { start: Wednesday, end: Friday },
+        let schedule = Schedule::Sun {
+            event: SolarEvent::Sunset,
+            when: WeekdayCondition::Range { start: Wednesday, end: Friday }.into(),
             offset: 0,
+            jitter: std::time::Duration::ZERO,
         };
 
         let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap(); // A Friday
-        let upcoming = schedule.after(now, location()).take(5).collect::<Vec<_>>();
+        let upcoming = schedule.after(now, location(), Arc::default()).take(5).collect::<Vec<_>>();
 
         assert_eq!(upcoming[0], Utc.with_ymd_and_hms(2000, 08, 04, 19, 26, 57).unwrap());
         assert_eq!(upcoming[1], Utc.with_ymd_and_hms(2000, 08, 09, 19, 17, 55).unwrap());
@@ -224,6 +420,187 @@ mod tests {
         assert_eq!(upcoming[4].to_weekday(), Wednesday);
     }
 
+    #[test]
+    fn test_schedule_with_sunrise_event_on_calendar_days() {
+        let calendars = Calendars::from([
+            ("holidays".to_string(), Calendar::new(vec![Date { year: None, month: 8, day: 7 }])),
+            ("vacation".to_string(), Calendar::new(vec![Date { year: Some(2000), month: 8, day: 12 }])),
+        ]);
+        let schedule = Schedule::Sun {
+            event: SolarEvent::Sunrise,
+            when: DayCondition {
+                weekdays: WeekdayCondition::Range { start: Monday, end: Tuesday },
+                include: vec!["vacation".to_string()],
+                exclude: vec!["holidays".to_string()],
+            },
+            offset: 0,
+            jitter: std::time::Duration::ZERO,
+        };
+
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap(); // A Friday
+        let upcoming = schedule
+            .after(now, location(), Arc::new(calendars))
+            .take(3)
+            .map(|date_time| date_time.date_naive())
+            .collect::<Vec<_>>();
+
+        // Monday the 7th is a holiday and Saturday the 12th a vacation day
+        let date = |day| NaiveDate::from_ymd_opt(2000, 8, day).unwrap();
+        assert_eq!(upcoming, vec![date(8), date(12), date(14)]);
+    }
+
+    #[test]
+    fn test_schedule_with_sunrise_event_without_upcoming_days() {
+        let schedule = Schedule::Sun {
+            event: SolarEvent::Sunrise,
+            when: DayCondition {
+                weekdays: WeekdayCondition::Set(Vec::new()),
+                include: vec!["unknown".to_string()],
+                exclude: Vec::new(),
+            },
+            offset: 0,
+            jitter: std::time::Duration::ZERO,
+        };
+
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap();
+        assert_eq!(schedule.after(now, location(), Arc::default()).next(), None);
+    }
+
+    #[test]
+    fn displays_the_days_of_a_sun_event_schedule() {
+        let schedule = Schedule::Sun {
+            event: SolarEvent::Sunset,
+            when: DayCondition {
+                weekdays: WeekdayCondition::Weekend,
+                include: vec!["vacation".to_string()],
+                exclude: vec!["holidays".to_string()],
+            },
+            offset: 0,
+            jitter: std::time::Duration::ZERO,
+        };
+
+        assert_eq!(schedule.to_string(), "sunset on weekend or calendar vacation except calendar holidays");
+        assert_eq!(schedule.calendars(), vec!["vacation", "holidays"]);
+    }
+
+    #[test]
+    fn test_schedule_with_interval() {
+        let schedule = Schedule::Interval {
+            every: std::time::Duration::from_secs(90),
+            window: None,
+            jitter: std::time::Duration::ZERO,
+        };
+
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 10).unwrap();
+        let upcoming = schedule.after(now, location(), Arc::default()).take(3).collect::<Vec<_>>();
+
+        assert_eq!(
+            upcoming,
+            vec![
+                Utc.with_ymd_and_hms(2000, 8, 4, 12, 1, 30).unwrap(),
+                Utc.with_ymd_and_hms(2000, 8, 4, 12, 3, 0).unwrap(),
+                Utc.with_ymd_and_hms(2000, 8, 4, 12, 4, 30).unwrap(),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case::before_the_window((4, 12), vec![(4, 22), (5, 0), (5, 2), (5, 22)])]
+    #[case::in_the_window_of_yesterday((5, 1), vec![(5, 2), (5, 22), (6, 0), (6, 2)])]
+    fn test_schedule_with_interval_in_window_across_midnight(#[case] now: (u32, u32), #[case] expected: Vec<(u32, u32)>) {
+        let schedule = Schedule::Interval {
+            every: std::time::Duration::from_secs(2 * 3600),
+            window: Some(TimeWindow {
+                from: Time { hour: 22, minute: 0, second: 0 },
+                to: Time { hour: 3, minute: 0, second: 0 },
+            }),
+            jitter: std::time::Duration::ZERO,
+        };
+
+        let now = Utc.with_ymd_and_hms(2000, 8, now.0, now.1, 0, 0).unwrap();
+        let upcoming = schedule.after(now, location(), Arc::default()).take(4).collect::<Vec<_>>();
+
+        let expected = expected
+            .into_iter()
+            .map(|(day, hour)| Utc.with_ymd_and_hms(2000, 8, day, hour, 0, 0).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(upcoming, expected);
+    }
+
+    #[rstest]
+    #[case::upcoming((2000, 8, 4), vec![Utc.with_ymd_and_hms(2000, 12, 24, 18, 0, 0).unwrap()])]
+    #[case::passed((2001, 1, 1), vec![])]
+    fn test_schedule_at_runs_once(#[case] now: (i32, u32, u32), #[case] expected: Vec<DateTime<Utc>>) {
+        let schedule = Schedule::At {
+            datetime: NaiveDate::from_ymd_opt(2000, 12, 24).unwrap().and_hms_opt(18, 0, 0).unwrap(),
+            jitter: std::time::Duration::ZERO,
+        };
+
+        let now = Utc.with_ymd_and_hms(now.0, now.1, now.2, 12, 0, 0).unwrap();
+        let upcoming = schedule.after(now, location(), Arc::default()).collect::<Vec<_>>();
+
+        assert_eq!(upcoming, expected);
+    }
+
+    #[test]
+    fn displays_interval_and_one_shot_schedules() {
+        let interval = Schedule::Interval {
+            every: std::time::Duration::from_secs(900),
+            window: Some(TimeWindow {
+                from: Time { hour: 7, minute: 0, second: 0 },
+                to: Time { hour: 23, minute: 0, second: 0 },
+            }),
+            jitter: std::time::Duration::ZERO,
+        };
+        let at = Schedule::At {
+            datetime: NaiveDate::from_ymd_opt(2026, 12, 24).unwrap().and_hms_opt(18, 0, 0).unwrap(),
+            jitter: std::time::Duration::ZERO,
+        };
+
+        assert_eq!(interval.to_string(), "every 15m between 07:00 and 23:00");
+        assert_eq!(at.to_string(), "once at 2026-12-24 18:00:00");
+    }
+
+    #[test]
+    fn test_schedule_with_jitter() {
+        let schedule = Schedule::Cron {
+            expression: "0 0 20 * * *".to_string(),
+            jitter: std::time::Duration::from_secs(20 * 60),
+        };
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap();
+        let upcoming = |seed| {
+            schedule
+                .after_with_rng(now, location(), Arc::default(), StdRng::seed_from_u64(seed))
+                .take(10)
+                .collect::<Vec<_>>()
+        };
+
+        let occurrences = upcoming(42);
+        for (day, occurrence) in (4..).zip(&occurrences) {
+            let scheduled = Utc.with_ymd_and_hms(2000, 8, day, 20, 0, 0).unwrap();
+            assert!((*occurrence - scheduled).abs() <= Duration::minutes(20), "{} is not around {}", occurrence, scheduled);
+        }
+        assert!(occurrences.iter().any(|occurrence| occurrence.time() != occurrences[0].time()));
+        assert_eq!(occurrences, upcoming(42));
+    }
+
+    #[test]
+    fn test_schedule_with_sun_event_and_jitter() {
+        let schedule = Schedule::Sun {
+            event: SolarEvent::Sunset,
+            when: WeekdayCondition::Any.into(),
+            offset: -30,
+            jitter: std::time::Duration::from_secs(5 * 60),
+        };
+        let now = Utc.with_ymd_and_hms(2000, 8, 4, 12, 0, 0).unwrap();
+
+        let occurrence = schedule.after_with_rng(now, location(), Arc::default(), StdRng::seed_from_u64(7)).next().unwrap();
+
+        let sunset = Utc.with_ymd_and_hms(2000, 8, 4, 19, 26, 57).unwrap();
+        assert!((occurrence - (sunset - Duration::minutes(30))).abs() <= Duration::minutes(5), "{}", occurrence);
+        assert_eq!(schedule.to_string(), "sunset on any, offset -30m, jitter ±5m");
+    }
+
     fn location() -> GeoLocation {
         GeoLocation {
             latitude: 51.8615899,
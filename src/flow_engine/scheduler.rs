@@ -1,19 +1,53 @@
-use crate::domain::GeoLocation;
-use crate::execute_flows::{execute_flow, execute_flows};
+use crate::domain::{Calendars, GeoLocation};
+use crate::execute_flows::{Cause, execute_flow, execute_flows};
+use crate::flow_engine::flow::Flow;
+use crate::flow_engine::schedule::ScheduleIterator;
+use crate::flow_engine::{FlowContinuation, RunId, Schedule};
 use crate::flow_registry::FlowRegistry;
 use crate::store::StoreSnapshot;
-use chrono::Local;
-use std::sync::Arc;
+use chrono::{DateTime, Local};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::oneshot;
 use tokio::sync::watch::Receiver as WatchReceiver;
+use tokio::task::{JoinHandle, JoinSet};
 use tokio::time::{Instant, sleep_until};
 use tracing::{debug, error, info, instrument, warn};
 
 #[derive(Debug)]
 pub enum SchedulerCommand {
-    Schedule { flow_id: String },
-    ScheduleOnce { flow_id: String, node_id: String, delay: Duration },
+    // Schedules a flow, or reschedules it if it is scheduled already
+    Schedule {
+        flow_id: String,
+    },
+    Unschedule {
+        flow_id: String,
+    },
+    ScheduleOnce {
+        flow_id: String,
+        delay: Duration,
+        continuation: FlowContinuation,
+    },
+    // Evaluates the triggers of a reactive flow again after the delay, for triggers that change with the passing of time
+    Reevaluate {
+        flow_id: String,
+        delay: Duration,
+    },
+    // Cancels an active or queued run
+    Cancel {
+        run_id: RunId,
+    },
+    // Skips scheduled runs until resumed, suspended runs still resume
+    Pause,
+    Resume,
+    // Replies with the next fire times of each scheduled flow, at most `count` per flow
+    ListUpcoming {
+        count: usize,
+        reply: oneshot::Sender<HashMap<String, Vec<DateTime<Local>>>>,
+    },
 }
 
 // Pass config
@@ -24,65 +58,431 @@ pub async fn scheduler(
     notifier_rx: WatchReceiver<StoreSnapshot>,
     flow_registry: Arc<FlowRegistry>,
     geo_location: GeoLocation,
+    calendars: Arc<Calendars>,
 ) {
+    let mut scheduler = Scheduler {
+        tx,
+        notifier_rx,
+        flow_registry,
+        geo_location,
+        calendars,
+        jobs: HashMap::new(),
+        wake_ups: JoinSet::new(),
+        paused: Arc::new(AtomicBool::new(false)),
+    };
+
     while let Some(cmd) = rx.recv().await {
+        while scheduler.wake_ups.try_join_next().is_some() {} // Forgets the wake-ups that are done
+
         match cmd {
-            SchedulerCommand::Schedule { flow_id } => {
-                let Some(flow) = flow_registry.by_id(&flow_id) else {
-                    warn!("🕗 Scheduling flow '{}'... failed, flow not found", flow_id);
-                    continue;
+            SchedulerCommand::Schedule { flow_id } => scheduler.schedule(&flow_id),
+            SchedulerCommand::Unschedule { flow_id } => scheduler.unschedule(&flow_id),
+            SchedulerCommand::ScheduleOnce { flow_id, delay, continuation } => scheduler.schedule_once(flow_id, delay, continuation),
+            SchedulerCommand::Reevaluate { flow_id, delay } => scheduler.reevaluate(flow_id, delay),
+            SchedulerCommand::Cancel { run_id } => scheduler.cancel(run_id),
+            SchedulerCommand::Pause => {
+                scheduler.paused.store(true, Ordering::Relaxed);
+                info!("🕗 Paused scheduled runs");
+            }
+            SchedulerCommand::Resume => {
+                scheduler.paused.store(false, Ordering::Relaxed);
+                info!("🕗 Resumed scheduled runs");
+            }
+            SchedulerCommand::ListUpcoming { count, reply } => {
+                // The caller may have stopped waiting for the reply, which is fine
+                let _ = reply.send(scheduler.upcoming(count));
+            }
+        }
+    }
+}
+
+/// Owns the tasks it spawns: a job loop per schedule of each scheduled flow, and a wake-up per suspended run. The job
+/// loops are aborted when the scheduler stops, the wake-ups when their `JoinSet` is dropped. Runs that started already
+/// are not aborted with them.
+struct Scheduler {
+    tx: Sender<SchedulerCommand>,
+    notifier_rx: WatchReceiver<StoreSnapshot>,
+    flow_registry: Arc<FlowRegistry>,
+    geo_location: GeoLocation,
+    calendars: Arc<Calendars>,
+    jobs: HashMap<String, Vec<Job>>, // Job loops by flow id
+    wake_ups: JoinSet<()>,
+    paused: Arc<AtomicBool>,
+}
+
+impl Scheduler {
+    fn schedule(&mut self, flow_id: &str) {
+        let Some(flow) = self.flow_registry.by_id(flow_id) else {
+            warn!("🕗 Scheduling flow '{}'... failed, flow not found", flow_id);
+            return;
+        };
+
+        debug!("🕗 Scheduling flow '{}'...", flow.name());
+        if flow.schedules().next().is_none() {
+            error!("🕗 Scheduling flow '{}'... failed, not a scheduled flow", flow.name());
+            return;
+        }
+
+        if let Some(jobs) = self.jobs.remove(flow_id) {
+            debug!("🕗 Flow '{}' is scheduled already, rescheduling", flow.name());
+            jobs.iter().for_each(Job::abort);
+        }
+
+        // A job loop per schedule, each runs the flow for its own trigger
+        let jobs = flow.schedules().map(|(trigger_id, schedule)| self.spawn_job(&flow, trigger_id, schedule)).collect();
+        self.jobs.insert(flow_id.to_string(), jobs);
+    }
+
+    fn spawn_job(&self, flow: &Arc<Flow>, trigger_id: &str, schedule: &Schedule) -> Job {
+        for calendar in schedule.calendars().into_iter().filter(|calendar| !self.calendars.contains_key(*calendar)) {
+            warn!("⚠️ Flow '{}' is scheduled on days of unknown calendar '{}', it has no days", flow.name(), calendar);
+        }
+        info!(trigger = trigger_id, schedule = schedule.to_string(), "🕗 Scheduling flow '{}'... OK", flow.name());
+
+        let planned = Arc::new(Mutex::new(PlannedFires {
+            upcoming: schedule.upcoming(Local, self.geo_location.clone(), self.calendars.clone()),
+            planned: VecDeque::new(),
+        }));
+        let planned_clone = planned.clone();
+        let flow = flow.clone();
+        let trigger_id = trigger_id.to_string();
+        let notifier_rx = self.notifier_rx.clone();
+        let tx = self.tx.clone();
+        let flow_registry = self.flow_registry.clone();
+        let geo_location = self.geo_location.clone();
+        let calendars = self.calendars.clone();
+        let paused = self.paused.clone();
+        let handle = tokio::spawn(async move {
+            loop {
+                let next = planned_clone.lock().unwrap().first();
+                let Some(datetime) = next else {
+                    break;
                 };
+                let duration = datetime.signed_duration_since(Local::now());
+                if duration.num_milliseconds() < 0 {
+                    planned_clone.lock().unwrap().pop_first();
+                    continue; // Already passed
+                }
 
-                let flow_name = flow.name().to_string();
-                debug!("🕗 Scheduling flow '{}'...", flow_name);
+                let scheduled_instant = Instant::now() + Duration::from_millis(duration.num_milliseconds() as u64);
+                sleep_until(scheduled_instant).await;
+                planned_clone.lock().unwrap().pop_first();
 
-                let Some(schedule) = flow.schedule() else {
-                    error!("🕗 Scheduling flow '{}'... failed, not a scheduled flow", flow.name());
+                if paused.load(Ordering::Relaxed) {
+                    debug!(trigger = trigger_id, "🕗 Scheduled runs are paused, skipping flow '{}'", flow.name());
                     continue;
-                };
+                }
 
-                let schedule_str = schedule.to_string();
-
-                // Job loop
-                let notifier_rx_clone = notifier_rx.clone();
-                let tx_clone = tx.clone();
-                let geo_location_clone = geo_location.clone();
-                tokio::spawn(async move {
-                    for datetime in schedule.upcoming(Local, geo_location_clone.clone()) {
-                        let duration = datetime.signed_duration_since(Local::now());
-                        if duration.num_milliseconds() < 0 {
-                            continue; // Already passed
-                        }
-
-                        let scheduled_instant = Instant::now() + Duration::from_millis(duration.num_milliseconds() as u64);
-                        sleep_until(scheduled_instant).await;
-
-                        debug!("🕗 Running scheduled flow '{}'...", flow.name());
-                        let snapshot = notifier_rx_clone.borrow().clone();
-                        execute_flows(vec![flow.clone()], snapshot, tx_clone.clone(), geo_location_clone.clone()).await;
-                    }
-                });
-                info!(schedule = schedule_str, "🕗 Scheduling flow '{}'... OK", flow_name);
+                debug!(trigger = trigger_id, "🕗 Running scheduled flow '{}'...", flow.name());
+                // The run gets a task of its own, so aborting the job loop only stops the runs that are still to come
+                tokio::spawn(execute_flows(
+                    vec![flow.clone()],
+                    Cause::Schedule(trigger_id.clone()),
+                    notifier_rx.clone(),
+                    tx.clone(),
+                    flow_registry.clone(),
+                    geo_location.clone(),
+                    calendars.clone(),
+                ));
             }
-            SchedulerCommand::ScheduleOnce { flow_id, node_id, delay } => {
-                let Some(flow) = flow_registry.by_id(&flow_id) else {
-                    warn!("🕗 Scheduling flow '{}'... failed, flow not found", flow_id);
-                    return;
-                };
+            debug!(trigger = trigger_id, "🕗 Schedule of flow '{}' has no upcoming runs", flow.name());
+        });
+        Job { handle, planned }
+    }
 
-                debug!("🕗 Scheduling flow '{}' to run node '{}' after {:?}... OK", flow_id, node_id, delay);
-                let notifier_rx_clone = notifier_rx.clone();
-                let tx_clone = tx.clone();
-                let geo_location_clone = geo_location.clone();
-                tokio::spawn(async move {
-                    let scheduled_instant = Instant::now() + Duration::from_millis(delay.as_millis() as u64);
-                    sleep_until(scheduled_instant).await;
-
-                    debug!("🕗 Waking up flow '{}'...", flow.name());
-                    let snapshot = notifier_rx_clone.borrow().clone();
-                    execute_flow(flow, Some(node_id), snapshot, tx_clone.clone(), geo_location_clone.clone()).await;
-                });
+    fn unschedule(&mut self, flow_id: &str) {
+        match self.jobs.remove(flow_id) {
+            Some(jobs) => {
+                jobs.iter().for_each(Job::abort);
+                info!("🕗 Unscheduling flow '{}'... OK", flow_id);
             }
+            None => warn!("🕗 Unscheduling flow '{}'... failed, flow is not scheduled", flow_id),
         }
     }
+
+    fn schedule_once(&mut self, flow_id: String, delay: Duration, continuation: FlowContinuation) {
+        let Some(flow) = self.flow_registry.by_id(&flow_id) else {
+            warn!("🕗 Scheduling flow '{}'... failed, flow not found", flow_id);
+            return;
+        };
+
+        debug!(
+            run_id = %continuation.run_id(),
+            "🕗 Scheduling flow '{}' to run node '{}' after {:?}... OK",
+            flow_id,
+            continuation.node_id(),
+            delay
+        );
+        // Cancelling the run aborts the timer, or the task that executes the run once it resumed
+        let run_id = continuation.run_id();
+        let scheduled_instant = Instant::now() + Duration::from_millis(delay.as_millis() as u64);
+        let timer = tokio::spawn(sleep_until(scheduled_instant));
+        self.flow_registry.runs().suspend(&flow_id, run_id, timer.abort_handle());
+
+        let notifier_rx = self.notifier_rx.clone();
+        let tx = self.tx.clone();
+        let flow_registry = self.flow_registry.clone();
+        let geo_location = self.geo_location.clone();
+        let calendars = self.calendars.clone();
+        self.wake_ups.spawn(async move {
+            if timer.await.is_err() || !flow_registry.runs().resume(flow.id(), run_id) {
+                debug!(run_id = %run_id, "🕗 Run of flow '{}' was cancelled, not waking up", flow.name());
+                return;
+            }
+
+            debug!("🕗 Waking up flow '{}'...", flow.name());
+            execute_flow(flow, Some(continuation), notifier_rx, tx, flow_registry, geo_location, calendars).await;
+        });
+    }
+
+    fn reevaluate(&mut self, flow_id: String, delay: Duration) {
+        let Some(flow) = self.flow_registry.by_id(&flow_id) else {
+            warn!("🕗 Evaluating flow '{}' again... failed, flow not found", flow_id);
+            return;
+        };
+
+        debug!("🕗 Evaluating flow '{}' again after {:?}", flow.name(), delay);
+        let notifier_rx = self.notifier_rx.clone();
+        let tx = self.tx.clone();
+        let flow_registry = self.flow_registry.clone();
+        let geo_location = self.geo_location.clone();
+        let calendars = self.calendars.clone();
+        self.wake_ups.spawn(async move {
+            tokio::time::sleep(delay).await;
+            execute_flows(vec![flow], Cause::Reevaluation, notifier_rx, tx, flow_registry, geo_location, calendars).await;
+        });
+    }
+
+    fn cancel(&mut self, run_id: RunId) {
+        match self.flow_registry.runs().cancel(run_id) {
+            Some((flow_id, next)) => {
+                info!(run_id = %run_id, "⏹️ Cancelled run of flow '{}'", flow_id);
+                if let Some(next) = next {
+                    debug!(run_id = %next.run_id(), "⏯️ Starting queued run");
+                    self.schedule_once(flow_id, Duration::ZERO, next);
+                }
+            }
+            None => warn!(run_id = %run_id, "⏹️ Cancelling run... failed, run not found"),
+        }
+    }
+
+    /// Returns the next fire times of each scheduled flow, merged over its schedules. These are the times the job loops
+    /// planned, so they include the jitter the runs will have.
+    fn upcoming(&self, count: usize) -> HashMap<String, Vec<DateTime<Local>>> {
+        self.jobs
+            .iter()
+            .map(|(flow_id, jobs)| {
+                let mut times: Vec<DateTime<Local>> = jobs.iter().flat_map(|job| job.planned.lock().unwrap().next(count)).collect();
+                times.sort();
+                times.truncate(count);
+                (flow_id.clone(), times)
+            })
+            .collect()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.jobs.values().flatten().for_each(Job::abort);
+    }
+}
+
+/// The job loop of a schedule and the fire times it planned.
+struct Job {
+    handle: JoinHandle<()>,
+    planned: Arc<Mutex<PlannedFires>>,
+}
+
+impl Job {
+    fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+/// The fire times of a schedule, the first one is the one the job loop waits for. Times are planned when they are
+/// needed, once planned their jitter does not change anymore.
+struct PlannedFires {
+    upcoming: ScheduleIterator<Local>,
+    planned: VecDeque<DateTime<Local>>,
+}
+
+impl PlannedFires {
+    /// Returns the next `count` fire times, planning more of them if needed.
+    fn next(&mut self, count: usize) -> Vec<DateTime<Local>> {
+        while self.planned.len() < count
+            && let Some(datetime) = self.upcoming.next()
+        {
+            self.planned.push_back(datetime);
+        }
+        self.planned.iter().take(count).cloned().collect()
+    }
+
+    fn first(&mut self) -> Option<DateTime<Local>> {
+        self.next(1).pop()
+    }
+
+    fn pop_first(&mut self) {
+        self.planned.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_engine::Value;
+    use crate::flow_engine::flow::{FlowLink, FlowMode, FlowNode, FlowNodeKind, SleepFlowNode, Trigger, TriggerKind};
+    use crate::flow_engine::flow_runs::Admission;
+    use crate::flow_engine::scope::Scope;
+    use chrono::Timelike;
+    use pretty_assertions::assert_eq;
+    use tokio::sync::{mpsc, watch};
+
+    fn flow(id: &str, cron_expressions: &[&str]) -> Flow {
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start));
+        let triggers = cron_expressions
+            .iter()
+            .enumerate()
+            .map(|(index, expression)| {
+                Trigger::new(
+                    format!("schedule{}", index),
+                    TriggerKind::Schedule(Schedule::Cron {
+                        expression: expression.to_string(),
+                        jitter: Duration::ZERO,
+                    }),
+                )
+            })
+            .collect();
+        Flow::new(id.to_string(), id.to_string(), start_node, HashMap::new()).unwrap().with_triggers(triggers)
+    }
+
+    // Runs every second and then sleeps for an hour, so its runs stay active
+    fn sleeping_flow(id: &str) -> Flow {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let sleep_node = Arc::new(FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(end_node.clone(), Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(3600), false)),
+        ));
+        let start_node = Arc::new(FlowNode::new(
+            "startNode".to_string(),
+            vec![FlowLink::new(sleep_node.clone(), Value::None)],
+            FlowNodeKind::Start,
+        ));
+        let nodes_by_id = HashMap::from([("sleepNode".to_string(), sleep_node), ("endNode".to_string(), end_node)]);
+        let trigger = Trigger::new(
+            "schedule".to_string(),
+            TriggerKind::Schedule(Schedule::Cron {
+                expression: "* * * * * *".to_string(),
+                jitter: Duration::ZERO,
+            }),
+        );
+        Flow::new(id.to_string(), id.to_string(), start_node, nodes_by_id)
+            .unwrap()
+            .with_triggers(vec![trigger])
+            .with_mode(FlowMode::Single)
+    }
+
+    fn start_scheduler(flows: Vec<Flow>) -> (Sender<SchedulerCommand>, Arc<FlowRegistry>) {
+        let (tx, rx) = mpsc::channel::<SchedulerCommand>(32);
+        let (_, notifier_rx) = watch::channel(StoreSnapshot::default());
+        let flow_registry = Arc::new(FlowRegistry::new(flows));
+        tokio::spawn(scheduler(tx.clone(), rx, notifier_rx, flow_registry.clone(), GeoLocation::default(), Arc::default()));
+        (tx, flow_registry)
+    }
+
+    async fn list_upcoming(tx: &Sender<SchedulerCommand>, count: usize) -> HashMap<String, Vec<DateTime<Local>>> {
+        let (reply, reply_rx) = oneshot::channel();
+        tx.send(SchedulerCommand::ListUpcoming { count, reply }).await.unwrap();
+        reply_rx.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn lists_the_upcoming_runs_of_the_scheduled_flows_over_all_their_schedules() {
+        let (tx, _) = start_scheduler(vec![flow("twiceDaily", &["0 0 7 * * *", "0 0 19 * * *"]), flow("daily", &["0 30 12 * * *"])]);
+        tx.send(SchedulerCommand::Schedule { flow_id: "twiceDaily".to_string() }).await.unwrap();
+
+        let upcoming = list_upcoming(&tx, 3).await;
+
+        assert_eq!(upcoming.keys().collect::<Vec<_>>(), vec!["twiceDaily"]);
+        let times = &upcoming["twiceDaily"];
+        assert_eq!(times.len(), 3);
+        assert!(times.is_sorted());
+        assert!(times.iter().all(|time| time.hour() == 7 || time.hour() == 19));
+    }
+
+    #[tokio::test]
+    async fn lists_the_planned_runs_with_the_jitter_they_will_run_with() {
+        let trigger = Trigger::new(
+            "schedule".to_string(),
+            TriggerKind::Schedule(Schedule::Cron {
+                expression: "0 0 7 * * *".to_string(),
+                jitter: Duration::from_secs(1800),
+            }),
+        );
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start));
+        let flow = Flow::new("jittered".to_string(), "jittered".to_string(), start_node, HashMap::new())
+            .unwrap()
+            .with_triggers(vec![trigger]);
+        let (tx, _) = start_scheduler(vec![flow]);
+        tx.send(SchedulerCommand::Schedule { flow_id: "jittered".to_string() }).await.unwrap();
+
+        let first = list_upcoming(&tx, 2).await.remove("jittered").unwrap();
+        let second = list_upcoming(&tx, 3).await.remove("jittered").unwrap();
+
+        assert_eq!(first.len(), 2);
+        assert_eq!(second[..2], first[..]);
+    }
+
+    #[tokio::test]
+    async fn unscheduled_flows_have_no_upcoming_runs() {
+        let (tx, _) = start_scheduler(vec![flow("daily", &["0 30 12 * * *"])]);
+        tx.send(SchedulerCommand::Schedule { flow_id: "daily".to_string() }).await.unwrap();
+        tx.send(SchedulerCommand::Schedule { flow_id: "daily".to_string() }).await.unwrap();
+        assert_eq!(list_upcoming(&tx, 1).await["daily"].len(), 1);
+
+        tx.send(SchedulerCommand::Unschedule { flow_id: "daily".to_string() }).await.unwrap();
+
+        assert!(list_upcoming(&tx, 1).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancels_a_suspended_run() {
+        let (tx, flow_registry) = start_scheduler(vec![sleeping_flow("sleeping")]);
+        let flow = flow_registry.by_id("sleeping").unwrap();
+        let run_id = RunId::next();
+        assert_eq!(flow_registry.runs().admit(&flow, run_id, None), Admission::Started);
+        let continuation = FlowContinuation::new(run_id, "sleepNode".to_string(), Scope::new(), false);
+        tx.send(SchedulerCommand::ScheduleOnce {
+            flow_id: "sleeping".to_string(),
+            delay: Duration::from_secs(3600),
+            continuation,
+        })
+        .await
+        .unwrap();
+
+        tx.send(SchedulerCommand::Cancel { run_id }).await.unwrap();
+        list_upcoming(&tx, 1).await; // Waits until the scheduler handled the commands
+
+        assert!(!flow_registry.runs().is_active("sleeping", run_id));
+    }
+
+    #[tokio::test]
+    async fn skips_the_scheduled_runs_while_paused() {
+        let (tx, flow_registry) = start_scheduler(vec![sleeping_flow("sleeping")]);
+        let flow = flow_registry.by_id("sleeping").unwrap();
+        tx.send(SchedulerCommand::Pause).await.unwrap();
+        tx.send(SchedulerCommand::Schedule { flow_id: "sleeping".to_string() }).await.unwrap();
+
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        // No run started, so the flow admits a new one
+        let run_id = RunId::next();
+        assert_eq!(flow_registry.runs().admit(&flow, run_id, None), Admission::Started);
+        flow_registry.runs().finish("sleeping", run_id);
+
+        tx.send(SchedulerCommand::Resume).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+
+        assert_eq!(flow_registry.runs().admit(&flow, RunId::next(), None), Admission::Rejected);
+    }
 }
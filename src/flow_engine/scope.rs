@@ -24,6 +24,10 @@ impl Scope {
         self.data.get_mut(k).and_then(|v| v.downcast_mut::<T>())
     }
 
+    pub fn remove<T: 'static + Send + Sync>(&mut self, k: &str) -> Option<T> {
+        self.data.remove(k).and_then(|v| v.downcast::<T>().ok().map(|boxed| *boxed))
+    }
+
     pub fn ensure_entry_mut<T: 'static + Send + Sync, F: FnOnce() -> T>(&mut self, k: String, default: F) -> Option<&mut T> {
         self.data.entry(k).or_insert_with(|| Box::new(default())).downcast_mut::<T>()
     }
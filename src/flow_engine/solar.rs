@@ -0,0 +1,212 @@
+use crate::domain::GeoLocation;
+use chrono::{DateTime, NaiveDate, Utc};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use sunrise::{Coordinates, DawnType, SolarDay};
+use thiserror::Error;
+
+/// The elevation in degrees the sun drops below at the start of the evening golden hour.
+const GOLDEN_HOUR_START: f64 = 6.0;
+/// The elevation in degrees the sun drops below at the end of the evening golden hour.
+const GOLDEN_HOUR_END: f64 = -4.0;
+
+/// A moment of the day defined by the elevation of the sun.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SolarEvent {
+    Sunrise,
+    Sunset,
+    Dawn(Twilight),
+    Dusk(Twilight),
+    GoldenHour,                               // The start of the evening golden hour
+    Elevation { degrees: f64, rising: bool }, // The sun crosses the elevation, in the morning if rising
+}
+
+/// The kind of twilight of a dawn or dusk, civil is the brightest and astronomical the darkest.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Twilight {
+    Civil,
+    Nautical,
+    Astronomical,
+}
+
+impl SolarEvent {
+    /// Returns the time of the event on the given date, or `None` if the sun does not reach its elevation that day.
+    pub fn time_on(&self, location: &GeoLocation, date: NaiveDate) -> Option<DateTime<Utc>> {
+        let event = match self {
+            SolarEvent::Sunrise => sunrise::SolarEvent::Sunrise,
+            SolarEvent::Sunset => sunrise::SolarEvent::Sunset,
+            SolarEvent::Dawn(twilight) => sunrise::SolarEvent::Dawn(twilight.dawn_type()),
+            SolarEvent::Dusk(twilight) => sunrise::SolarEvent::Dusk(twilight.dawn_type()),
+            SolarEvent::GoldenHour => elevation(GOLDEN_HOUR_START, false),
+            SolarEvent::Elevation { degrees, rising } => elevation(*degrees, *rising),
+        };
+
+        // The expect is fine as GeoLocation is validated during deserialization
+        let coordinates = Coordinates::new(location.latitude, location.longitude).expect("valid coordinates");
+        let time = SolarDay::new(coordinates, date).with_altitude(location.altitude).event_time(event);
+
+        // If the sun does not reach the elevation, the calculation results in the Unix epoch rather than a time that day
+        ((time.date_naive() - date).num_days().abs() <= 1).then_some(time)
+    }
+}
+
+/// The elevation of an event of the `sunrise` crate is the depression below the horizon in radians.
+fn elevation(degrees: f64, rising: bool) -> sunrise::SolarEvent {
+    sunrise::SolarEvent::Elevation {
+        elevation: -degrees.to_radians(),
+        morning: rising,
+    }
+}
+
+impl FromStr for SolarEvent {
+    type Err = UnknownSolarEventError;
+
+    /// Parses the name of an event like `civilDusk`, the elevation event has no name.
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "sunrise" => Ok(SolarEvent::Sunrise),
+            "sunset" => Ok(SolarEvent::Sunset),
+            "civilDawn" => Ok(SolarEvent::Dawn(Twilight::Civil)),
+            "nauticalDawn" => Ok(SolarEvent::Dawn(Twilight::Nautical)),
+            "astronomicalDawn" => Ok(SolarEvent::Dawn(Twilight::Astronomical)),
+            "civilDusk" => Ok(SolarEvent::Dusk(Twilight::Civil)),
+            "nauticalDusk" => Ok(SolarEvent::Dusk(Twilight::Nautical)),
+            "astronomicalDusk" => Ok(SolarEvent::Dusk(Twilight::Astronomical)),
+            "goldenHour" => Ok(SolarEvent::GoldenHour),
+            _ => Err(UnknownSolarEventError(name.to_string())),
+        }
+    }
+}
+
+#[derive(Error, PartialEq, Debug)]
+#[error("unknown solar event '{0}', expected sunrise, sunset, civilDawn, nauticalDawn, astronomicalDawn, civilDusk, nauticalDusk, astronomicalDusk or goldenHour")]
+pub struct UnknownSolarEventError(String);
+
+impl Twilight {
+    fn dawn_type(self) -> DawnType {
+        match self {
+            Twilight::Civil => DawnType::Civil,
+            Twilight::Nautical => DawnType::Nautical,
+            Twilight::Astronomical => DawnType::Astronomical,
+        }
+    }
+}
+
+impl Display for SolarEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SolarEvent::Sunrise => write!(f, "sunrise"),
+            SolarEvent::Sunset => write!(f, "sunset"),
+            SolarEvent::Dawn(twilight) => write!(f, "{}Dawn", twilight),
+            SolarEvent::Dusk(twilight) => write!(f, "{}Dusk", twilight),
+            SolarEvent::GoldenHour => write!(f, "goldenHour"),
+            SolarEvent::Elevation { degrees, rising: true } => write!(f, "sun rising above {}°", degrees),
+            SolarEvent::Elevation { degrees, rising: false } => write!(f, "sun setting below {}°", degrees),
+        }
+    }
+}
+
+impl Display for Twilight {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Twilight::Civil => write!(f, "civil"),
+            Twilight::Nautical => write!(f, "nautical"),
+            Twilight::Astronomical => write!(f, "astronomical"),
+        }
+    }
+}
+
+/// Returns whether the sun is in the golden hour of the morning or the evening, when it is low above the horizon.
+pub fn is_golden_hour(location: &GeoLocation, at: DateTime<Utc>) -> bool {
+    (GOLDEN_HOUR_END..GOLDEN_HOUR_START).contains(&sun_elevation(location, at))
+}
+
+/// Returns the elevation of the sun above the horizon in degrees, negative if it is below the horizon. The approximation
+/// is accurate to about a tenth of a degree and ignores atmospheric refraction.
+pub fn sun_elevation(location: &GeoLocation, at: DateTime<Utc>) -> f64 {
+    let days = (at.timestamp() as f64 / 86400.0) - 10957.5; // Since the J2000 epoch
+
+    let mean_longitude = (280.460 + 0.9856474 * days).rem_euclid(360.0);
+    let mean_anomaly = (357.528 + 0.9856003 * days).rem_euclid(360.0).to_radians();
+    let ecliptic_longitude = (mean_longitude + 1.915 * mean_anomaly.sin() + 0.020 * (2.0 * mean_anomaly).sin()).to_radians();
+    let obliquity = (23.439 - 0.0000004 * days).to_radians();
+
+    let declination = (obliquity.sin() * ecliptic_longitude.sin()).asin();
+    let right_ascension = (obliquity.cos() * ecliptic_longitude.sin()).atan2(ecliptic_longitude.cos());
+    let sidereal_time = (280.46061837 + 360.98564736629 * days).rem_euclid(360.0);
+    let hour_angle = (sidereal_time + location.longitude).to_radians() - right_ascension;
+
+    let latitude = location.latitude.to_radians();
+    (latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos())
+        .asin()
+        .to_degrees()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn location() -> GeoLocation {
+        GeoLocation {
+            latitude: 51.9244,
+            longitude: 4.4777,
+            altitude: 0.0,
+        }
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2000, 8, 4).unwrap()
+    }
+
+    #[rstest]
+    #[case::sunset(SolarEvent::Sunset, -0.83)]
+    #[case::civil_dusk(SolarEvent::Dusk(Twilight::Civil), -6.0)]
+    #[case::nautical_dawn(SolarEvent::Dawn(Twilight::Nautical), -12.0)]
+    #[case::astronomical_dusk(SolarEvent::Dusk(Twilight::Astronomical), -18.0)]
+    #[case::golden_hour(SolarEvent::GoldenHour, 6.0)]
+    #[case::rising_elevation(SolarEvent::Elevation { degrees: 30.0, rising: true }, 30.0)]
+    fn the_sun_is_at_the_elevation_of_an_event_at_its_time(#[case] event: SolarEvent, #[case] expected_elevation: f64) {
+        let time = event.time_on(&location(), date()).unwrap();
+
+        let elevation = sun_elevation(&location(), time);
+        assert!((elevation - expected_elevation).abs() < 0.3, "elevation at {} is {}", time, elevation);
+    }
+
+    #[test]
+    fn events_are_ordered_through_the_day() {
+        let time = |event: SolarEvent| event.time_on(&location(), date()).unwrap();
+
+        assert!(time(SolarEvent::Dawn(Twilight::Astronomical)) < time(SolarEvent::Dawn(Twilight::Nautical)));
+        assert!(time(SolarEvent::Dawn(Twilight::Civil)) < time(SolarEvent::Sunrise));
+        assert!(time(SolarEvent::GoldenHour) < time(SolarEvent::Sunset));
+        assert!(time(SolarEvent::Sunset) < time(SolarEvent::Dusk(Twilight::Civil)));
+        assert_eq!(time(SolarEvent::Sunset), Utc.with_ymd_and_hms(2000, 8, 4, 19, 26, 42).unwrap());
+    }
+
+    #[test]
+    fn parses_the_name_of_an_event() {
+        assert_eq!("nauticalDusk".parse(), Ok(SolarEvent::Dusk(Twilight::Nautical)));
+        assert_eq!("noon".parse::<SolarEvent>(), Err(UnknownSolarEventError("noon".to_string())));
+        assert_eq!(SolarEvent::Dawn(Twilight::Astronomical).to_string().parse(), Ok(SolarEvent::Dawn(Twilight::Astronomical)));
+    }
+
+    #[test]
+    fn time_on_is_none_if_the_sun_does_not_reach_the_elevation() {
+        let event = SolarEvent::Elevation { degrees: 70.0, rising: true };
+
+        assert_eq!(event.time_on(&location(), date()), None);
+    }
+
+    #[rstest]
+    #[case::noon((12, 0), false)]
+    #[case::evening((19, 0), true)]
+    #[case::night((23, 0), false)]
+    fn is_golden_hour_in_the_evening(#[case] time: (u32, u32), #[case] expected: bool) {
+        let at = Utc.with_ymd_and_hms(2000, 8, 4, time.0, time.1, 0).unwrap();
+
+        assert_eq!(is_golden_hour(&location(), at), expected);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Remembers the last result of each trigger, to detect when a trigger changes from false to true.
+#[derive(Debug, Default)]
+pub struct TriggerStates {
+    previous: Mutex<HashMap<String, bool>>,
+}
+
+impl TriggerStates {
+    pub fn new() -> Self {
+        TriggerStates::default()
+    }
+
+    /// Stores the trigger result and returns whether it changed from false to true. The first result of a trigger is
+    /// only stored, as there is no transition to detect yet.
+    pub fn rising_edge(&self, trigger_key: &str, result: bool) -> bool {
+        let mut previous = self.previous.lock().unwrap();
+        match previous.insert(trigger_key.to_string(), result) {
+            Some(previous_result) => !previous_result && result,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_a_rising_edge() {
+        let states = TriggerStates::new();
+
+        assert!(!states.rising_edge("flow", false));
+        assert!(states.rising_edge("flow", true));
+        assert!(!states.rising_edge("flow", true));
+        assert!(!states.rising_edge("flow", false));
+        assert!(states.rising_edge("flow", true));
+    }
+
+    #[test]
+    fn ignores_the_first_result() {
+        let states = TriggerStates::new();
+
+        assert!(!states.rising_edge("flow", true));
+        assert!(!states.rising_edge("other_flow", true));
+        assert!(!states.rising_edge("flow", true));
+    }
+}
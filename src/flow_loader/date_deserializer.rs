@@ -0,0 +1,46 @@
+use crate::domain::Date;
+use serde::de::{Error, Unexpected};
+use serde::{Deserialize, Deserializer};
+
+impl<'de> Deserialize<'de> for Date {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+        value
+            .parse()
+            .map_err(|_| Error::invalid_value(Unexpected::Str(&value), &"a date in YYYY-MM-DD format, or MM-DD for a date that recurs every year"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case("12-01", Date { year: None, month: 12, day: 1 })]
+    #[case("02-29", Date { year: None, month: 2, day: 29 })]
+    #[case("2026-01-06", Date { year: Some(2026), month: 1, day: 6 })]
+    #[case("2028-02-29", Date { year: Some(2028), month: 2, day: 29 })]
+    fn deserializes_valid_date(#[case] date: &str, #[case] expected: Date) {
+        let result = serde_json::from_value::<Date>(json!(date)).unwrap();
+        assert_eq!(result, expected);
+    }
+
+    #[rstest]
+    #[case::missing_day("12")]
+    #[case::short_year("26-12-01")]
+    #[case::short_month("2026-1-06")]
+    #[case::invalid_month("13-01")]
+    #[case::invalid_day("04-31")]
+    #[case::no_leap_year("2026-02-29")]
+    #[case::invalid_characters("ab-cd")]
+    #[case::wrong_separator("12/01")]
+    fn fails_for_an_invalid_date(#[case] date: &str) {
+        let result = serde_json::from_value::<Date>(json!(date));
+        assert!(result.is_err());
+    }
+}
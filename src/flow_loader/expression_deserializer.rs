@@ -0,0 +1,87 @@
+use crate::flow_engine::Expression;
+use crate::flow_loader::expression_parser::parse_expression;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+/// An expression is either an object, or a string in the textual syntax of `parse_expression`.
+impl<'de> Deserialize<'de> for Expression {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value: serde_json::Value = Deserialize::deserialize(deserializer)?;
+        match value {
+            serde_json::Value::String(text) => parse_expression(&text).map_err(Error::custom),
+            value => Expression::deserialize(value).map_err(Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Number;
+    use crate::flow_engine::Value;
+    use crate::flow_engine::expression::{AggregateExpression, AggregateFunction, DeviceSelector};
+    use pretty_assertions::assert_eq;
+    use serde_json::json;
+
+    fn expected() -> Expression {
+        Expression::And {
+            lhs: Box::new(Expression::PropertyValue {
+                device_id: "hallway".to_string(),
+                property_id: "on".to_string(),
+            }),
+            rhs: Box::new(Expression::Literal {
+                value: Value::Number(Number::PositiveInt(1)),
+            }),
+        }
+    }
+
+    #[test]
+    fn deserializes_an_object() {
+        let json = json!({
+            "type": "and",
+            "lhs": { "type": "propertyValue", "deviceId": "hallway", "propertyId": "on" },
+            "rhs": { "type": "literal", "value": 1 }
+        });
+
+        assert_eq!(serde_json::from_value::<Expression>(json).unwrap(), expected());
+    }
+
+    #[test]
+    fn deserializes_a_string() {
+        assert_eq!(serde_json::from_value::<Expression>(json!("hallway.on && 1")).unwrap(), expected());
+    }
+
+    #[test]
+    fn deserializes_strings_nested_in_an_object() {
+        let json = json!({ "type": "and", "lhs": "hallway.on", "rhs": { "type": "literal", "value": 1 } });
+
+        assert_eq!(serde_json::from_value::<Expression>(json).unwrap(), expected());
+    }
+
+    #[test]
+    fn deserializes_an_aggregate() {
+        let json = json!({ "type": "aggregate", "function": "any", "devices": { "room": "Living room" }, "propertyId": "on" });
+
+        assert_eq!(
+            serde_json::from_value::<Expression>(json).unwrap(),
+            Expression::Aggregate(AggregateExpression {
+                function: AggregateFunction::Any,
+                devices: DeviceSelector {
+                    room: Some("Living room".to_string()),
+                    ..DeviceSelector::default()
+                },
+                property_id: "on".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn fails_with_the_position_of_a_syntax_error() {
+        let result = serde_json::from_value::<Expression>(json!("hallway.on &&"));
+
+        assert_eq!(result.unwrap_err().to_string(), "unexpected end of expression at position 14, expected an expression");
+    }
+}
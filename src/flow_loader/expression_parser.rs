@@ -0,0 +1,1097 @@
+use crate::domain::color::Color;
+use crate::domain::device::DeviceType;
+use crate::domain::property::CartesianCoordinate;
+use crate::domain::{Date, Number, Time, Weekday, WeekdayCondition};
+use crate::flow_engine::expression::{
+    AggregateExpression, AggregateFunction, DeviceSelector, Expression, Pattern, PropertyChangedExpression, PropertyDurationExpression, TemporalExpression,
+};
+use crate::flow_engine::{SolarEvent, Value};
+use humantime_serde::re::humantime;
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Parses the textual syntax of an expression, like `"hallway".on && kitchen.brightness > 50 && isDaytime()`.
+///
+/// - Properties are referenced as `device.property`, ids that are not plain identifiers are quoted, like
+///   `"ab917a9a-a7d5-4853-9518-75909236a182".on`. All segments but the last form the device id.
+/// - Variables are referenced by their name, parameters passed by a calling flow as `$name`.
+/// - Literals are numbers, strings like `"long_press"`, `true`, `false`, `none` and colors like `color("#ffb46b")`,
+///   `rgb(255, 180, 107)` or `xyY(0.4851, 0.4331, 1.0)`.
+/// - Operators from low to high precedence: `||`, `&&`, `==` `!=`, `<` `<=` `>` `>=`, `+` `-`, `*` `/` `%` and the
+///   unary `!` and `-`.
+/// - Functions: `isDaytime()`, `isNighttime()`, `hasSunRisen()`, `hasSunSet()`, `isAfterSolarEvent("civilDusk")`,
+///   `isAfterSunElevation(-3.5, "setting")`, `isGoldenHour()`, `sunElevation()`, `isBeforeTime("07:30")`,
+///   `isAfterTime("22:00")`, `isBetweenTimes("22:30", "06:15")`, `isBetweenDates("12-01", "01-06")`,
+///   `isDate("2026-12-25")`, `isCalendarDay("holidays")`, `isToday("Mon-Fri")`, `changed(device.property)`, `changed(device.property, from, to)`
+///   with `_` for any value, `unchangedFor(device.property, "2h")`, `changedWithin(device.property, "5m")`,
+///   `valueAgo(device.property, "10m")` for the value in the property history, `min(a, b)`,
+///   `max(a, b)`, `clamp(value, min, max)`, `round(value)`, `abs(value)`, `contains(text, "part")`,
+///   `startsWith(text, "prefix")`, `matches(text, "regex")`, `colorDistance(a, b)`, `hue(color)`,
+///   `saturation(color)`, `variable("name")`, `parameter("name")` and `triggeredBy()` for the id of the trigger that
+///   started the run.
+/// - Aggregates over a group of devices: `any(selector, property)`, `all`, `count`, `average`, `min` and `max`. The
+///   selector is `allDevices()`, or one or more of `devices("id", ...)`, `deviceType("Light")`, `room("Living room")`
+///   and `tag("downstairs")` joined by `&&`, like `any(room("Living room") && tag("ceiling"), on)`.
+///
+/// Error positions are 1-based character positions.
+pub fn parse_expression(text: &str) -> Result<Expression, ParseError> {
+    let mut parser = Parser { tokens: tokenize(text)?, index: 0 };
+    let expression = parser.binary(0)?;
+    match parser.peek() {
+        Token::End => Ok(expression),
+        _ => Err(parser.unexpected("an operator or the end of the expression")),
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(Number),
+    String(String),
+    Identifier(String),
+    Dollar,
+    Dot,
+    Comma,
+    LeftParenthesis,
+    RightParenthesis,
+    And,
+    Or,
+    Not,
+    EqualTo,
+    NotEqualTo,
+    GreaterThan,
+    GreaterThanOrEqualTo,
+    LessThan,
+    LessThanOrEqualTo,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    Percent,
+    End,
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Token::Number(number) => return write!(f, "number {}", number),
+            Token::String(string) => return write!(f, "string \"{}\"", string),
+            Token::Identifier(name) => return write!(f, "'{}'", name),
+            Token::End => return write!(f, "end of expression"),
+            Token::Dollar => "$",
+            Token::Dot => ".",
+            Token::Comma => ",",
+            Token::LeftParenthesis => "(",
+            Token::RightParenthesis => ")",
+            Token::And => "&&",
+            Token::Or => "||",
+            Token::Not => "!",
+            Token::EqualTo => "==",
+            Token::NotEqualTo => "!=",
+            Token::GreaterThan => ">",
+            Token::GreaterThanOrEqualTo => ">=",
+            Token::LessThan => "<",
+            Token::LessThanOrEqualTo => "<=",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Percent => "%",
+        };
+        write!(f, "'{}'", symbol)
+    }
+}
+
+/// Splits the text into tokens with their position, the last token is always `Token::End`.
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let characters: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut index = 0;
+
+    while index < characters.len() {
+        let character = characters[index];
+        let position = index + 1;
+        if character.is_whitespace() {
+            index += 1;
+            continue;
+        }
+
+        let (token, length) = match (character, characters.get(index + 1)) {
+            ('&', Some('&')) => (Token::And, 2),
+            ('|', Some('|')) => (Token::Or, 2),
+            ('=', Some('=')) => (Token::EqualTo, 2),
+            ('!', Some('=')) => (Token::NotEqualTo, 2),
+            ('>', Some('=')) => (Token::GreaterThanOrEqualTo, 2),
+            ('<', Some('=')) => (Token::LessThanOrEqualTo, 2),
+            ('!', _) => (Token::Not, 1),
+            ('>', _) => (Token::GreaterThan, 1),
+            ('<', _) => (Token::LessThan, 1),
+            ('+', _) => (Token::Plus, 1),
+            ('-', _) => (Token::Minus, 1),
+            ('*', _) => (Token::Star, 1),
+            ('/', _) => (Token::Slash, 1),
+            ('%', _) => (Token::Percent, 1),
+            ('$', _) => (Token::Dollar, 1),
+            ('.', _) => (Token::Dot, 1),
+            (',', _) => (Token::Comma, 1),
+            ('(', _) => (Token::LeftParenthesis, 1),
+            (')', _) => (Token::RightParenthesis, 1),
+            ('"', _) => string_token(&characters[index..], position)?,
+            (character, _) if character.is_ascii_digit() => number_token(&characters[index..], position)?,
+            (character, _) if is_identifier_start(character) => {
+                let length = characters[index..].iter().take_while(|character| is_identifier_part(**character)).count();
+                (Token::Identifier(characters[index..index + length].iter().collect()), length)
+            }
+            (character, _) => return Err(ParseError::UnexpectedCharacter { character, position }),
+        };
+
+        tokens.push((token, position));
+        index += length;
+    }
+
+    tokens.push((Token::End, characters.len() + 1));
+    Ok(tokens)
+}
+
+/// Reads a string that starts at the first character, a backslash escapes the next character.
+fn string_token(characters: &[char], position: usize) -> Result<(Token, usize), ParseError> {
+    let mut string = String::new();
+    let mut index = 1;
+    loop {
+        match characters.get(index) {
+            Some('"') => return Ok((Token::String(string), index + 1)),
+            Some('\\') if index + 1 < characters.len() => {
+                string.push(characters[index + 1]);
+                index += 2;
+            }
+            Some(character) => {
+                string.push(*character);
+                index += 1;
+            }
+            None => return Err(ParseError::UnterminatedString { position }),
+        }
+    }
+}
+
+/// Reads an integer, or a Float if it has a fraction or an exponent.
+fn number_token(characters: &[char], position: usize) -> Result<(Token, usize), ParseError> {
+    let digits = |from: usize| characters[from..].iter().take_while(|character| character.is_ascii_digit()).count();
+
+    let mut length = digits(0);
+    let mut is_float = false;
+    if characters.get(length) == Some(&'.') && characters.get(length + 1).is_some_and(char::is_ascii_digit) {
+        length += 1 + digits(length + 1);
+        is_float = true;
+    }
+    if matches!(characters.get(length), Some('e' | 'E')) {
+        let sign = usize::from(matches!(characters.get(length + 1), Some('+' | '-')));
+        let exponent = digits(length + 1 + sign);
+        if exponent > 0 {
+            length += 1 + sign + exponent;
+            is_float = true;
+        }
+    }
+
+    let text: String = characters[..length].iter().collect();
+    let number = if is_float {
+        text.parse().ok().map(Number::Float)
+    } else {
+        text.parse().ok().map(Number::PositiveInt)
+    };
+    number.map(|number| (Token::Number(number), length)).ok_or(ParseError::InvalidNumber { text, position })
+}
+
+fn is_identifier_start(character: char) -> bool {
+    character.is_alphabetic() || character == '_'
+}
+
+fn is_identifier_part(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+type BinaryConstructor = fn(Box<Expression>, Box<Expression>) -> Expression;
+
+const BINARY_LEVELS: usize = 6;
+
+/// Returns the binary operator for the token at the precedence level, levels go from low to high precedence.
+fn binary_operator(level: usize, token: &Token) -> Option<BinaryConstructor> {
+    use Expression::*;
+
+    match (level, token) {
+        (0, Token::Or) => Some(|lhs, rhs| Or { lhs, rhs }),
+        (1, Token::And) => Some(|lhs, rhs| And { lhs, rhs }),
+        (2, Token::EqualTo) => Some(|lhs, rhs| EqualTo { lhs, rhs }),
+        (2, Token::NotEqualTo) => Some(|lhs, rhs| NotEqualTo { lhs, rhs }),
+        (3, Token::GreaterThan) => Some(|lhs, rhs| GreaterThan { lhs, rhs }),
+        (3, Token::GreaterThanOrEqualTo) => Some(|lhs, rhs| GreaterThanOrEqualTo { lhs, rhs }),
+        (3, Token::LessThan) => Some(|lhs, rhs| LessThan { lhs, rhs }),
+        (3, Token::LessThanOrEqualTo) => Some(|lhs, rhs| LessThanOrEqualTo { lhs, rhs }),
+        (4, Token::Plus) => Some(|lhs, rhs| Add { lhs, rhs }),
+        (4, Token::Minus) => Some(|lhs, rhs| Subtract { lhs, rhs }),
+        (5, Token::Star) => Some(|lhs, rhs| Multiply { lhs, rhs }),
+        (5, Token::Slash) => Some(|lhs, rhs| Divide { lhs, rhs }),
+        (5, Token::Percent) => Some(|lhs, rhs| Modulo { lhs, rhs }),
+        _ => None,
+    }
+}
+
+/// An argument of a function call, `_` is only valid as an argument.
+enum Argument {
+    Wildcard,
+    Expression(Expression),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    index: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index].0
+    }
+
+    fn peek_next(&self) -> &Token {
+        self.peek_at(1)
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        &self.tokens[(self.index + offset).min(self.tokens.len() - 1)].0
+    }
+
+    fn position(&self) -> usize {
+        self.tokens[self.index].1
+    }
+
+    /// Returns the current token and moves to the next one, it stays at the end once reached.
+    fn advance(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.index].clone();
+        if self.index < self.tokens.len() - 1 {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        let matches = self.peek() == token;
+        if matches {
+            self.advance();
+        }
+        matches
+    }
+
+    fn unexpected(&self, expected: &'static str) -> ParseError {
+        ParseError::UnexpectedToken {
+            found: self.peek().to_string(),
+            expected,
+            position: self.position(),
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expression, ParseError> {
+        if level == BINARY_LEVELS {
+            return self.unary();
+        }
+
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(constructor) = binary_operator(level, self.peek()) {
+            self.advance();
+            let rhs = self.binary(level + 1)?;
+            lhs = constructor(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Expression, ParseError> {
+        if self.eat(&Token::Not) {
+            return Ok(Expression::Not { expression: Box::new(self.unary()?) });
+        }
+
+        if self.eat(&Token::Minus) {
+            return Ok(match self.unary()? {
+                Expression::Literal { value: Value::Number(number) } => Expression::Literal {
+                    value: Value::Number(Number::PositiveInt(0) - number),
+                },
+                expression => Expression::Subtract {
+                    lhs: Box::new(Expression::Literal {
+                        value: Value::Number(Number::PositiveInt(0)),
+                    }),
+                    rhs: Box::new(expression),
+                },
+            });
+        }
+
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expression, ParseError> {
+        let (token, position) = self.advance();
+        match token {
+            Token::Number(number) => Ok(Expression::Literal { value: Value::Number(number) }),
+            Token::LeftParenthesis => {
+                let expression = self.binary(0)?;
+                if !self.eat(&Token::RightParenthesis) {
+                    return Err(self.unexpected("')'"));
+                }
+                Ok(expression)
+            }
+            Token::Dollar => match self.advance() {
+                (Token::Identifier(name), _) => Ok(Expression::Parameter { name }),
+                (token, position) => Err(ParseError::UnexpectedToken {
+                    found: token.to_string(),
+                    expected: "a parameter name",
+                    position,
+                }),
+            },
+            Token::String(segment) if *self.peek() == Token::Dot => self.property(segment),
+            Token::String(value) => Ok(Expression::Literal { value: Value::String(value) }),
+            Token::Identifier(name) => match self.peek() {
+                Token::LeftParenthesis if self.starts_aggregate(&name) => {
+                    self.advance();
+                    self.aggregate(&name)
+                }
+                Token::LeftParenthesis => {
+                    self.advance();
+                    let arguments = self.arguments()?;
+                    function(&name, arguments, position)
+                }
+                Token::Dot => self.property(name),
+                _ => Ok(match name.as_str() {
+                    "true" => Expression::Literal { value: Value::Boolean(true) },
+                    "false" => Expression::Literal { value: Value::Boolean(false) },
+                    "none" => Expression::Literal { value: Value::None },
+                    _ => Expression::Variable { name },
+                }),
+            },
+            token => Err(ParseError::UnexpectedToken {
+                found: token.to_string(),
+                expected: "an expression",
+                position,
+            }),
+        }
+    }
+
+    /// Aggregates share their name with `min` and `max`, they are told apart by the device selector they start with.
+    fn starts_aggregate(&self, name: &str) -> bool {
+        aggregate_function(name).is_some()
+            && matches!(self.peek_at(1), Token::Identifier(selector) if SELECTORS.contains(&selector.as_str()))
+            && *self.peek_at(2) == Token::LeftParenthesis
+    }
+
+    /// Parses an aggregate like `any(room("Living room") && tag("ceiling"), on)`, after its opening parenthesis.
+    fn aggregate(&mut self, name: &str) -> Result<Expression, ParseError> {
+        let function = aggregate_function(name).ok_or_else(|| self.unexpected("an aggregate function"))?;
+        let devices = self.selector()?;
+        if !self.eat(&Token::Comma) {
+            return Err(self.unexpected("'&&' or ','"));
+        }
+        let property_id = match self.advance() {
+            (Token::Identifier(property_id) | Token::String(property_id), _) => property_id,
+            (token, position) => {
+                return Err(ParseError::UnexpectedToken {
+                    found: token.to_string(),
+                    expected: "a property id",
+                    position,
+                });
+            }
+        };
+        if !self.eat(&Token::RightParenthesis) {
+            return Err(self.unexpected("')'"));
+        }
+
+        Ok(Expression::Aggregate(AggregateExpression { function, devices, property_id }))
+    }
+
+    /// Parses device selectors joined by `&&`, a device has to match all of them.
+    fn selector(&mut self) -> Result<DeviceSelector, ParseError> {
+        let mut selector = DeviceSelector::default();
+        loop {
+            let (name, position) = match self.advance() {
+                (Token::Identifier(name), position) if SELECTORS.contains(&name.as_str()) => (name, position),
+                (token, position) => {
+                    return Err(ParseError::UnexpectedToken {
+                        found: token.to_string(),
+                        expected: "a device selector like room(\"Living room\")",
+                        position,
+                    });
+                }
+            };
+            if !self.eat(&Token::LeftParenthesis) {
+                return Err(self.unexpected("'('"));
+            }
+
+            let arguments = self.arguments()?;
+            let count = arguments.len();
+            let mut arguments = Arguments {
+                function: &name,
+                position,
+                arguments: arguments.into_iter(),
+            };
+            match name.as_str() {
+                "devices" => {
+                    arguments.expect(count >= 1, "at least 1")?;
+                    let ids = (0..count).map(|_| arguments.string("a device id").map(|(id, _)| id)).collect::<Result<_, _>>()?;
+                    selector.ids = Some(ids);
+                }
+                "deviceType" => {
+                    arguments.expect(count == 1, "1")?;
+                    selector.device_type = Some(arguments.deserialize::<DeviceType>("a device type like \"Light\"")?);
+                }
+                "room" => {
+                    arguments.expect(count == 1, "1")?;
+                    selector.room = Some(arguments.string("a room name")?.0);
+                }
+                "tag" => {
+                    arguments.expect(count == 1, "1")?;
+                    selector.tag = Some(arguments.string("a tag")?.0);
+                }
+                _ => arguments.expect(count == 0, "0")?, // allDevices
+            }
+
+            if !self.eat(&Token::And) {
+                return Ok(selector);
+            }
+        }
+    }
+
+    /// Parses the rest of a property reference, after its first segment and before the '.' that follows it.
+    fn property(&mut self, first_segment: String) -> Result<Expression, ParseError> {
+        let mut segments = vec![first_segment];
+        while self.eat(&Token::Dot) {
+            match self.advance() {
+                (Token::Identifier(segment) | Token::String(segment), _) => segments.push(segment),
+                (token, position) => {
+                    return Err(ParseError::UnexpectedToken {
+                        found: token.to_string(),
+                        expected: "a device or property id",
+                        position,
+                    });
+                }
+            }
+        }
+
+        let property_id = segments.pop().unwrap_or_default();
+        Ok(Expression::PropertyValue {
+            device_id: segments.join("."),
+            property_id,
+        })
+    }
+
+    /// Parses the arguments of a function call, after its opening parenthesis.
+    fn arguments(&mut self) -> Result<Vec<(Argument, usize)>, ParseError> {
+        let mut arguments = Vec::new();
+        if self.eat(&Token::RightParenthesis) {
+            return Ok(arguments);
+        }
+
+        loop {
+            let position = self.position();
+            let argument = match (self.peek(), self.peek_next()) {
+                (Token::Identifier(name), Token::Comma | Token::RightParenthesis) if name == "_" => {
+                    self.advance();
+                    Argument::Wildcard
+                }
+                _ => Argument::Expression(self.binary(0)?),
+            };
+            arguments.push((argument, position));
+
+            if self.eat(&Token::RightParenthesis) {
+                return Ok(arguments);
+            }
+            if !self.eat(&Token::Comma) {
+                return Err(self.unexpected("',' or ')'"));
+            }
+        }
+    }
+}
+
+fn function(name: &str, arguments: Vec<(Argument, usize)>, position: usize) -> Result<Expression, ParseError> {
+    use Expression::*;
+
+    let count = arguments.len();
+    let mut arguments = Arguments {
+        function: name,
+        position,
+        arguments: arguments.into_iter(),
+    };
+
+    match name {
+        "isDaytime" => arguments.expect(count == 0, "0").map(|_| temporal(TemporalExpression::IsDaytime)),
+        "isNighttime" => arguments.expect(count == 0, "0").map(|_| temporal(TemporalExpression::IsNighttime)),
+        "hasSunRisen" => arguments.expect(count == 0, "0").map(|_| temporal(TemporalExpression::HasSunRisen)),
+        "hasSunSet" => arguments.expect(count == 0, "0").map(|_| temporal(TemporalExpression::HasSunSet)),
+        "isGoldenHour" => arguments.expect(count == 0, "0").map(|_| temporal(TemporalExpression::IsGoldenHour)),
+        "sunElevation" => arguments.expect(count == 0, "0").map(|_| temporal(TemporalExpression::SunElevation)),
+        "isAfterSolarEvent" => {
+            arguments.expect(count == 1, "1")?;
+            let (name, position) = arguments.string("a solar event like \"civilDusk\"")?;
+            let event = name.parse::<SolarEvent>().map_err(|err| arguments.invalid(err, position))?;
+            Ok(temporal(TemporalExpression::IsAfterSolarEvent { event }))
+        }
+        "isAfterSunElevation" => {
+            arguments.expect(count == 2, "2")?;
+            let (degrees, position) = arguments.number("an elevation in degrees")?;
+            let degrees = degrees
+                .as_f64()
+                .filter(|degrees| (-90.0..=90.0).contains(degrees))
+                .ok_or_else(|| arguments.invalid("expected an elevation from -90 to 90 degrees", position))?;
+            let rising = match arguments.string("a direction")? {
+                (direction, _) if direction == "rising" => true,
+                (direction, _) if direction == "setting" => false,
+                (_, position) => return Err(arguments.invalid("expected \"rising\" or \"setting\"", position)),
+            };
+            Ok(temporal(TemporalExpression::IsAfterSolarEvent {
+                event: SolarEvent::Elevation { degrees, rising },
+            }))
+        }
+        "isBeforeTime" | "isAfterTime" => {
+            arguments.expect(count == 1, "1")?;
+            let time = arguments.deserialize::<Time>("a time like \"07:30\"")?;
+            Ok(temporal(if name == "isBeforeTime" {
+                TemporalExpression::IsBeforeTime { time }
+            } else {
+                TemporalExpression::IsAfterTime { time }
+            }))
+        }
+        "isBetweenTimes" => {
+            arguments.expect(count == 2, "2")?;
+            let from = arguments.deserialize::<Time>("a time like \"22:30\"")?;
+            let to = arguments.deserialize::<Time>("a time like \"06:15\"")?;
+            Ok(temporal(TemporalExpression::IsBetweenTimes { from, to }))
+        }
+        "isBetweenDates" => {
+            arguments.expect(count == 2, "2")?;
+            let from = arguments.deserialize::<Date>("a date like \"12-01\" or \"2026-12-01\"")?;
+            let to = arguments.deserialize::<Date>("a date like \"01-06\" or \"2027-01-06\"")?;
+            Ok(temporal(TemporalExpression::IsBetweenDates { from, to }))
+        }
+        "isDate" => {
+            arguments.expect(count == 1, "1")?;
+            let date = arguments.deserialize::<Date>("a date like \"12-25\" or \"2026-12-25\"")?;
+            Ok(temporal(TemporalExpression::IsDate { date }))
+        }
+        "isCalendarDay" => {
+            arguments.expect(count == 1, "1")?;
+            let (calendar, _) = arguments.string("a calendar name")?;
+            Ok(temporal(TemporalExpression::IsCalendarDay { calendar }))
+        }
+        "isToday" => {
+            arguments.expect(count >= 1, "at least 1")?;
+            let when = if count == 1 {
+                arguments.deserialize::<WeekdayCondition>("a weekday condition like \"Mon-Fri\"")?
+            } else {
+                WeekdayCondition::Set(
+                    (0..count)
+                        .map(|_| arguments.deserialize::<Weekday>("a weekday like \"Monday\""))
+                        .collect::<Result<_, _>>()?,
+                )
+            };
+            Ok(temporal(TemporalExpression::IsToday { when }))
+        }
+        "changed" => {
+            arguments.expect(count == 1 || count == 3, "1 or 3")?;
+            let (device_id, property_id) = arguments.property()?;
+            let (from, to) = if count == 3 { (arguments.value()?, arguments.value()?) } else { (None, None) };
+            Ok(PropertyChanged(PropertyChangedExpression { device_id, property_id, from, to }))
+        }
+        "unchangedFor" | "changedWithin" | "valueAgo" => {
+            arguments.expect(count == 2, "2")?;
+            let (device_id, property_id) = arguments.property()?;
+            let duration = arguments.duration()?;
+            let expression = PropertyDurationExpression { device_id, property_id, duration };
+            Ok(match name {
+                "unchangedFor" => UnchangedFor(expression),
+                "changedWithin" => ChangedWithin(expression),
+                _ => ValueAgo(expression),
+            })
+        }
+        "min" | "max" => {
+            arguments.expect(count == 2, "2")?;
+            let lhs = Box::new(arguments.expression()?);
+            let rhs = Box::new(arguments.expression()?);
+            Ok(if name == "min" { Min { lhs, rhs } } else { Max { lhs, rhs } })
+        }
+        "clamp" => {
+            arguments.expect(count == 3, "3")?;
+            let expression = Box::new(arguments.expression()?);
+            let min = Box::new(arguments.expression()?);
+            let max = Box::new(arguments.expression()?);
+            Ok(Clamp { expression, min, max })
+        }
+        "round" | "abs" => {
+            arguments.expect(count == 1, "1")?;
+            let expression = Box::new(arguments.expression()?);
+            Ok(if name == "round" { Round { expression } } else { Abs { expression } })
+        }
+        "contains" | "startsWith" => {
+            arguments.expect(count == 2, "2")?;
+            let lhs = Box::new(arguments.expression()?);
+            let rhs = Box::new(arguments.expression()?);
+            Ok(if name == "contains" { Contains { lhs, rhs } } else { StartsWith { lhs, rhs } })
+        }
+        "matches" => {
+            arguments.expect(count == 2, "2")?;
+            let expression = Box::new(arguments.expression()?);
+            let pattern = arguments.deserialize::<Pattern>("a regular expression")?;
+            Ok(Matches { expression, pattern })
+        }
+        "colorDistance" => {
+            arguments.expect(count == 2, "2")?;
+            let lhs = Box::new(arguments.expression()?);
+            let rhs = Box::new(arguments.expression()?);
+            Ok(ColorDistance { lhs, rhs })
+        }
+        "hue" | "saturation" => {
+            arguments.expect(count == 1, "1")?;
+            let expression = Box::new(arguments.expression()?);
+            Ok(if name == "hue" { Hue { expression } } else { Saturation { expression } })
+        }
+        "color" => {
+            arguments.expect(count == 1, "1")?;
+            let color = arguments.deserialize::<Color>("a hex color like \"#ffb46b\"")?;
+            Ok(color_literal(color))
+        }
+        "rgb" => {
+            arguments.expect(count == 3, "3")?;
+            let (r, g, b) = (arguments.channel()?, arguments.channel()?, arguments.channel()?);
+            Ok(color_literal(Color::RGB(r, g, b)))
+        }
+        "xyY" => {
+            arguments.expect(count == 3, "3")?;
+            let (x, y, brightness) = (arguments.float()?, arguments.float()?, arguments.float()?);
+            Ok(color_literal(Color::CIE_xyY {
+                xy: CartesianCoordinate::new(x, y),
+                brightness,
+            }))
+        }
+        "variable" => {
+            arguments.expect(count == 1, "1")?;
+            let (name, _) = arguments.string("a variable name")?;
+            Ok(Variable { name })
+        }
+        "parameter" => {
+            arguments.expect(count == 1, "1")?;
+            let (name, _) = arguments.string("a parameter name")?;
+            Ok(Parameter { name })
+        }
+        "triggeredBy" => arguments.expect(count == 0, "0").map(|_| TriggeredBy),
+        _ => Err(ParseError::UnknownFunction { name: name.to_string(), position }),
+    }
+}
+
+const SELECTORS: [&str; 5] = ["allDevices", "devices", "deviceType", "room", "tag"];
+
+fn aggregate_function(name: &str) -> Option<AggregateFunction> {
+    match name {
+        "any" => Some(AggregateFunction::Any),
+        "all" => Some(AggregateFunction::All),
+        "count" => Some(AggregateFunction::Count),
+        "average" => Some(AggregateFunction::Average),
+        "min" => Some(AggregateFunction::Min),
+        "max" => Some(AggregateFunction::Max),
+        _ => None,
+    }
+}
+
+fn temporal(expression: TemporalExpression) -> Expression {
+    Expression::Temporal { expression }
+}
+
+fn color_literal(color: Color) -> Expression {
+    Expression::Literal { value: Value::Color(color) }
+}
+
+/// Takes the arguments of a function call one by one, after their number is checked.
+struct Arguments<'a> {
+    function: &'a str,
+    position: usize,
+    arguments: std::vec::IntoIter<(Argument, usize)>,
+}
+
+impl Arguments<'_> {
+    fn expect(&self, valid_count: bool, expected: &'static str) -> Result<(), ParseError> {
+        if valid_count {
+            Ok(())
+        } else {
+            Err(ParseError::ArgumentCount {
+                function: self.function.to_string(),
+                expected,
+                actual: self.arguments.len(),
+                position: self.position,
+            })
+        }
+    }
+
+    fn next(&mut self, expected: &str) -> Result<(Argument, usize), ParseError> {
+        self.arguments.next().ok_or_else(|| self.invalid(format!("expected {}", expected), self.position))
+    }
+
+    fn invalid(&self, message: impl Display, position: usize) -> ParseError {
+        ParseError::InvalidArgument {
+            function: self.function.to_string(),
+            message: message.to_string(),
+            position,
+        }
+    }
+
+    fn string(&mut self, expected: &str) -> Result<(String, usize), ParseError> {
+        match self.next(expected)? {
+            (Argument::Expression(Expression::Literal { value: Value::String(string) }), position) => Ok((string, position)),
+            (_, position) => Err(self.invalid(format!("expected {} as a string", expected), position)),
+        }
+    }
+
+    fn deserialize<T: DeserializeOwned>(&mut self, expected: &str) -> Result<T, ParseError> {
+        let (string, position) = self.string(expected)?;
+        T::deserialize(serde_json::Value::String(string)).map_err(|err| self.invalid(err, position))
+    }
+
+    fn duration(&mut self) -> Result<Duration, ParseError> {
+        let (string, position) = self.string("a duration like \"2h\"")?;
+        humantime::parse_duration(&string).map_err(|err| self.invalid(err, position))
+    }
+
+    fn number(&mut self, expected: &str) -> Result<(Number, usize), ParseError> {
+        match self.next(expected)? {
+            (Argument::Expression(Expression::Literal { value: Value::Number(number) }), position) => Ok((number, position)),
+            (_, position) => Err(self.invalid(format!("expected {}", expected), position)),
+        }
+    }
+
+    fn channel(&mut self) -> Result<u8, ParseError> {
+        let (number, position) = self.number("a color channel from 0 to 255")?;
+        number
+            .as_u64()
+            .and_then(|channel| u8::try_from(channel).ok())
+            .ok_or_else(|| self.invalid("expected a color channel from 0 to 255", position))
+    }
+
+    fn float(&mut self) -> Result<f64, ParseError> {
+        let (number, position) = self.number("a number")?;
+        number.as_f64().ok_or_else(|| self.invalid("expected a number", position))
+    }
+
+    fn expression(&mut self) -> Result<Expression, ParseError> {
+        match self.next("an expression")? {
+            (Argument::Expression(expression), _) => Ok(expression),
+            (_, position) => Err(self.invalid("expected an expression", position)),
+        }
+    }
+
+    fn property(&mut self) -> Result<(String, String), ParseError> {
+        match self.next("a property")? {
+            (Argument::Expression(Expression::PropertyValue { device_id, property_id }), _) => Ok((device_id, property_id)),
+            (_, position) => Err(self.invalid("expected a property like device.property", position)),
+        }
+    }
+
+    /// Returns the value of a literal, or `None` for `_`.
+    fn value(&mut self) -> Result<Option<Value>, ParseError> {
+        match self.next("a value")? {
+            (Argument::Expression(Expression::Literal { value }), _) => Ok(Some(value)),
+            (Argument::Wildcard, _) => Ok(None),
+            (_, position) => Err(self.invalid("expected a literal value or '_'", position)),
+        }
+    }
+}
+
+#[derive(Error, PartialEq, Debug)]
+pub enum ParseError {
+    #[error("unexpected character '{character}' at position {position}")]
+    UnexpectedCharacter { character: char, position: usize },
+    #[error("unterminated string starting at position {position}")]
+    UnterminatedString { position: usize },
+    #[error("invalid number '{text}' at position {position}")]
+    InvalidNumber { text: String, position: usize },
+    #[error("unexpected {found} at position {position}, expected {expected}")]
+    UnexpectedToken { found: String, expected: &'static str, position: usize },
+    #[error("unknown function '{name}' at position {position}")]
+    UnknownFunction { name: String, position: usize },
+    #[error("function '{function}' at position {position} expects {expected} argument(s), but got {actual}")]
+    ArgumentCount {
+        function: String,
+        expected: &'static str,
+        actual: usize,
+        position: usize,
+    },
+    #[error("invalid argument for function '{function}' at position {position}, {message}")]
+    InvalidArgument { function: String, message: String, position: usize },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Weekday::{Monday, Wednesday};
+    use Expression::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    fn property(device_id: &str, property_id: &str) -> Box<Expression> {
+        Box::new(PropertyValue {
+            device_id: device_id.to_string(),
+            property_id: property_id.to_string(),
+        })
+    }
+
+    fn number(value: u64) -> Box<Expression> {
+        Box::new(Literal {
+            value: Value::Number(Number::PositiveInt(value)),
+        })
+    }
+
+    fn variable(name: &str) -> Box<Expression> {
+        Box::new(Variable { name: name.to_string() })
+    }
+
+    #[test]
+    fn parses_operators_by_precedence() {
+        let result = parse_expression("light.kitchen.brightness > 50 && isDaytime() || !$override");
+
+        assert_eq!(
+            result,
+            Ok(Or {
+                lhs: Box::new(And {
+                    lhs: Box::new(GreaterThan {
+                        lhs: property("light.kitchen", "brightness"),
+                        rhs: number(50),
+                    }),
+                    rhs: Box::new(temporal(TemporalExpression::IsDaytime)),
+                }),
+                rhs: Box::new(Not {
+                    expression: Box::new(Parameter { name: "override".to_string() }),
+                }),
+            })
+        );
+    }
+
+    #[test]
+    fn parses_arithmetic_left_associative_with_parentheses() {
+        let result = parse_expression("a - b - c * (d + 2)");
+
+        assert_eq!(
+            result,
+            Ok(Subtract {
+                lhs: Box::new(Subtract {
+                    lhs: variable("a"),
+                    rhs: variable("b")
+                }),
+                rhs: Box::new(Multiply {
+                    lhs: variable("c"),
+                    rhs: Box::new(Add { lhs: variable("d"), rhs: number(2) }),
+                }),
+            })
+        );
+    }
+
+    #[rstest]
+    #[case("42", Value::Number(Number::PositiveInt(42)))]
+    #[case("-42", Value::Number(Number::NegativeInt(-42)))]
+    #[case("2.5", Value::Number(Number::Float(2.5)))]
+    #[case("1e3", Value::Number(Number::Float(1000.0)))]
+    #[case("true", Value::Boolean(true))]
+    #[case("false", Value::Boolean(false))]
+    #[case("none", Value::None)]
+    #[case("\"long_press\"", Value::String("long_press".to_string()))]
+    fn parses_literals(#[case] text: &str, #[case] expected: Value) {
+        assert_eq!(parse_expression(text), Ok(Literal { value: expected }));
+    }
+
+    #[rstest]
+    #[case("\"ab917a9a-a7d5-4853-9518-75909236a182\".on", "ab917a9a-a7d5-4853-9518-75909236a182", "on")]
+    #[case("hallway.\"color temperature\"", "hallway", "color temperature")]
+    #[case("\"say \\\"hi\\\"\".on", "say \"hi\"", "on")]
+    fn parses_quoted_ids(#[case] text: &str, #[case] device_id: &str, #[case] property_id: &str) {
+        assert_eq!(parse_expression(text), Ok(*property(device_id, property_id)));
+    }
+
+    #[test]
+    fn parses_functions() {
+        assert_eq!(
+            parse_expression("isToday(\"Monday\", \"Wednesday\") && isAfterTime(\"07:30\")"),
+            Ok(And {
+                lhs: Box::new(temporal(TemporalExpression::IsToday {
+                    when: WeekdayCondition::Set(vec![Monday, Wednesday]),
+                })),
+                rhs: Box::new(temporal(TemporalExpression::IsAfterTime {
+                    time: Time { hour: 7, minute: 30, second: 0 }
+                })),
+            })
+        );
+        assert_eq!(
+            parse_expression("changed(hallway.on, _, true)"),
+            Ok(PropertyChanged(PropertyChangedExpression {
+                device_id: "hallway".to_string(),
+                property_id: "on".to_string(),
+                from: None,
+                to: Some(Value::Boolean(true)),
+            }))
+        );
+        assert_eq!(
+            parse_expression("unchangedFor(hallway.on, \"1h 30m\")"),
+            Ok(UnchangedFor(PropertyDurationExpression {
+                device_id: "hallway".to_string(),
+                property_id: "on".to_string(),
+                duration: Duration::from_secs(5400),
+            }))
+        );
+        assert_eq!(
+            parse_expression("clamp(round(x), 0, variable(\"true\"))"),
+            Ok(Clamp {
+                expression: Box::new(Round { expression: variable("x") }),
+                min: number(0),
+                max: variable("true"),
+            })
+        );
+        assert_eq!(
+            parse_expression("isBetweenDates(\"12-01\", \"2027-01-06\")"),
+            Ok(temporal(TemporalExpression::IsBetweenDates {
+                from: Date { year: None, month: 12, day: 1 },
+                to: Date { year: Some(2027), month: 1, day: 6 },
+            }))
+        );
+        assert_eq!(
+            parse_expression("isAfterSunElevation(-3.5, \"setting\")"),
+            Ok(temporal(TemporalExpression::IsAfterSolarEvent {
+                event: SolarEvent::Elevation { degrees: -3.5, rising: false },
+            }))
+        );
+    }
+
+    #[test]
+    fn parses_aggregates() {
+        assert_eq!(
+            parse_expression("any(room(\"Living room\") && tag(\"ceiling\"), on) && min(1, 2) == 1"),
+            Ok(And {
+                lhs: Box::new(Aggregate(AggregateExpression {
+                    function: AggregateFunction::Any,
+                    devices: DeviceSelector {
+                        room: Some("Living room".to_string()),
+                        tag: Some("ceiling".to_string()),
+                        ..DeviceSelector::default()
+                    },
+                    property_id: "on".to_string(),
+                })),
+                rhs: Box::new(EqualTo {
+                    lhs: Box::new(Min { lhs: number(1), rhs: number(2) }),
+                    rhs: number(1),
+                }),
+            })
+        );
+        assert_eq!(
+            parse_expression("average(devices(\"a\", \"b\") && deviceType(\"Light\"), \"color temperature\")"),
+            Ok(Aggregate(AggregateExpression {
+                function: AggregateFunction::Average,
+                devices: DeviceSelector {
+                    ids: Some(vec!["a".to_string(), "b".to_string()]),
+                    device_type: Some(DeviceType::Light),
+                    ..DeviceSelector::default()
+                },
+                property_id: "color temperature".to_string(),
+            }))
+        );
+    }
+
+    #[rstest]
+    #[case("a && ", ParseError::UnexpectedToken { found: "end of expression".to_string(), expected: "an expression", position: 6 })]
+    #[case("(a || b", ParseError::UnexpectedToken { found: "end of expression".to_string(), expected: "')'", position: 8 })]
+    #[case("a b", ParseError::UnexpectedToken { found: "'b'".to_string(), expected: "an operator or the end of the expression", position: 3 })]
+    #[case("hallway.", ParseError::UnexpectedToken { found: "end of expression".to_string(), expected: "a device or property id", position: 9 })]
+    #[case("$1 > 0", ParseError::UnexpectedToken { found: "number 1".to_string(), expected: "a parameter name", position: 2 })]
+    #[case("a & b", ParseError::UnexpectedCharacter { character: '&', position: 3 })]
+    #[case("a == \"b", ParseError::UnterminatedString { position: 6 })]
+    #[case("99999999999999999999", ParseError::InvalidNumber { text: "99999999999999999999".to_string(), position: 1 })]
+    #[case("1 + isNoon()", ParseError::UnknownFunction { name: "isNoon".to_string(), position: 5 })]
+    #[case("min(1)", ParseError::ArgumentCount { function: "min".to_string(), expected: "2", actual: 1, position: 1 })]
+    #[case("any(room(\"a\"), 1)", ParseError::UnexpectedToken { found: "number 1".to_string(), expected: "a property id", position: 16 })]
+    #[case("all(tag(\"a\") || tag(\"b\"), on)", ParseError::UnexpectedToken { found: "'||'".to_string(), expected: "'&&' or ','", position: 14 })]
+    #[case("count(allDevices() && on, on)", ParseError::UnexpectedToken { found: "'on'".to_string(), expected: "a device selector like room(\"Living room\")", position: 23 })]
+    #[case("changed(1 + 2)", ParseError::InvalidArgument { function: "changed".to_string(), message: "expected a property like device.property".to_string(), position: 9 })]
+    fn fails_with_the_position_of_the_error(#[case] text: &str, #[case] expected: ParseError) {
+        assert_eq!(parse_expression(text), Err(expected));
+    }
+
+    #[test]
+    fn fails_on_an_invalid_pattern() {
+        let result = parse_expression("matches(button.event, \"long_(press\")");
+
+        assert!(matches!(result, Err(ParseError::InvalidArgument { position: 23, .. })), "{:?}", result);
+    }
+
+    #[test]
+    fn fails_on_an_invalid_color_channel() {
+        let result = parse_expression("rgb(255, 256, 0)");
+
+        assert_eq!(
+            result,
+            Err(ParseError::InvalidArgument {
+                function: "rgb".to_string(),
+                message: "expected a color channel from 0 to 255".to_string(),
+                position: 10,
+            })
+        );
+    }
+
+    #[test]
+    fn fails_on_an_invalid_date() {
+        let result = parse_expression("isDate(\"02-30\")");
+
+        assert!(matches!(result, Err(ParseError::InvalidArgument { position: 8, .. })), "{:?}", result);
+    }
+
+    #[test]
+    fn fails_on_an_unknown_solar_event() {
+        let result = parse_expression("isAfterSolarEvent(\"noon\")");
+
+        assert!(matches!(result, Err(ParseError::InvalidArgument { position: 19, .. })), "{:?}", result);
+    }
+
+    #[test]
+    fn fails_on_an_invalid_sun_direction() {
+        let result = parse_expression("isAfterSunElevation(6, \"up\")");
+
+        assert_eq!(
+            result,
+            Err(ParseError::InvalidArgument {
+                function: "isAfterSunElevation".to_string(),
+                message: "expected \"rising\" or \"setting\"".to_string(),
+                position: 24,
+            })
+        );
+    }
+
+    #[test]
+    fn fails_on_an_invalid_time() {
+        let result = parse_expression("isBeforeTime(\"25:00\")");
+
+        assert!(matches!(result, Err(ParseError::InvalidArgument { position: 14, .. })), "{:?}", result);
+    }
+
+    #[rstest]
+    #[case("light.kitchen.brightness > 50 && isDaytime()")]
+    #[case("(a || b) && !(c == d)")]
+    #[case("a - (b - c) * 2 / -1.5 % 3")]
+    #[case("!!a != b >= 0 == (c == d)")]
+    #[case("\"ab917a9a-a7d5-4853-9518-75909236a182\".on == none")]
+    #[case("changed(hallway.on, false, _) || changed(\"hall way\".on)")]
+    #[case("changedWithin(hallway.on, \"5m\") && unchangedFor(hallway.on, \"2h 30m\")")]
+    #[case("valueAgo(hallway.on, \"10m\") != hallway.on")]
+    #[case("isToday(\"Monday-Friday\") && isToday(\"Monday\", \"Wednesday\") && isBeforeTime(\"07:05\")")]
+    #[case("hasSunRisen() && !hasSunSet() || isNighttime()")]
+    #[case("isAfterSolarEvent(\"nauticalDusk\") || isAfterSunElevation(-3.5, \"setting\") && isGoldenHour() || sunElevation() < 10")]
+    #[case("isBetweenTimes(\"22:30\", \"06:15:30\") && (isBetweenDates(\"12-01\", \"01-06\") || isDate(\"2026-12-31\")) && !isCalendarDay(\"public holidays\")")]
+    #[case("max(min(a, 1), abs(clamp($level, 0, 100))) + round(variable(\"none\"))")]
+    #[case("parameter(\"brightness level\") * 2.0")]
+    #[case("triggeredBy() == \"frontDoor\"")]
+    #[case("button.event == \"long_press\" && contains($room, \"living\") || startsWith(variable(\"_\"), \"a\\\\b\")")]
+    #[case("matches(scene.name, \"^(Relax|Read)$\") != false")]
+    #[case("colorDistance(lamp.color, color(\"#ffb46b\")) < 0.05 && hue(rgb(255, 0, 0)) + saturation(xyY(0.4851, 0.4331, 1.0)) > 0")]
+    #[case("any(room(\"Living room\") && tag(\"ceiling\"), on) || count(allDevices(), \"motion detected\") > 2")]
+    #[case("max(devices(\"a\", \"b\") && deviceType(\"Light\"), brightness) - min(1, average(allDevices(), brightness))")]
+    fn prints_expressions_it_parses(#[case] text: &str) {
+        let expression = parse_expression(text).unwrap();
+
+        assert_eq!(expression.to_string(), text);
+        assert_eq!(parse_expression(&expression.to_string()), Ok(expression));
+    }
+}
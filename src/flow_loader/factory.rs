@@ -1,7 +1,7 @@
-use crate::flow_engine::Value;
-use crate::flow_engine::flow::{ActionFlowNode, Flow, FlowLink, FlowNode, FlowNodeKind};
-use crate::flow_loader::serialized_flow::{SerializedFlow, SerializedFlowNode};
-use std::collections::{HashMap, VecDeque};
+use crate::flow_engine::flow::{ActionFlowNode, CallFlowNode, Flow, FlowLink, FlowNode, FlowNodeKind, SleepFlowNode, Trigger, TriggerKind, TriggerMode};
+use crate::flow_engine::{Expression, Schedule, Value};
+use crate::flow_loader::serialized_flow::{SerializedFlow, SerializedFlowNode, SerializedTrigger};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use thiserror::Error;
 
@@ -30,7 +30,7 @@ pub fn from_json(json: &str) -> Result<Flow, FlowFactoryError> {
 
     let mut nodes_map: HashMap<String, SerializedFlowNode> = flow.nodes.into_iter().map(|node| (node.id().to_owned(), node)).collect(); // Take ownership of the nodes
     let mut remaining_children: HashMap<String, usize> = HashMap::with_capacity(nodes_map.len());
-    let mut child_to_parent: HashMap<String, String> = HashMap::new();
+    let mut child_to_parents: HashMap<String, Vec<String>> = HashMap::new();
 
     for (id, node) in nodes_map.iter() {
         let out_count = node.outgoing_nodes().len();
@@ -40,16 +40,23 @@ pub fn from_json(json: &str) -> Result<Flow, FlowFactoryError> {
         for link in node.outgoing_nodes().iter() {
             value_to_nodes.entry(&link.value).or_default().push(link.node_id.clone());
 
-            // Parent linkage check
-            if let Some(prev) = child_to_parent.insert(link.node_id.clone(), id.clone()) {
-                // `prev` is the previously registered parent id
+            // Parent linkage check, only join nodes can have multiple parents
+            let parents = child_to_parents.entry(link.node_id.clone()).or_default();
+            parents.push(id.clone());
+            let is_join_node = matches!(nodes_map.get(&link.node_id), Some(SerializedFlowNode::JoinNode(_)));
+            if parents.len() > 1 && !is_join_node {
                 return Err(FlowFactoryError::TooManyParentNodes {
                     node_id: link.node_id.clone(),
-                    parent_nodes: vec![prev, id.clone()],
+                    parent_nodes: parents.clone(),
                 });
             }
         }
 
+        // All links of a fork node are followed, so their values are irrelevant
+        if matches!(node, SerializedFlowNode::ForkNode(_)) {
+            continue;
+        }
+
         let duplicates: Vec<String> = value_to_nodes.values().filter(|node_ids| node_ids.len() > 1).flatten().cloned().collect();
         if !duplicates.is_empty() {
             return Err(FlowFactoryError::DuplicateLinkValues { node_id: id.clone(), duplicates });
@@ -68,18 +75,26 @@ pub fn from_json(json: &str) -> Result<Flow, FlowFactoryError> {
         };
 
         // Unless it's a start node, ensure it has a parent
-        if !matches!(serialized_node, SerializedFlowNode::StartNode(_)) {
-            let has_parent = child_to_parent.get(serialized_node.id()).is_some();
-            if !has_parent {
-                return Err(FlowFactoryError::NoConnectingNode {
-                    node: serialized_node.id().to_owned(),
-                    flow: flow.name,
-                });
-            }
+        let num_parents = child_to_parents.get(serialized_node.id()).map_or(0, Vec::len);
+        if !matches!(serialized_node, SerializedFlowNode::StartNode(_)) && num_parents == 0 {
+            return Err(FlowFactoryError::NoConnectingNode {
+                node: serialized_node.id().to_owned(),
+                flow: flow.name,
+            });
+        }
+
+        if let SerializedFlowNode::JoinNode(join_node) = &serialized_node
+            && let Some(count) = join_node.count.filter(|count| *count == 0 || *count > num_parents)
+        {
+            return Err(FlowFactoryError::InvalidJoinCount {
+                node_id: join_node.id.clone(),
+                count,
+                incoming: num_parents,
+            });
         }
 
         let outgoing_nodes = map_outgoing_nodes(&serialized_node, &flow_node_map)?;
-        let node = to_flow_node(serialized_node, outgoing_nodes);
+        let node = to_flow_node(serialized_node, outgoing_nodes, num_parents);
 
         let node_id = node.id().to_owned();
         let node_arc = Arc::new(node);
@@ -91,7 +106,7 @@ pub fn from_json(json: &str) -> Result<Flow, FlowFactoryError> {
         flow_node_map.insert(node_id.clone(), node_arc);
 
         // Ensure all children are handled, so outgoing nodes will be correct
-        if let Some(parent_id) = child_to_parent.get(&node_id) {
+        for parent_id in child_to_parents.get(&node_id).into_iter().flatten() {
             if let Some(count) = remaining_children.get_mut(parent_id) {
                 *count = count.saturating_sub(1);
                 if *count == 0 {
@@ -107,18 +122,153 @@ pub fn from_json(json: &str) -> Result<Flow, FlowFactoryError> {
         });
     }
 
-    let flow = Flow::new(
-        flow.id,
-        flow.name,
-        flow.schedule,
-        flow.trigger,
-        start_node.ok_or_else(|| FlowFactoryError::MissingStartNode)?,
-        flow_node_map,
-    )
-    .expect("Flow creation failed");
+    validate_fork_joins(&flow_node_map, &child_to_parents)?;
+
+    let call_only = flow.call_only;
+    if call_only && (flow.schedule.is_some() || flow.trigger.is_some() || !flow.triggers.is_empty() || flow.condition.is_some()) {
+        return Err(FlowFactoryError::TriggeredCallOnlyFlow);
+    }
+    let (triggers, condition) = to_triggers(flow.schedule, flow.trigger, flow.trigger_mode, flow.triggers, flow.condition)?;
+    let mut flow = Flow::new(flow.id, flow.name, start_node.ok_or_else(|| FlowFactoryError::MissingStartNode)?, flow_node_map)
+        .expect("Flow creation failed")
+        .with_mode(flow.mode);
+    if !triggers.is_empty() || call_only {
+        flow = flow.with_triggers(triggers);
+    }
+    if let Some(condition) = condition {
+        flow = flow.with_condition(condition);
+    }
     Ok(flow)
 }
 
+/// Collects the triggers of a flow, the `schedule` and `trigger` of the flow come first. The `trigger` of a scheduled
+/// flow is the condition of the flow, otherwise it is an expression trigger. Triggers without an id are identified by
+/// their kind.
+fn to_triggers(
+    schedule: Option<Schedule>,
+    trigger: Option<Expression>,
+    trigger_mode: Option<TriggerMode>,
+    serialized_triggers: Vec<SerializedTrigger>,
+    condition: Option<Expression>,
+) -> Result<(Vec<Trigger>, Option<Expression>), FlowFactoryError> {
+    let mut triggers = Vec::with_capacity(serialized_triggers.len() + 1);
+    let condition = match (schedule, trigger) {
+        (Some(_), Some(_)) if condition.is_some() => return Err(FlowFactoryError::ConflictingCondition),
+        (Some(schedule), trigger) => {
+            triggers.push(Trigger::new("schedule".to_string(), TriggerKind::Schedule(schedule)));
+            trigger.or(condition)
+        }
+        (None, Some(trigger)) => {
+            let mode = trigger_mode.unwrap_or_else(|| default_trigger_mode(&trigger));
+            triggers.push(Trigger::new("expression".to_string(), TriggerKind::Expression { expression: trigger, mode }));
+            condition
+        }
+        (None, None) => condition,
+    };
+
+    for (index, serialized_trigger) in serialized_triggers.into_iter().enumerate() {
+        let SerializedTrigger {
+            id,
+            schedule,
+            property_changed,
+            expression,
+            trigger_mode,
+        } = serialized_trigger;
+        let (kind_name, kind) = match (schedule, property_changed, expression) {
+            (Some(schedule), None, None) => ("schedule", TriggerKind::Schedule(schedule)),
+            (None, Some(property_changed), None) => ("propertyChanged", expression_trigger(Expression::PropertyChanged(property_changed), trigger_mode)),
+            (None, None, Some(expression)) => ("expression", expression_trigger(expression, trigger_mode)),
+            _ => return Err(FlowFactoryError::InvalidTrigger { position: index + 1 }),
+        };
+        triggers.push(Trigger::new(id.unwrap_or_else(|| kind_name.to_string()), kind));
+    }
+
+    let mut ids = HashSet::new();
+    let duplicates: BTreeSet<&str> = triggers.iter().map(Trigger::id).filter(|id| !ids.insert(*id)).collect();
+    if !duplicates.is_empty() {
+        return Err(FlowFactoryError::DuplicateTriggerIds {
+            ids: duplicates.into_iter().map(str::to_string).collect(),
+        });
+    }
+
+    Ok((triggers, condition))
+}
+
+fn expression_trigger(expression: Expression, trigger_mode: Option<TriggerMode>) -> TriggerKind {
+    let mode = trigger_mode.unwrap_or_else(|| default_trigger_mode(&expression));
+    TriggerKind::Expression { expression, mode }
+}
+
+fn default_trigger_mode(expression: &Expression) -> TriggerMode {
+    if expression.depends_on_changes() {
+        TriggerMode::Level // Property changes are events already, they never stay true
+    } else {
+        TriggerMode::Edge
+    }
+}
+
+/// Checks that the branches of each fork node end at a single join node, and that this join node is only reached by the
+/// branches of that fork node.
+fn validate_fork_joins(flow_node_map: &HashMap<String, Arc<FlowNode>>, child_to_parents: &HashMap<String, Vec<String>>) -> Result<(), FlowFactoryError> {
+    let mut paired_join_nodes = HashSet::new();
+    for fork_node in flow_node_map.values().filter(|node| matches!(node.kind(), FlowNodeKind::Fork)) {
+        let (join_node, branch_nodes) = fork_join(fork_node)?;
+        let mut parents = child_to_parents.get(join_node.id()).into_iter().flatten();
+        if !paired_join_nodes.insert(join_node.id()) || parents.any(|parent| !branch_nodes.contains(parent)) {
+            return Err(FlowFactoryError::UnpairedJoinNode { node_id: join_node.id().to_string() });
+        }
+    }
+
+    match flow_node_map
+        .values()
+        .find(|node| matches!(node.kind(), FlowNodeKind::Join(_)) && !paired_join_nodes.contains(node.id()))
+    {
+        Some(join_node) => Err(FlowFactoryError::UnpairedJoinNode { node_id: join_node.id().to_string() }),
+        None => Ok(()),
+    }
+}
+
+/// Returns the join node the branches of the fork node end at, together with the ids of the nodes on the branches.
+fn fork_join(fork_node: &FlowNode) -> Result<(&FlowNode, HashSet<String>), FlowFactoryError> {
+    let mut branch_nodes = HashSet::from([fork_node.id().to_string()]);
+    let mut join_nodes = BTreeMap::new();
+    for link in fork_node.outgoing_nodes() {
+        collect_join_nodes(link.node(), &mut branch_nodes, &mut join_nodes)?;
+    }
+
+    if join_nodes.len() != 1 {
+        return Err(FlowFactoryError::MismatchedForkJoin {
+            node_id: fork_node.id().to_string(),
+            join_node_ids: join_nodes.into_keys().collect(),
+        });
+    }
+    let (_, join_node) = join_nodes.pop_first().expect("a single join node");
+    Ok((join_node, branch_nodes))
+}
+
+/// Follows all paths from the node until they reach a join node or an end node, nested fork nodes are passed at their
+/// own join node.
+fn collect_join_nodes<'a>(node: &'a FlowNode, branch_nodes: &mut HashSet<String>, join_nodes: &mut BTreeMap<String, &'a FlowNode>) -> Result<(), FlowFactoryError> {
+    let next_nodes = match node.kind() {
+        FlowNodeKind::Join(_) => {
+            join_nodes.insert(node.id().to_string(), node);
+            return Ok(());
+        }
+        _ if !branch_nodes.insert(node.id().to_string()) => return Ok(()),
+        FlowNodeKind::Fork => {
+            let (nested_join_node, _) = fork_join(node)?;
+            branch_nodes.insert(nested_join_node.id().to_string());
+            nested_join_node.outgoing_nodes()
+        }
+        _ => node.outgoing_nodes(),
+    };
+
+    for link in next_nodes {
+        collect_join_nodes(link.node(), branch_nodes, join_nodes)?;
+    }
+    Ok(())
+}
+
 fn map_outgoing_nodes(serialized_node: &SerializedFlowNode, flow_node_map: &HashMap<String, Arc<FlowNode>>) -> Result<Vec<FlowLink>, FlowFactoryError> {
     serialized_node
         .outgoing_nodes()
@@ -135,13 +285,16 @@ fn map_outgoing_nodes(serialized_node: &SerializedFlowNode, flow_node_map: &Hash
 }
 
 // Must own serialized_node so the contents can be moved to avoid copying data
-fn to_flow_node(serialized_node: SerializedFlowNode, outgoing_nodes: Vec<FlowLink>) -> FlowNode {
+fn to_flow_node(serialized_node: SerializedFlowNode, outgoing_nodes: Vec<FlowLink>, num_parents: usize) -> FlowNode {
     match serialized_node {
         SerializedFlowNode::StartNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::Start),
         SerializedFlowNode::EndNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::End),
         SerializedFlowNode::ConditionalNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::Conditional(node.expression)),
         SerializedFlowNode::ActionNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::Action(ActionFlowNode::new(node.action))),
-        SerializedFlowNode::SleepNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::Sleep(node.duration)),
+        SerializedFlowNode::SleepNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::Sleep(SleepFlowNode::new(node.duration, node.check_trigger))),
+        SerializedFlowNode::ForkNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::Fork),
+        SerializedFlowNode::JoinNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::Join(node.count.unwrap_or(num_parents))),
+        SerializedFlowNode::CallFlowNode(node) => FlowNode::new(node.id, outgoing_nodes, FlowNodeKind::CallFlow(CallFlowNode::new(node.flow_id, node.parameters))),
     }
 }
 
@@ -165,17 +318,34 @@ pub enum FlowFactoryError {
     UnusedNodes { nodes: Vec<String> },
     #[error("duplicate outgoing link values for node '{node_id}', pointing to {}", duplicates.join(", "))]
     DuplicateLinkValues { node_id: String, duplicates: Vec<String> },
+    #[error("join node '{node_id}' waits for {count} branch(es), but has {incoming} incoming link(s)")]
+    InvalidJoinCount { node_id: String, count: usize, incoming: usize },
+    #[error("the branches of fork node '{node_id}' must end at a single join node, found {}: {}", join_node_ids.len(), join_node_ids.join(", "))]
+    MismatchedForkJoin { node_id: String, join_node_ids: Vec<String> },
+    #[error("join node '{node_id}' must only be reached by the branches of a single fork node")]
+    UnpairedJoinNode { node_id: String },
+    #[error("trigger {position} must have exactly one of 'schedule', 'propertyChanged' or 'expression'")]
+    InvalidTrigger { position: usize },
+    #[error("duplicate trigger ids: {}, give each trigger a unique id", ids.join(", "))]
+    DuplicateTriggerIds { ids: Vec<String> },
+    #[error("the trigger of a scheduled flow is its condition, it cannot have both a trigger and a condition")]
+    ConflictingCondition,
+    #[error("a call-only flow only runs when it is called, it cannot have a schedule, triggers or a condition")]
+    TriggeredCallOnlyFlow,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::domain::Number;
-    use crate::flow_engine::Expression::Literal;
+    use crate::flow_engine::Expression::{Literal, PropertyChanged};
     use crate::flow_engine::Value;
     use crate::flow_engine::action::{ControlDeviceAction, LogAction};
-    use crate::flow_engine::property_value::PropertyValue::SetBooleanValue;
+    use crate::flow_engine::expression::PropertyChangedExpression;
+    use crate::flow_engine::property_value::Payload;
+    use crate::flow_engine::property_value::PropertyValueExpression::SetBooleanValue;
     use pretty_assertions::assert_eq;
+    use rstest::rstest;
     use std::time::Duration;
 
     #[tokio::test]
@@ -248,6 +418,43 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn returns_an_error_if_a_join_node_waits_for_more_branches_than_incoming_links() {
+        let json = include_str!("../../tests/resources/flows/invalid/invalidJoinCountFlow.json");
+        let result = from_json(json);
+        match result {
+            Err(FlowFactoryError::InvalidJoinCount { node_id, count, incoming }) => {
+                assert_eq!(node_id, "joinNode");
+                assert_eq!(count, 3);
+                assert_eq!(incoming, 2);
+            }
+            other => panic!("expected FlowFactoryError::InvalidJoinCount, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_an_error_if_the_branches_of_a_fork_node_end_at_different_join_nodes() {
+        let json = include_str!("../../tests/resources/flows/invalid/mismatchedForkJoinFlow.json");
+        let result = from_json(json);
+        match result {
+            Err(FlowFactoryError::MismatchedForkJoin { node_id, join_node_ids }) => {
+                assert_eq!(node_id, "forkNode");
+                assert_eq!(join_node_ids, vec!["joinNode", "otherJoinNode"]);
+            }
+            other => panic!("expected FlowFactoryError::MismatchedForkJoin, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn returns_an_error_if_a_join_node_is_reached_outside_the_branches_of_its_fork_node() {
+        let json = include_str!("../../tests/resources/flows/invalid/unpairedJoinNodeFlow.json");
+        let result = from_json(json);
+        match result {
+            Err(FlowFactoryError::UnpairedJoinNode { node_id }) => assert_eq!(node_id, "joinNode"),
+            other => panic!("expected FlowFactoryError::UnpairedJoinNode, got {:?}", other),
+        }
+    }
+
     #[tokio::test]
     async fn creates_a_flow_with_a_start_and_end_node() {
         let json = include_str!("../../tests/resources/flows/emptyFlow.json");
@@ -256,15 +463,7 @@ mod tests {
         let end_node = FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End);
         let start_node = FlowNode::new("startNode".to_string(), vec![FlowLink::new(Arc::new(end_node), Value::None)], FlowNodeKind::Start);
 
-        let expected = Flow::new(
-            "01K7KK65D87SZGGZE7VB8QYT20".to_string(),
-            "emptyFlow".to_string(),
-            None,
-            None,
-            Arc::new(start_node),
-            HashMap::new(),
-        )
-        .unwrap();
+        let expected = Flow::new("01K7KK65D87SZGGZE7VB8QYT20".to_string(), "emptyFlow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
         assert_eq!(format!("{:#?}", flow), format!("{:#?}", expected));
     }
 
@@ -283,15 +482,7 @@ mod tests {
 
         let start_node = FlowNode::new("startNode".to_string(), vec![FlowLink::new(Arc::new(action_node), Value::None)], FlowNodeKind::Start);
 
-        let expected = Flow::new(
-            "01K7KK6H5R7Y72QJEJSJQCKMRQ".to_string(),
-            "logFlow".to_string(),
-            None,
-            None,
-            Arc::new(start_node),
-            HashMap::new(),
-        )
-        .unwrap();
+        let expected = Flow::new("01K7KK6H5R7Y72QJEJSJQCKMRQ".to_string(), "logFlow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
         assert_eq!(format!("{:#?}", flow), format!("{:#?}", expected));
     }
 
@@ -307,7 +498,7 @@ mod tests {
             vec![FlowLink::new(Arc::new(end_node), Value::None)],
             FlowNodeKind::Action(ActionFlowNode::new(Box::new(ControlDeviceAction::new(
                 "42".to_string(),
-                HashMap::from([("fan".to_string(), SetBooleanValue(true))]),
+                HashMap::from([("fan".to_string(), SetBooleanValue(Payload::Literal(true)))]),
             )))),
         );
 
@@ -316,8 +507,6 @@ mod tests {
         let expected = Flow::new(
             "01K7KK5FC54SN8D4QYVNEGFYG4".to_string(),
             "controlDeviceFlow".to_string(),
-            None,
-            None,
             Arc::new(start_node),
             HashMap::new(),
         )
@@ -349,8 +538,6 @@ mod tests {
         let expected = Flow::new(
             "01K8JSTTCC831M6TERRH41D595".to_string(),
             "conditionalFlow".to_string(),
-            None,
-            None,
             Arc::new(start_node),
             HashMap::new(),
         )
@@ -368,21 +555,184 @@ mod tests {
         let sleep_node = FlowNode::new(
             "sleepNode".to_string(),
             vec![FlowLink::new(Arc::new(end_node), Value::None)],
-            FlowNodeKind::Sleep(Duration::from_secs(3907)),
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(3907), false)),
         );
 
         let start_node = FlowNode::new("startNode".to_string(), vec![FlowLink::new(Arc::new(sleep_node), Value::None)], FlowNodeKind::Start);
 
-        let expected = Flow::new(
-            "01K7KK7E6GG26XZZDXSGFZCWQ4".to_string(),
-            "sleepFlow".to_string(),
-            None,
-            None,
-            Arc::new(start_node),
-            HashMap::new(),
-        )
-        .unwrap();
+        let expected = Flow::new("01K7KK7E6GG26XZZDXSGFZCWQ4".to_string(), "sleepFlow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
+        assert_eq!(format!("{:#?}", flow), format!("{:#?}", expected));
+    }
+
+    #[tokio::test]
+    async fn creates_a_flow_with_fork_and_join_nodes() {
+        let json = include_str!("../../tests/resources/flows/forkJoinFlow.json");
+        let flow = from_json(json).unwrap();
+
+        let end_node = FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End);
+        let join_node = Arc::new(FlowNode::new(
+            "joinNode".to_string(),
+            vec![FlowLink::new(Arc::new(end_node), Value::None)],
+            FlowNodeKind::Join(1),
+        ));
+
+        let action_node = FlowNode::new(
+            "logNode".to_string(),
+            vec![FlowLink::new(join_node.clone(), Value::None)],
+            FlowNodeKind::Action(ActionFlowNode::new(Box::new(LogAction::new("Action is triggered".to_string())))),
+        );
+        let sleep_node = FlowNode::new(
+            "sleepNode".to_string(),
+            vec![FlowLink::new(join_node, Value::None)],
+            FlowNodeKind::Sleep(SleepFlowNode::new(Duration::from_secs(300), false)),
+        );
+
+        let fork_node = FlowNode::new(
+            "forkNode".to_string(),
+            vec![FlowLink::new(Arc::new(action_node), Value::None), FlowLink::new(Arc::new(sleep_node), Value::None)],
+            FlowNodeKind::Fork,
+        );
+
+        let start_node = FlowNode::new("startNode".to_string(), vec![FlowLink::new(Arc::new(fork_node), Value::None)], FlowNodeKind::Start);
+
+        let expected = Flow::new("01K9A3N6Q2W7ZB5XJ4Y8KT1M0C".to_string(), "forkJoinFlow".to_string(), Arc::new(start_node), HashMap::new()).unwrap();
+        assert_eq!(format!("{:#?}", flow), format!("{:#?}", expected));
+    }
+
+    const NODES: &str = r#""nodes": [{ "id": "startNode", "type": "startNode", "outgoingNode": "endNode" }, { "id": "endNode", "type": "endNode" }]"#;
+
+    fn trigger_modes(flow: &Flow) -> Vec<TriggerMode> {
+        flow.trigger_expressions().map(|(_, _, mode)| mode).collect()
+    }
+
+    #[tokio::test]
+    async fn uses_edge_triggering_only_for_flows_with_a_trigger_by_default() {
+        let trigger = r#""trigger": { "type": "literal", "value": true }"#;
+
+        let flow = from_json(&format!(r#"{{ "id": "id", "name": "flow", {} }}"#, NODES)).unwrap();
+        assert_eq!(trigger_modes(&flow), vec![TriggerMode::Level]);
+
+        let flow = from_json(&format!(r#"{{ "id": "id", "name": "flow", {}, {} }}"#, trigger, NODES)).unwrap();
+        assert_eq!(trigger_modes(&flow), vec![TriggerMode::Edge]);
+
+        let flow = from_json(&format!(r#"{{ "id": "id", "name": "flow", "triggerMode": "level", {}, {} }}"#, trigger, NODES)).unwrap();
+        assert_eq!(trigger_modes(&flow), vec![TriggerMode::Level]);
+    }
+
+    #[tokio::test]
+    async fn creates_a_level_triggered_flow_for_a_property_changed_trigger() {
+        let json = include_str!("../../tests/resources/flows/propertyChangedFlow.json");
+        let flow = from_json(json).unwrap();
+
+        let expected = PropertyChanged(PropertyChangedExpression {
+            device_id: "ab917a9a-a7d5-4853-9518-75909236a182".to_string(),
+            property_id: "on".to_string(),
+            from: Some(Value::Boolean(false)),
+            to: Some(Value::Boolean(true)),
+        });
+        assert_eq!(
+            flow.triggers(),
+            &[Trigger::new(
+                "expression".to_string(),
+                TriggerKind::Expression {
+                    expression: expected,
+                    mode: TriggerMode::Level
+                }
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn uses_the_trigger_of_a_scheduled_flow_as_its_condition() {
+        let json = include_str!("../../tests/resources/flows/logFlowWithSchedule.json").replacen("\"schedule\"", r#""trigger": "lamp.on", "schedule""#, 1);
+        let flow = from_json(&json).unwrap();
+
+        let schedule_ids = flow.schedules().map(|(trigger_id, _)| trigger_id).collect::<Vec<_>>();
+        assert_eq!(schedule_ids, vec!["schedule"]);
+        assert_eq!(flow.trigger_expressions().count(), 0);
+        assert_eq!(flow.condition().to_string(), "lamp.on");
+    }
+
+    #[tokio::test]
+    async fn creates_a_flow_with_multiple_triggers() {
+        let json = include_str!("../../tests/resources/flows/multipleTriggersFlow.json");
+        let flow = from_json(json).unwrap();
+
+        let schedules = flow.schedules().map(|(trigger_id, schedule)| format!("{}: {}", trigger_id, schedule)).collect::<Vec<_>>();
+        assert_eq!(schedules, vec!["sunset: sunset on any, offset -15m"]);
+        let expressions = flow
+            .trigger_expressions()
+            .map(|(trigger_id, expression, mode)| (trigger_id, expression.to_string(), mode))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            expressions,
+            vec![
+                ("frontDoor", "changed(\"front-door\".open, _, true)".to_string(), TriggerMode::Level),
+                ("expression", "hallway.motion".to_string(), TriggerMode::Level),
+            ]
+        );
+        assert_eq!(flow.condition().to_string(), "!livingRoom.on");
+    }
+
+    #[rstest]
+    #[case::no_kind(r#"[{ "id": "empty" }]"#, "trigger 1 must have exactly one of 'schedule', 'propertyChanged' or 'expression'")]
+    #[case::two_kinds(
+        r#"[{ "expression": "lamp.on" }, { "schedule": "0 0 7 * * *", "expression": "lamp.on" }]"#,
+        "trigger 2 must have exactly one of 'schedule', 'propertyChanged' or 'expression'"
+    )]
+    #[case::duplicate_ids(
+        r#"[{ "expression": "lamp.on" }, { "expression": "fan.on" }, { "id": "lamp", "schedule": "0 0 7 * * *" }, { "id": "lamp", "expression": "lamp.on" }]"#,
+        "duplicate trigger ids: expression, lamp, give each trigger a unique id"
+    )]
+    fn returns_an_error_for_invalid_triggers(#[case] triggers: &str, #[case] expected: &str) {
+        let result = from_json(&format!(r#"{{ "id": "id", "name": "flow", "triggers": {}, {} }}"#, triggers, NODES));
+
+        assert_eq!(result.unwrap_err().to_string(), expected);
+    }
+
+    #[test]
+    fn returns_an_error_if_a_scheduled_flow_has_both_a_trigger_and_a_condition() {
+        let result = from_json(&format!(
+            r#"{{ "id": "id", "name": "flow", "schedule": "0 0 7 * * *", "trigger": "lamp.on", "condition": "fan.on", {} }}"#,
+            NODES
+        ));
+
+        assert!(matches!(result, Err(FlowFactoryError::ConflictingCondition)));
+    }
+
+    #[tokio::test]
+    async fn creates_a_flow_with_a_call_flow_node() {
+        let json = include_str!("../../tests/resources/flows/callFlowFlow.json");
+        let flow = from_json(json).unwrap();
+
+        let end_node = FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End);
+
+        let parameters = HashMap::from([(
+            "level".to_string(),
+            Literal {
+                value: Value::Number(Number::PositiveInt(30)),
+            },
+        )]);
+        let call_flow_node = FlowNode::new(
+            "callFlowNode".to_string(),
+            vec![FlowLink::new(Arc::new(end_node), Value::None)],
+            FlowNodeKind::CallFlow(CallFlowNode::new("01K7KK6H5R7Y72QJEJSJQCKMRQ".to_string(), parameters)),
+        );
+
+        let start_node = FlowNode::new("startNode".to_string(), vec![FlowLink::new(Arc::new(call_flow_node), Value::None)], FlowNodeKind::Start);
+
+        let expected = Flow::new("01K9C2X7M4R8TQ1VZ6N3HJ5WDE".to_string(), "callFlowFlow".to_string(), Arc::new(start_node), HashMap::new())
+            .unwrap()
+            .with_triggers(vec![]);
         assert_eq!(format!("{:#?}", flow), format!("{:#?}", expected));
+        assert_eq!(flow.called_flow_ids(), vec!["01K7KK6H5R7Y72QJEJSJQCKMRQ"]);
+    }
+
+    #[test]
+    fn rejects_a_call_only_flow_with_a_trigger() {
+        let result = from_json(&format!(r#"{{ "id": "id", "name": "flow", "callOnly": true, "trigger": "lamp.on", {} }}"#, NODES));
+
+        assert!(matches!(result, Err(FlowFactoryError::TriggeredCallOnlyFlow)));
     }
 }
 
@@ -393,8 +743,9 @@ impl std::fmt::Debug for Flow {
         f.debug_struct("Flow")
             .field("id", &self.id())
             .field("name", &self.name())
-            .field("schedule", &self.schedule())
-            .field("trigger", &self.trigger())
+            .field("triggers", &self.triggers())
+            .field("condition", &self.condition())
+            .field("mode", &self.mode())
             .field("start_node", &self.start_node())
             .finish()
     }
@@ -0,0 +1,227 @@
+use crate::flow_engine::flow::Flow;
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
+
+/// Rejects flows that call themselves, directly or via other flows, flows that call a flow with a sleep node and flows
+/// that call a flow that does not exist or is rejected itself. Returns the valid flows and an error for every rejected
+/// flow.
+pub fn validate_flow_calls(flows: Vec<Flow>) -> (Vec<Flow>, Vec<FlowCallError>) {
+    let calls: HashMap<&str, Vec<&str>> = flows
+        .iter()
+        .map(|flow| {
+            let mut called_flow_ids = flow.called_flow_ids();
+            called_flow_ids.sort();
+            called_flow_ids.dedup();
+            (flow.id(), called_flow_ids)
+        })
+        .collect();
+
+    let mut errors = Vec::new();
+    let mut rejected: HashSet<String> = HashSet::new();
+
+    let mut visited: HashSet<&str> = HashSet::new();
+    for flow in flows.iter() {
+        let mut call_chain = Vec::new();
+        find_recursive_calls(flow.id(), &calls, &mut visited, &mut call_chain, &mut |cycle| {
+            rejected.extend(cycle.iter().cloned());
+            errors.push(FlowCallError::RecursiveCall { call_chain: cycle });
+        });
+    }
+
+    // A called flow runs as part of the run of its caller, where it cannot sleep
+    let sleep_node_ids: HashMap<&str, &str> = flows.iter().filter_map(|flow| flow.sleep_node_id().map(|node_id| (flow.id(), node_id))).collect();
+    let sleeping_calls: Vec<(&str, &str, &str)> = flows
+        .iter()
+        .filter(|flow| !rejected.contains(flow.id()))
+        .filter_map(|flow| {
+            calls[flow.id()]
+                .iter()
+                .find_map(|called_flow_id| sleep_node_ids.get(called_flow_id).map(|node_id| (flow.id(), *called_flow_id, *node_id)))
+        })
+        .collect();
+    for (flow_id, called_flow_id, node_id) in sleeping_calls {
+        rejected.insert(flow_id.to_string());
+        errors.push(FlowCallError::SleepingCalledFlow {
+            flow_id: flow_id.to_string(),
+            called_flow_id: called_flow_id.to_string(),
+            node_id: node_id.to_string(),
+        });
+    }
+
+    // Reject callers of unknown or rejected flows until nothing changes, as callers of those callers are affected too
+    loop {
+        let invalid_calls: Vec<(&str, &str)> = flows
+            .iter()
+            .filter(|flow| !rejected.contains(flow.id()))
+            .filter_map(|flow| {
+                calls[flow.id()]
+                    .iter()
+                    .find(|id| !calls.contains_key(*id) || rejected.contains(**id))
+                    .map(|called_flow_id| (flow.id(), *called_flow_id))
+            })
+            .collect();
+
+        if invalid_calls.is_empty() {
+            break;
+        }
+
+        for (flow_id, called_flow_id) in invalid_calls {
+            rejected.insert(flow_id.to_string());
+            errors.push(FlowCallError::InvalidCalledFlow {
+                flow_id: flow_id.to_string(),
+                called_flow_id: called_flow_id.to_string(),
+            });
+        }
+    }
+
+    let flows = flows.into_iter().filter(|flow| !rejected.contains(flow.id())).collect();
+    (flows, errors)
+}
+
+fn find_recursive_calls<'a>(
+    flow_id: &'a str,
+    calls: &HashMap<&'a str, Vec<&'a str>>,
+    visited: &mut HashSet<&'a str>,
+    call_chain: &mut Vec<&'a str>,
+    on_cycle: &mut impl FnMut(Vec<String>),
+) {
+    if let Some(position) = call_chain.iter().position(|id| *id == flow_id) {
+        let mut cycle: Vec<String> = call_chain[position..].iter().map(|id| id.to_string()).collect();
+        cycle.push(flow_id.to_string());
+        on_cycle(cycle);
+        return;
+    }
+
+    if !visited.insert(flow_id) {
+        return;
+    }
+
+    call_chain.push(flow_id);
+    for called_flow_id in calls.get(flow_id).into_iter().flatten() {
+        find_recursive_calls(called_flow_id, calls, visited, call_chain, on_cycle);
+    }
+    call_chain.pop();
+}
+
+#[derive(Error, PartialEq, Debug)]
+pub enum FlowCallError {
+    #[error("recursive flow call: {}", call_chain.join(" -> "))]
+    RecursiveCall { call_chain: Vec<String> },
+    #[error("flow '{flow_id}' calls flow '{called_flow_id}' which has sleep node '{node_id}', called flows cannot sleep")]
+    SleepingCalledFlow { flow_id: String, called_flow_id: String, node_id: String },
+    #[error("flow '{flow_id}' calls flow '{called_flow_id}' which is unknown or failed to load")]
+    InvalidCalledFlow { flow_id: String, called_flow_id: String },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_loader::factory::from_json;
+    use pretty_assertions::assert_eq;
+
+    fn flow(id: &str, called_flow_ids: &[&str]) -> Flow {
+        let mut nodes = vec![format!(r#"{{ "id": "startNode", "type": "startNode", "outgoingNode": "call0" }}"#)];
+        for (index, called_flow_id) in called_flow_ids.iter().enumerate() {
+            nodes.push(format!(
+                r#"{{ "id": "call{}", "type": "callFlowNode", "flowId": "{}", "outgoingNode": "call{}" }}"#,
+                index,
+                called_flow_id,
+                index + 1
+            ));
+        }
+        nodes.push(format!(r#"{{ "id": "call{}", "type": "endNode" }}"#, called_flow_ids.len()));
+
+        let json = format!(r#"{{ "id": "{}", "name": "{}", "nodes": [{}] }}"#, id, id, nodes.join(","));
+        from_json(&json).unwrap()
+    }
+
+    fn ids(flows: &[Flow]) -> Vec<&str> {
+        let mut ids = flows.iter().map(Flow::id).collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn accepts_flows_without_recursive_calls() {
+        let (flows, errors) = validate_flow_calls(vec![flow("a", &["b", "c"]), flow("b", &["c"]), flow("c", &[])]);
+
+        assert_eq!(ids(&flows), vec!["a", "b", "c"]);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_flow_that_calls_itself() {
+        let (flows, errors) = validate_flow_calls(vec![flow("a", &["a"]), flow("b", &[])]);
+
+        assert_eq!(ids(&flows), vec!["b"]);
+        assert_eq!(
+            errors,
+            vec![FlowCallError::RecursiveCall {
+                call_chain: vec!["a".to_string(), "a".to_string()]
+            }]
+        );
+    }
+
+    #[test]
+    fn rejects_flows_that_call_each_other_and_their_callers() {
+        let (flows, errors) = validate_flow_calls(vec![flow("a", &["b"]), flow("b", &["c"]), flow("c", &["b"]), flow("d", &[])]);
+
+        assert_eq!(ids(&flows), vec!["d"]);
+        assert_eq!(
+            errors,
+            vec![
+                FlowCallError::RecursiveCall {
+                    call_chain: vec!["b".to_string(), "c".to_string(), "b".to_string()]
+                },
+                FlowCallError::InvalidCalledFlow {
+                    flow_id: "a".to_string(),
+                    called_flow_id: "b".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_flow_that_calls_a_flow_with_a_sleep_node_and_its_callers() {
+        let sleeping_flow = from_json(
+            r#"{ "id": "c", "name": "c", "nodes": [
+                { "id": "startNode", "type": "startNode", "outgoingNode": "sleepNode" },
+                { "id": "sleepNode", "type": "sleepNode", "duration": "5m", "outgoingNode": "endNode" },
+                { "id": "endNode", "type": "endNode" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let (flows, errors) = validate_flow_calls(vec![flow("a", &["b"]), flow("b", &["c"]), sleeping_flow]);
+
+        assert_eq!(ids(&flows), vec!["c"]);
+        assert_eq!(
+            errors,
+            vec![
+                FlowCallError::SleepingCalledFlow {
+                    flow_id: "b".to_string(),
+                    called_flow_id: "c".to_string(),
+                    node_id: "sleepNode".to_string()
+                },
+                FlowCallError::InvalidCalledFlow {
+                    flow_id: "a".to_string(),
+                    called_flow_id: "b".to_string()
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_a_flow_that_calls_an_unknown_flow() {
+        let (flows, errors) = validate_flow_calls(vec![flow("a", &["unknown"])]);
+
+        assert!(flows.is_empty());
+        assert_eq!(
+            errors,
+            vec![FlowCallError::InvalidCalledFlow {
+                flow_id: "a".to_string(),
+                called_flow_id: "unknown".to_string()
+            }]
+        );
+    }
+}
@@ -0,0 +1,72 @@
+use crate::flow_engine::flow::FlowMode;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+impl<'de> Deserialize<'de> for FlowMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+
+        let (mode, max) = match &value {
+            Value::String(mode) => (mode.as_str(), None),
+            Value::Object(map) => {
+                let mode = map.get("type").and_then(|v| v.as_str()).ok_or_else(|| Error::custom("missing or invalid field 'type'"))?;
+                let max = match map.get("max") {
+                    Some(max) => match max.as_u64() {
+                        Some(max) if max > 0 => Some(max as usize),
+                        _ => return Err(Error::custom("field 'max' must be a positive number")),
+                    },
+                    None => None,
+                };
+                (mode, max)
+            }
+            _ => return Err(Error::custom("a string containing a mode or an object with 'type' and 'max'")),
+        };
+
+        match (mode, max) {
+            ("single", None) => Ok(FlowMode::Single),
+            ("restart", None) => Ok(FlowMode::Restart),
+            ("queued", max) => Ok(FlowMode::Queued { max }),
+            ("parallel", max) => Ok(FlowMode::Parallel { max }),
+            ("single" | "restart", Some(_)) => Err(Error::custom(format!("mode '{}' does not support field 'max'", mode))),
+            _ => Err(Error::custom(format!("unknown mode '{}', expected single, restart, queued or parallel", mode))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case::single(json!("single"), FlowMode::Single)]
+    #[case::restart(json!("restart"), FlowMode::Restart)]
+    #[case::queued(json!("queued"), FlowMode::Queued { max: None })]
+    #[case::parallel(json!("parallel"), FlowMode::Parallel { max: None })]
+    #[case::single_object(json!({ "type": "single" }), FlowMode::Single)]
+    #[case::queued_with_max(json!({ "type": "queued", "max": 3 }), FlowMode::Queued { max: Some(3) })]
+    #[case::parallel_with_max(json!({ "type": "parallel", "max": 5 }), FlowMode::Parallel { max: Some(5) })]
+    fn deserializes_valid_values(#[case] json: Value, #[case] expected: FlowMode) {
+        let parsed: FlowMode = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, expected);
+    }
+
+    #[rstest]
+    #[case::unknown_mode(json!("sequential"), "unknown mode 'sequential'")]
+    #[case::missing_type(json!({ "max": 3 }), "missing or invalid field 'type'")]
+    #[case::zero_max(json!({ "type": "queued", "max": 0 }), "field 'max' must be a positive number")]
+    #[case::max_not_supported(json!({ "type": "single", "max": 2 }), "mode 'single' does not support field 'max'")]
+    #[case::invalid_type(json!(42), "a string containing a mode or an object with 'type' and 'max'")]
+    fn deserialize_fails_for_invalid_cases(#[case] json: Value, #[case] expected_message: &str) {
+        let parsed: Result<FlowMode, _> = serde_json::from_value(json);
+        let err = parsed.expect_err("expected an error but got Ok");
+        let msg = err.to_string();
+        assert!(msg.contains(expected_message), "Expected error message to contain '{expected_message}', but got '{msg}'");
+    }
+}
@@ -1,6 +1,7 @@
 use crate::extensions::path_ext::FileName;
 use crate::flow_engine::flow::Flow;
 use crate::flow_loader::factory::{FlowFactoryError, from_json};
+use crate::flow_loader::flow_call_validator::{FlowCallError, validate_flow_calls};
 use futures::stream::FuturesUnordered;
 use std::io;
 use std::path::PathBuf;
@@ -21,13 +22,18 @@ pub async fn load_flows_from(directory: &str, extension: &str) -> Result<Vec<Flo
 
     let results = load_files(files).await;
     let (flows, errors): (Vec<_>, Vec<_>) = results.into_iter().partition(Result::is_ok);
+    let flows = flows.into_iter().filter_map(Result::ok).collect();
+    let mut errors: Vec<_> = errors.into_iter().filter_map(Result::err).collect();
 
-    for error in errors.iter().filter_map(|res| res.as_ref().err()) {
+    let (flows, call_errors) = validate_flow_calls(flows);
+    errors.extend(call_errors.into_iter().map(LoaderError::from));
+
+    for error in errors.iter() {
         log_error(error);
     }
 
     info!("📁 Loading flows... OK, {} loaded, {} failed", flows.len(), errors.len());
-    Ok(flows.into_iter().filter_map(Result::ok).collect())
+    Ok(flows)
 }
 
 #[instrument]
@@ -71,6 +77,7 @@ fn log_error(error: &LoaderError) {
             Some(path) => warn!("⚠️ Failed to load '{}': {}", path.string_file_name(), source),
             None => warn!("⚠️ {}", source),
         },
+        LoaderError::FlowCall(err) => warn!("⚠️ {}", err),
         LoaderError::JoinError(err) => warn!("⚠️ {}", err),
     }
 }
@@ -82,6 +89,8 @@ pub enum LoaderError {
     #[error("{}", source)]
     Io { source: io::Error, path: Option<PathBuf> },
     #[error(transparent)]
+    FlowCall(#[from] FlowCallError),
+    #[error(transparent)]
     JoinError(#[from] JoinError),
 }
 
@@ -1,11 +1,19 @@
 mod color_deserializer;
+mod date_deserializer;
+mod expression_deserializer;
+mod expression_parser;
 mod factory;
+mod flow_call_validator;
+mod flow_mode_deserializer;
 mod loader;
+mod pattern_deserializer;
 mod property_value_deserializer;
 mod schedule_deserializer;
 pub(in crate::flow_loader) mod serialized_flow;
 mod serialized_flow_link_deserializer;
+mod solar_event_deserializer;
 mod time_deserializer;
+mod trigger_mode_deserializer;
 mod value_deserializer;
 mod weekday_condition_deserializer;
 mod weekday_deserializer;
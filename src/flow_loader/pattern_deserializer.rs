@@ -0,0 +1,36 @@
+use crate::flow_engine::expression::Pattern;
+use regex::Regex;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+impl<'de> Deserialize<'de> for Pattern {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let pattern = String::deserialize(deserializer)?;
+        Regex::new(&pattern)
+            .map(Pattern)
+            .map_err(|e| Error::custom(format!("invalid pattern '{}': {}", pattern, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn deserializes_a_valid_pattern() {
+        let pattern = serde_json::from_value::<Pattern>(json!("^long_(press|release)$")).unwrap();
+
+        assert!(pattern.0.is_match("long_press"));
+    }
+
+    #[test]
+    fn fails_for_an_invalid_pattern() {
+        let result = serde_json::from_value::<Pattern>(json!("long_(press"));
+
+        assert!(result.unwrap_err().to_string().starts_with("invalid pattern 'long_(press': regex parse error"));
+    }
+}
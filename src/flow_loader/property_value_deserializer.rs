@@ -1,12 +1,13 @@
 use crate::domain::Number;
 use crate::domain::color::Color;
-use crate::flow_engine::property_value::PropertyValue;
+use crate::flow_engine::Expression;
+use crate::flow_engine::property_value::{Payload, PropertyValueExpression};
 use serde::de::Error;
 use serde::{Deserialize, Deserializer};
 use serde_json::Number as JsonNumber;
 use std::ops::{Index, IndexMut};
 
-impl<'de> Deserialize<'de> for PropertyValue {
+impl<'de> Deserialize<'de> for PropertyValueExpression {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
@@ -16,31 +17,50 @@ impl<'de> Deserialize<'de> for PropertyValue {
 
         match kind {
             "boolean" => {
-                let value = value.index_mut("value").as_bool().ok_or_missing("value", "boolean")?;
-                Ok(PropertyValue::SetBooleanValue(value))
+                let payload = payload(value.index_mut("value"), |value| value.as_bool().ok_or_missing("value", "boolean"))?;
+                Ok(PropertyValueExpression::SetBooleanValue(payload))
             }
-            "toggle" => Ok(PropertyValue::ToggleBooleanValue),
+            "toggle" => Ok(PropertyValueExpression::ToggleBooleanValue),
             "number" => {
-                let value = value.index_mut("value").as_number().ok_or_missing("value", "number")?;
-                Ok(PropertyValue::SetNumberValue(value.into()))
+                let payload = payload(value.index_mut("value"), |value| value.as_number().map(Number::from).ok_or_missing("value", "number"))?;
+                Ok(PropertyValueExpression::SetNumberValue(payload))
             }
             "increment" => {
-                let value = value.index_mut("value").as_number().ok_or_missing("value", "number")?;
-                Ok(PropertyValue::IncrementNumberValue(value.into()))
+                let payload = payload(value.index_mut("value"), |value| value.as_number().map(Number::from).ok_or_missing("value", "number"))?;
+                Ok(PropertyValueExpression::IncrementNumberValue(payload))
             }
             "decrement" => {
-                let value = value.index_mut("value").as_number().ok_or_missing("value", "number")?;
-                Ok(PropertyValue::DecrementNumberValue(value.into()))
+                let payload = payload(value.index_mut("value"), |value| value.as_number().map(Number::from).ok_or_missing("value", "number"))?;
+                Ok(PropertyValueExpression::DecrementNumberValue(payload))
             }
             "color" => {
-                let color = Color::deserialize(value.index("value")).map_err(|e| Error::custom(e.to_string()))?;
-                Ok(PropertyValue::SetColor(color))
+                // Colors are strings or objects as well, so only an object with a type is an expression
+                let value = value.index("value");
+                let payload = if value.get("type").is_some() {
+                    Payload::Expression(expression(value)?)
+                } else {
+                    Payload::Literal(Color::deserialize(value).map_err(|e| Error::custom(e.to_string()))?)
+                };
+                Ok(PropertyValueExpression::SetColor(payload))
             }
             _ => Err(Error::unknown_variant(&kind, &["boolean", "toggle", "number", "increment", "decrement", "color"])),
         }
     }
 }
 
+/// Deserializes a payload, an object or a string is an expression that is evaluated when the action executes.
+fn payload<T, E: Error>(value: &serde_json::Value, literal: impl FnOnce(&serde_json::Value) -> Result<T, E>) -> Result<Payload<T>, E> {
+    if value.is_object() || value.is_string() {
+        expression(value).map(Payload::Expression)
+    } else {
+        literal(value).map(Payload::Literal)
+    }
+}
+
+fn expression<E: Error>(value: &serde_json::Value) -> Result<Expression, E> {
+    <Expression as Deserialize>::deserialize(value).map_err(|e| Error::custom(format!("invalid expression for field 'value': {}", e)))
+}
+
 impl From<&JsonNumber> for Number {
     fn from(value: &JsonNumber) -> Self {
         if let Some(int_value) = value.as_u64() {
@@ -95,9 +115,9 @@ mod tests {
           }
         "#;
 
-        let response = serde_json::from_str::<PropertyValue>(json);
+        let response = serde_json::from_str::<PropertyValueExpression>(json);
         assert!(response.is_ok());
-        assert_eq!(response.unwrap(), PropertyValue::SetBooleanValue(true));
+        assert_eq!(response.unwrap(), PropertyValueExpression::SetBooleanValue(Payload::Literal(true)));
     }
 
     #[test]
@@ -108,9 +128,9 @@ mod tests {
           }
         "#;
 
-        let response = serde_json::from_str::<PropertyValue>(json);
+        let response = serde_json::from_str::<PropertyValueExpression>(json);
         assert!(response.is_ok());
-        assert_eq!(response.unwrap(), PropertyValue::ToggleBooleanValue);
+        assert_eq!(response.unwrap(), PropertyValueExpression::ToggleBooleanValue);
     }
 
     #[rstest]
@@ -126,9 +146,9 @@ mod tests {
             json_value
         );
 
-        let response = serde_json::from_str::<PropertyValue>(&json);
+        let response = serde_json::from_str::<PropertyValueExpression>(&json);
         assert!(response.is_ok());
-        assert_eq!(response.unwrap(), PropertyValue::SetNumberValue(expected_number));
+        assert_eq!(response.unwrap(), PropertyValueExpression::SetNumberValue(Payload::Literal(expected_number)));
     }
 
     #[rstest]
@@ -144,9 +164,9 @@ mod tests {
             json_value
         );
 
-        let response = serde_json::from_str::<PropertyValue>(&json);
+        let response = serde_json::from_str::<PropertyValueExpression>(&json);
         assert!(response.is_ok());
-        assert_eq!(response.unwrap(), PropertyValue::IncrementNumberValue(expected_number));
+        assert_eq!(response.unwrap(), PropertyValueExpression::IncrementNumberValue(Payload::Literal(expected_number)));
     }
 
     #[rstest]
@@ -162,16 +182,96 @@ mod tests {
             json_value
         );
 
-        let response = serde_json::from_str::<PropertyValue>(&json);
+        let response = serde_json::from_str::<PropertyValueExpression>(&json);
         assert!(response.is_ok());
-        assert_eq!(response.unwrap(), PropertyValue::DecrementNumberValue(expected_number));
+        assert_eq!(response.unwrap(), PropertyValueExpression::DecrementNumberValue(Payload::Literal(expected_number)));
+    }
+
+    #[test]
+    fn deserialize_set_number_value_with_an_expression() {
+        let json = r#"
+          {
+            "type": "number",
+            "value": {
+              "type": "propertyValue",
+              "deviceId": "lamp",
+              "propertyId": "brightness"
+            }
+          }
+        "#;
+
+        let response = serde_json::from_str::<PropertyValueExpression>(json);
+        assert_eq!(
+            response.unwrap(),
+            PropertyValueExpression::SetNumberValue(Payload::Expression(Expression::PropertyValue {
+                device_id: "lamp".to_string(),
+                property_id: "brightness".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn deserialize_increment_number_value_with_a_textual_expression() {
+        let json = r#"
+          {
+            "type": "increment",
+            "value": "$step"
+          }
+        "#;
+
+        let response = serde_json::from_str::<PropertyValueExpression>(json);
+        assert_eq!(
+            response.unwrap(),
+            PropertyValueExpression::IncrementNumberValue(Payload::Expression(Expression::Parameter { name: "step".to_string() }))
+        );
+    }
+
+    #[test]
+    fn deserialize_set_color_with_an_expression() {
+        let json = r#"
+          {
+            "type": "color",
+            "value": {
+              "type": "propertyValue",
+              "deviceId": "lamp",
+              "propertyId": "color"
+            }
+          }
+        "#;
+
+        let response = serde_json::from_str::<PropertyValueExpression>(json);
+        assert_eq!(
+            response.unwrap(),
+            PropertyValueExpression::SetColor(Payload::Expression(Expression::PropertyValue {
+                device_id: "lamp".to_string(),
+                property_id: "color".to_string(),
+            }))
+        );
+    }
+
+    #[test]
+    fn deserialize_fails_for_an_invalid_expression() {
+        let json = r#"
+          {
+            "type": "boolean",
+            "value": { "type": "unknown" }
+          }
+        "#;
+
+        let response = serde_json::from_str::<PropertyValueExpression>(json);
+        assert!(
+            response
+                .unwrap_err()
+                .to_string()
+                .starts_with("invalid expression for field 'value': unknown variant `unknown`")
+        );
     }
 
     #[rstest]
-    #[case::valid_hex("#000000", Ok(PropertyValue::SetColor(Color::Hex("#000000".to_string()))))]
+    #[case::valid_hex("#000000", Ok(PropertyValueExpression::SetColor(Payload::Literal(Color::Hex("#000000".to_string())))))]
     #[case::invalid_hex("#000", Err(Error::custom("invalid value: string \"#000\", expected a 6-digit hex color")))]
     #[case::invalid_hex("#00000Z", Err(Error::custom("invalid value: string \"#00000Z\", expected a 6-digit hex color")))]
-    fn deserialize_color_values(#[case] json_value: String, #[case] expected: serde_json::Result<PropertyValue>) {
+    fn deserialize_color_values(#[case] json_value: String, #[case] expected: serde_json::Result<PropertyValueExpression>) {
         let json = format!(
             r#"{{
                 "type": "color",
@@ -180,7 +280,7 @@ mod tests {
             json_value
         );
 
-        let response = serde_json::from_str::<PropertyValue>(&json);
+        let response = serde_json::from_str::<PropertyValueExpression>(&json);
 
         // As serde_json::Error does not implement PartialEq, use debug print for comparison
         assert_eq!(format!("{:#?}", response), format!("{:#?}", expected));
@@ -1,9 +1,12 @@
 use crate::domain::WeekdayCondition;
-use crate::flow_engine::Schedule;
+use crate::flow_engine::{DayCondition, Schedule, SolarEvent, TimeWindow};
+use chrono::NaiveDateTime;
+use humantime_serde::re::humantime;
 use serde::de::{Error, Unexpected};
 use serde::{Deserialize, Deserializer};
 use serde_json::Value;
 use std::str::FromStr;
+use std::time::Duration;
 
 impl<'de> Deserialize<'de> for Schedule {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
@@ -13,45 +16,140 @@ impl<'de> Deserialize<'de> for Schedule {
         let value = Value::deserialize(deserializer)?;
 
         match value {
-            Value::String(cron) => {
-                // Validate the cron expression
-                match cron::Schedule::from_str(&cron) {
-                    Ok(_) => Ok(Schedule::Cron(cron)),
-                    Err(_e) => Err(Error::invalid_value(Unexpected::Str(&cron), &"a valid cron expression")),
-                }
-            }
+            Value::String(expression) => cron(expression, Duration::ZERO),
             Value::Object(map) => {
-                let event = map.get("event").and_then(|v| v.as_str()).ok_or_else(|| Error::custom("missing or invalid field 'event'"))?;
-                let when_value = map.get("when");
-                let when = match when_value {
-                    Some(v) => WeekdayCondition::deserialize(v).map_err(Error::custom)?,
-                    None => WeekdayCondition::Any,
-                };
-                let offset = map.get("offset").and_then(|v| v.as_i64()).unwrap_or(0);
-
-                match event {
-                    "sunrise" => Ok(Schedule::Sunrise { when, offset }),
-                    "sunset" => Ok(Schedule::Sunset { when, offset }),
-                    _ => Err(Error::custom(format!("unknown schedule event '{}'", event))),
+                let jitter = jitter(&map)?;
+                if let Some(expression) = map.get("cron") {
+                    let expression = expression.as_str().ok_or_else(|| Error::custom("invalid field 'cron', expected a cron expression"))?;
+                    cron(expression.to_string(), jitter)
+                } else if map.contains_key("every") {
+                    interval(&map, jitter)
+                } else if map.contains_key("at") {
+                    at(&map, jitter)
+                } else {
+                    sun_event(&map, jitter)
                 }
             }
-            _ => Err(Error::custom("a string containing a cron expression or an object with 'event', 'when' and 'offset'")),
+            _ => Err(Error::custom(
+                "a string containing a cron expression or an object with 'cron', with 'event', 'when' and 'offset', with 'every' and 'window', or with 'at'",
+            )),
         }
     }
 }
 
+fn cron<E: Error>(expression: String, jitter: Duration) -> Result<Schedule, E> {
+    // Validate the cron expression
+    match cron::Schedule::from_str(&expression) {
+        Ok(_) => Ok(Schedule::Cron { expression, jitter }),
+        Err(_e) => Err(Error::invalid_value(Unexpected::Str(&expression), &"a valid cron expression")),
+    }
+}
+
+/// Deserializes the optional jitter of a schedule like `"±20m"`, the sign may be left out.
+fn jitter<E: Error>(map: &serde_json::Map<String, Value>) -> Result<Duration, E> {
+    let Some(jitter) = map.get("jitter") else {
+        return Ok(Duration::ZERO);
+    };
+
+    jitter
+        .as_str()
+        .map(|jitter| jitter.strip_prefix('±').unwrap_or(jitter))
+        .and_then(|jitter| humantime::parse_duration(jitter).ok())
+        .ok_or_else(|| Error::custom("invalid field 'jitter', expected a duration like '±20m'"))
+}
+
+/// Deserializes a sun event like `{ "event": "sunset", "when": "Mon-Fri", "offset": -15 }`.
+fn sun_event<E: Error>(map: &serde_json::Map<String, Value>, jitter: Duration) -> Result<Schedule, E> {
+    let event = map
+        .get("event")
+        .filter(|v| v.is_string() || v.is_object())
+        .ok_or_else(|| Error::custom("missing or invalid field 'event'"))?;
+    let when = match map.get("when") {
+        Some(Value::Object(when)) => day_condition(when)?,
+        Some(v) => WeekdayCondition::deserialize(v).map_err(Error::custom)?.into(),
+        None => WeekdayCondition::Any.into(),
+    };
+    let offset = map.get("offset").and_then(|v| v.as_i64()).unwrap_or(0);
+
+    let event = match event {
+        Value::String(name) => name.parse().map_err(|_| Error::custom(format!("unknown schedule event '{}'", name)))?,
+        _ => SolarEvent::deserialize(event).map_err(Error::custom)?,
+    };
+    Ok(Schedule::Sun { event, when, offset, jitter })
+}
+
+/// Deserializes a one-shot schedule like `{ "at": "2026-12-24T18:00" }`.
+fn at<E: Error>(map: &serde_json::Map<String, Value>, jitter: Duration) -> Result<Schedule, E> {
+    let at = map.get("at").and_then(|v| v.as_str()).unwrap_or_default();
+    ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(at, format).ok())
+        .map(|datetime| Schedule::At { datetime, jitter })
+        .ok_or_else(|| Error::custom("invalid field 'at', expected a local date and time like '2026-12-24T18:00'"))
+}
+
+/// Deserializes an interval like `{ "every": "15m", "window": { "from": "07:00", "to": "23:00" } }`, the window is optional.
+fn interval<E: Error>(map: &serde_json::Map<String, Value>, jitter: Duration) -> Result<Schedule, E> {
+    let every = map
+        .get("every")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| Error::custom("invalid field 'every', expected a duration like '15m'"))?;
+    let every = humantime::parse_duration(every).map_err(|e| Error::custom(format!("invalid field 'every': {}", e)))?;
+    if every.as_secs() < 1 {
+        return Err(Error::custom("invalid field 'every', the interval must be at least 1s"));
+    }
+
+    let window = match map.get("window") {
+        Some(window) => {
+            let window = TimeWindow::deserialize(window).map_err(|e| Error::custom(format!("invalid field 'window': {}", e)))?;
+            if window.from == window.to {
+                return Err(Error::custom("invalid field 'window', from and to must differ"));
+            }
+            Some(window)
+        }
+        None => None,
+    };
+
+    Ok(Schedule::Interval { every, window, jitter })
+}
+
+/// Deserializes a `when` like `{ "days": "weekdays", "include": ["vacation"], "exclude": ["holidays"] }`. Without days
+/// it is every day, or only the days of the included calendars if there are any.
+fn day_condition<E: Error>(when: &serde_json::Map<String, Value>) -> Result<DayCondition, E> {
+    let calendars = |field: &str| match when.get(field) {
+        Some(value) => Vec::<String>::deserialize(value).map_err(|e| Error::custom(format!("invalid field '{}': {}", field, e))),
+        None => Ok(Vec::new()),
+    };
+    let include = calendars("include")?;
+    let exclude = calendars("exclude")?;
+    let weekdays = match when.get("days") {
+        Some(days) => WeekdayCondition::deserialize(days).map_err(Error::custom)?,
+        None if include.is_empty() => WeekdayCondition::Any,
+        None => WeekdayCondition::Set(Vec::new()),
+    };
+
+    Ok(DayCondition { weekdays, include, exclude })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::domain::Time;
     use crate::domain::Weekday::*;
+    use crate::flow_engine::solar::Twilight;
+    use chrono::NaiveDate;
     use pretty_assertions::assert_eq;
     use rstest::rstest;
     use serde_json::json;
+    use std::time::Duration;
 
     #[test]
     fn deserializes_a_cron_expression() {
         let parsed: Schedule = serde_json::from_value(json!("0 12 * * * *")).unwrap();
-        let expected = Schedule::Cron("0 12 * * * *".to_string());
+        let expected = Schedule::Cron {
+            expression: "0 12 * * * *".to_string(),
+            jitter: Duration::ZERO,
+        };
         assert_eq!(parsed, expected);
     }
 
@@ -67,7 +165,7 @@ mod tests {
     #[rstest]
     #[case::with_cron_expression(
         json!("0 12 * * * *"),
-        Schedule::Cron("0 12 * * * *".to_string())
+        Schedule::Cron { expression: "0 12 * * * *".to_string(), jitter: Duration::ZERO }
     )]
     #[case::with_offset(
         json!({
@@ -75,7 +173,7 @@ mod tests {
             "when": "Wednesday",
             "offset": 5
         }),
-        Schedule::Sunrise { when: WeekdayCondition::Specific(Wednesday), offset: 5 }
+        Schedule::Sun { event: SolarEvent::Sunrise, when: WeekdayCondition::Specific(Wednesday).into(), offset: 5, jitter: Duration::ZERO }
     )]
     #[rstest]
     #[case::with_negative_offset(
@@ -84,20 +182,87 @@ mod tests {
             "when": "Wednesday",
             "offset": -5
         }),
-        Schedule::Sunrise { when: WeekdayCondition::Specific(Wednesday), offset: -5 }
+        Schedule::Sun { event: SolarEvent::Sunrise, when: WeekdayCondition::Specific(Wednesday).into(), offset: -5, jitter: Duration::ZERO }
     )]
     #[case::without_offset(
         json!({
             "event": "sunrise",
             "when": "Wednesday-Saturday"
         }),
-        Schedule::Sunrise { when: WeekdayCondition::Range { start: Wednesday, end: Saturday }, offset: 0 }
+        Schedule::Sun { event: SolarEvent::Sunrise, when: WeekdayCondition::Range { start: Wednesday, end: Saturday }.into(), offset: 0, jitter: Duration::ZERO }
     )]
     #[case::without_when(
         json!({
             "event": "sunrise"
         }),
-        Schedule::Sunrise { when: WeekdayCondition::Any, offset: 0 }
+        Schedule::Sun { event: SolarEvent::Sunrise, when: WeekdayCondition::Any.into(), offset: 0, jitter: Duration::ZERO }
+    )]
+    #[case::with_calendars(
+        json!({
+            "event": "sunset",
+            "when": { "days": "weekdays", "exclude": ["holidays"] }
+        }),
+        Schedule::Sun { event: SolarEvent::Sunset, when: DayCondition { weekdays: WeekdayCondition::Weekdays, include: vec![], exclude: vec!["holidays".to_string()] }, offset: 0, jitter: Duration::ZERO }
+    )]
+    #[case::with_only_included_calendars(
+        json!({
+            "event": "sunset",
+            "when": { "include": ["vacation"] }
+        }),
+        Schedule::Sun { event: SolarEvent::Sunset, when: DayCondition { weekdays: WeekdayCondition::Set(vec![]), include: vec!["vacation".to_string()], exclude: vec![] }, offset: 0, jitter: Duration::ZERO }
+    )]
+    #[case::with_twilight(
+        json!({
+            "event": "civilDusk",
+            "when": "weekend"
+        }),
+        Schedule::Sun { event: SolarEvent::Dusk(Twilight::Civil), when: WeekdayCondition::Weekend.into(), offset: 0, jitter: Duration::ZERO }
+    )]
+    #[case::with_elevation(
+        json!({
+            "event": { "elevation": -3.5, "direction": "setting" },
+            "offset": -10
+        }),
+        Schedule::Sun { event: SolarEvent::Elevation { degrees: -3.5, rising: false }, when: WeekdayCondition::Any.into(), offset: -10, jitter: Duration::ZERO }
+    )]
+    #[case::with_interval(
+        json!({
+            "every": "90s"
+        }),
+        Schedule::Interval { every: Duration::from_secs(90), window: None, jitter: Duration::ZERO }
+    )]
+    #[case::with_interval_in_window(
+        json!({
+            "every": "15m",
+            "window": { "from": "07:00", "to": "23:00" }
+        }),
+        Schedule::Interval { every: Duration::from_secs(900), window: Some(TimeWindow { from: Time { hour: 7, minute: 0, second: 0 }, to: Time { hour: 23, minute: 0, second: 0 } }), jitter: Duration::ZERO }
+    )]
+    #[case::at(
+        json!({
+            "at": "2026-12-24T18:00"
+        }),
+        Schedule::At { datetime: NaiveDate::from_ymd_opt(2026, 12, 24).unwrap().and_hms_opt(18, 0, 0).unwrap(), jitter: Duration::ZERO }
+    )]
+    #[case::at_with_seconds(
+        json!({
+            "at": "2026-12-24T18:00:30"
+        }),
+        Schedule::At { datetime: NaiveDate::from_ymd_opt(2026, 12, 24).unwrap().and_hms_opt(18, 0, 30).unwrap(), jitter: Duration::ZERO }
+    )]
+    #[case::cron_with_jitter(
+        json!({
+            "cron": "0 0 20 * * *",
+            "jitter": "±20m"
+        }),
+        Schedule::Cron { expression: "0 0 20 * * *".to_string(), jitter: Duration::from_secs(1200) }
+    )]
+    #[case::sun_event_with_jitter(
+        json!({
+            "event": "sunset",
+            "jitter": "10m"
+        }),
+        Schedule::Sun { event: SolarEvent::Sunset, when: WeekdayCondition::Any.into(), offset: 0, jitter: Duration::from_secs(600) }
     )]
     fn deserializes_valid_values(#[case] json: Value, #[case] expected: Schedule) {
         let parsed: Schedule = serde_json::from_value(json).unwrap();
@@ -125,9 +290,67 @@ mod tests {
         }),
         "expected a string like 'Monday', 'Mon-Fri'"
     )]
+    #[case::invalid_calendars(
+        json!({
+            "event": "sunrise",
+            "when": { "exclude": "holidays" }
+        }),
+        "invalid field 'exclude'"
+    )]
+    #[case::invalid_elevation(
+        json!({
+            "event": { "elevation": -3.5, "direction": "down" }
+        }),
+        "missing or invalid field 'direction'"
+    )]
+    #[case::invalid_interval(
+        json!({
+            "every": "often"
+        }),
+        "invalid field 'every'"
+    )]
+    #[case::too_short_interval(
+        json!({
+            "every": "500ms"
+        }),
+        "the interval must be at least 1s"
+    )]
+    #[case::invalid_window(
+        json!({
+            "every": "15m",
+            "window": { "from": "07:00" }
+        }),
+        "invalid field 'window'"
+    )]
+    #[case::empty_window(
+        json!({
+            "every": "15m",
+            "window": { "from": "07:00", "to": "07:00" }
+        }),
+        "from and to must differ"
+    )]
+    #[case::invalid_jitter(
+        json!({
+            "event": "sunset",
+            "jitter": "-20m"
+        }),
+        "invalid field 'jitter'"
+    )]
+    #[case::invalid_cron_field(
+        json!({
+            "cron": "0 0 20 * *"
+        }),
+        "expected a valid cron expression"
+    )]
+    #[case::invalid_at(
+        json!({
+            "at": "2026-12-24"
+        }),
+        "invalid field 'at'"
+    )]
     #[case::invalid_cron_expression(
         json!(42),
-        "a string containing a cron expression or an object with 'event', 'when' and 'offset'"
+        "a string containing a cron expression or an object with 'cron', with 'event', 'when' and 'offset', with 'every' and 'window', or with 'at'"
     )]
     fn deserialize_fails_for_invalid_cases(#[case] json: Value, #[case] expected_message: &str) {
         let parsed: Result<Schedule, _> = serde_json::from_value(json);
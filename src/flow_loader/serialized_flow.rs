@@ -1,17 +1,40 @@
 use crate::flow_engine::action::Action;
+use crate::flow_engine::expression::PropertyChangedExpression;
+use crate::flow_engine::flow::{FlowMode, TriggerMode};
 use crate::flow_engine::{Expression, Schedule, Value};
 use serde::Deserialize;
+use std::collections::HashMap;
 use std::time::Duration;
 
 #[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct SerializedFlow {
     pub(crate) id: String,
     pub(crate) name: String,
     pub(crate) schedule: Option<Schedule>,
-    pub(crate) trigger: Option<Expression>,
+    pub(crate) trigger: Option<Expression>,       // The condition of the schedule if there is one
+    pub(crate) trigger_mode: Option<TriggerMode>, // Defaults to edge for flows with a trigger on the store state, level otherwise
+    #[serde(default)]
+    pub(crate) triggers: Vec<SerializedTrigger>, // Next to the schedule and trigger above
+    pub(crate) condition: Option<Expression>,
+    #[serde(default)]
+    pub(crate) mode: FlowMode,
+    #[serde(default)]
+    pub(crate) call_only: bool, // The flow has no triggers, it only runs when another flow calls it
     pub(crate) nodes: Vec<SerializedFlowNode>,
 }
 
+/// One of the triggers of a flow, with exactly one of `schedule`, `propertyChanged` or `expression`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SerializedTrigger {
+    pub(crate) id: Option<String>, // Defaults to the kind of trigger
+    pub(crate) schedule: Option<Schedule>,
+    pub(crate) property_changed: Option<PropertyChangedExpression>,
+    pub(crate) expression: Option<Expression>,
+    pub(crate) trigger_mode: Option<TriggerMode>, // Of an expression, with the same default as the trigger of a flow
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "camelCase")]
 pub enum SerializedFlowNode {
@@ -20,6 +43,9 @@ pub enum SerializedFlowNode {
     ConditionalNode(SerializedConditionalFlowNode),
     ActionNode(SerializedActionFlowNode),
     SleepNode(SerializedSleepFlowNode),
+    ForkNode(SerializedForkFlowNode),
+    JoinNode(SerializedJoinFlowNode),
+    CallFlowNode(SerializedCallFlowNode),
 }
 
 impl SerializedFlowNode {
@@ -30,6 +56,9 @@ impl SerializedFlowNode {
             SerializedFlowNode::ConditionalNode(node) => &node.id,
             SerializedFlowNode::ActionNode(node) => &node.id,
             SerializedFlowNode::SleepNode(node) => &node.id,
+            SerializedFlowNode::ForkNode(node) => &node.id,
+            SerializedFlowNode::JoinNode(node) => &node.id,
+            SerializedFlowNode::CallFlowNode(node) => &node.id,
         }
     }
 
@@ -40,6 +69,9 @@ impl SerializedFlowNode {
             SerializedFlowNode::ConditionalNode(node) => node.outgoing_nodes.iter().collect(),
             SerializedFlowNode::ActionNode(node) => vec![&node.outgoing_node],
             SerializedFlowNode::SleepNode(node) => vec![&node.outgoing_node],
+            SerializedFlowNode::ForkNode(node) => node.outgoing_nodes.iter().collect(),
+            SerializedFlowNode::JoinNode(node) => vec![&node.outgoing_node],
+            SerializedFlowNode::CallFlowNode(node) => vec![&node.outgoing_node],
         }
     }
 }
@@ -85,6 +117,33 @@ pub struct SerializedSleepFlowNode {
     pub(crate) outgoing_node: SerializedFlowLink,
     #[serde(with = "humantime_serde")]
     pub(crate) duration: Duration,
+    #[serde(default)]
+    pub(crate) check_trigger: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub struct SerializedForkFlowNode {
+    pub(crate) id: String,
+    pub(crate) outgoing_nodes: Vec<SerializedFlowLink>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub struct SerializedJoinFlowNode {
+    pub(crate) id: String,
+    pub(crate) outgoing_node: SerializedFlowLink,
+    pub(crate) count: Option<usize>, // Defaults to all incoming branches
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub struct SerializedCallFlowNode {
+    pub(crate) id: String,
+    pub(crate) outgoing_node: SerializedFlowLink,
+    pub(crate) flow_id: String,
+    #[serde(default)]
+    pub(crate) parameters: HashMap<String, Expression>,
 }
 
 #[cfg(test)]
@@ -105,6 +164,11 @@ mod tests {
             name: "logFlow".to_string(),
             schedule: None,
             trigger: None,
+            trigger_mode: None,
+            triggers: vec![],
+            condition: None,
+            mode: FlowMode::default(),
+            call_only: false,
             nodes: vec![
                 SerializedFlowNode::StartNode(SerializedStartFlowNode {
                     id: "startNode".to_string(),
@@ -147,6 +211,15 @@ mod tests {
         assert_eq!(flow.schedule, None);
     }
 
+    #[tokio::test]
+    async fn test_serialized_flow_with_mode() {
+        let json = include_str!("../../tests/resources/flows/queuedFlow.json");
+
+        let flow = serde_json::from_str::<SerializedFlow>(json).unwrap();
+
+        assert_eq!(flow.mode, FlowMode::Queued { max: Some(3) });
+    }
+
     #[tokio::test]
     async fn test_serialized_flow_with_sleep_node() {
         let json = include_str!("../../tests/resources/flows/sleepFlow.json");
@@ -157,6 +230,11 @@ mod tests {
             name: "sleepFlow".to_string(),
             schedule: None,
             trigger: None,
+            trigger_mode: None,
+            triggers: vec![],
+            condition: None,
+            mode: FlowMode::default(),
+            call_only: false,
             nodes: vec![
                 SerializedFlowNode::StartNode(SerializedStartFlowNode {
                     id: "startNode".to_string(),
@@ -172,6 +250,7 @@ mod tests {
                         value: Value::None,
                     },
                     duration: Duration::from_secs(3907),
+                    check_trigger: false,
                 }),
                 SerializedFlowNode::EndNode(SerializedEndFlowNode { id: "endNode".to_string() }),
             ],
@@ -69,6 +69,7 @@ mod tests {
     #[rstest]
     #[case::with_boolean(json!(true), flow_engine::Value::Boolean(true))]
     #[case::with_number(json!(0.2), flow_engine::Value::Number(Number::Float(0.2)))]
+    #[case::with_string(json!("long_press"), flow_engine::Value::String("long_press".to_string()))]
     fn deserializes_a_link_with_value(#[case] value: Value, #[case] expected: flow_engine::Value) {
         let parsed = serde_json::from_value::<SerializedFlowLink>(json!({ "node": "node_id", "value": value })).unwrap();
         assert_eq!(
@@ -0,0 +1,68 @@
+use crate::flow_engine::SolarEvent;
+use serde::de::{Error, Unexpected};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Deserializes the name of an event like `"civilDusk"`, or an elevation like `{ "elevation": -3.5, "direction": "setting" }`.
+impl<'de> Deserialize<'de> for SolarEvent {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = Value::deserialize(deserializer)?;
+
+        match value {
+            Value::String(name) => name.parse().map_err(Error::custom),
+            Value::Object(map) => {
+                let degrees = map
+                    .get("elevation")
+                    .and_then(|v| v.as_f64())
+                    .filter(|degrees| (-90.0..=90.0).contains(degrees))
+                    .ok_or_else(|| Error::custom("missing or invalid field 'elevation', expected degrees from -90 to 90"))?;
+                let rising = match map.get("direction").and_then(|v| v.as_str()) {
+                    Some("rising") => true,
+                    Some("setting") => false,
+                    _ => return Err(Error::custom("missing or invalid field 'direction', expected 'rising' or 'setting'")),
+                };
+                Ok(SolarEvent::Elevation { degrees, rising })
+            }
+            _ => Err(Error::invalid_type(
+                Unexpected::Other(&value.to_string()),
+                &"the name of a solar event or an object with 'elevation' and 'direction'",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_engine::solar::Twilight;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::json;
+
+    #[rstest]
+    #[case::sunset(json!("sunset"), SolarEvent::Sunset)]
+    #[case::civil_dawn(json!("civilDawn"), SolarEvent::Dawn(Twilight::Civil))]
+    #[case::astronomical_dusk(json!("astronomicalDusk"), SolarEvent::Dusk(Twilight::Astronomical))]
+    #[case::golden_hour(json!("goldenHour"), SolarEvent::GoldenHour)]
+    #[case::setting_elevation(json!({ "elevation": -3.5, "direction": "setting" }), SolarEvent::Elevation { degrees: -3.5, rising: false })]
+    #[case::rising_elevation(json!({ "elevation": 10, "direction": "rising" }), SolarEvent::Elevation { degrees: 10.0, rising: true })]
+    fn deserializes_valid_values(#[case] json: Value, #[case] expected: SolarEvent) {
+        let parsed: SolarEvent = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, expected);
+    }
+
+    #[rstest]
+    #[case::unknown_event(json!("noon"), "unknown solar event 'noon'")]
+    #[case::missing_elevation(json!({ "direction": "rising" }), "missing or invalid field 'elevation'")]
+    #[case::elevation_out_of_range(json!({ "elevation": 95, "direction": "rising" }), "missing or invalid field 'elevation'")]
+    #[case::invalid_direction(json!({ "elevation": 10, "direction": "up" }), "missing or invalid field 'direction'")]
+    #[case::invalid_type(json!(42), "the name of a solar event or an object with 'elevation' and 'direction'")]
+    fn deserialize_fails_for_invalid_cases(#[case] json: Value, #[case] expected_message: &str) {
+        let err = serde_json::from_value::<SolarEvent>(json).expect_err("expected an error but got Ok");
+        let msg = err.to_string();
+        assert!(msg.contains(expected_message), "Expected error message to contain '{expected_message}', but got '{msg}'");
+    }
+}
@@ -10,8 +10,8 @@ impl<'de> Deserialize<'de> for Time {
         let value = String::deserialize(deserializer)?;
 
         let parts: Vec<&str> = value.split(':').collect();
-        if parts.len() != 2 {
-            return Err(Error::invalid_value(Unexpected::Str(&value), &"a time in HH:MM format"));
+        if parts.len() != 2 && parts.len() != 3 {
+            return Err(Error::invalid_value(Unexpected::Str(&value), &"a time in HH:MM or HH:MM:SS format"));
         }
 
         let hour: u8 = parts[0]
@@ -22,19 +22,26 @@ impl<'de> Deserialize<'de> for Time {
             return Err(Error::invalid_value(Unexpected::Str(parts[0]), &"a valid hour between 0 and 23"));
         }
 
-        if parts[1].len() != 2 {
-            return Err(Error::invalid_value(Unexpected::Str(parts[1]), &"a valid minute between 0 and 59"));
-        }
+        let minute = two_digits(parts[1], "a valid minute between 0 and 59")?;
+        let second = match parts.get(2) {
+            Some(second) => two_digits(second, "a valid second between 0 and 59")?,
+            None => 0,
+        };
 
-        let minute: u8 = parts[1]
-            .parse()
-            .map_err(|_| Error::invalid_value(Unexpected::Str(parts[0]), &"a valid minute between 0 and 59"))?;
+        Ok(Time { hour, minute, second })
+    }
+}
 
-        if minute > 59 {
-            return Err(Error::invalid_value(Unexpected::Str(parts[0]), &"a valid minute between 0 and 59"));
-        }
+/// Parses the minutes or seconds of a time, these always have two digits.
+fn two_digits<E: Error>(text: &str, expected: &str) -> Result<u8, E> {
+    let error = || Error::invalid_value(Unexpected::Str(text), &expected);
+    if text.len() != 2 {
+        return Err(error());
+    }
 
-        Ok(Time { hour, minute })
+    match text.parse() {
+        Ok(value) if value <= 59 => Ok(value),
+        _ => Err(error()),
     }
 }
 
@@ -45,10 +52,12 @@ mod tests {
     use serde_json::json;
 
     #[rstest]
-    #[case("0:00", Time { hour: 0, minute: 0 })]
-    #[case("00:00", Time { hour: 0, minute: 0 })]
-    #[case("20:00", Time { hour: 20, minute:0 })]
-    #[case("23:59", Time { hour: 23, minute: 59 })]
+    #[case("0:00", Time { hour: 0, minute: 0, second: 0 })]
+    #[case("00:00", Time { hour: 0, minute: 0, second: 0 })]
+    #[case("20:00", Time { hour: 20, minute:0, second: 0 })]
+    #[case("23:59", Time { hour: 23, minute: 59, second: 0 })]
+    #[case("23:59:00", Time { hour: 23, minute: 59, second: 0 })]
+    #[case("06:15:30", Time { hour: 6, minute: 15, second: 30 })]
     fn deserializes_valid_time(#[case] time: &str, #[case] expected: Time) {
         let result = serde_json::from_value::<Time>(json!(time)).unwrap();
         assert_eq!(result, expected);
@@ -62,8 +71,10 @@ mod tests {
     #[case::hour_too_large("24:00")]
     #[case::invalid_minutes("0:a")]
     #[case::minutes_too_large("0:60")]
-    #[case::minutes_too_large("23:59:00")]
     #[case::minutes_too_short("23:5")]
+    #[case::seconds_too_large("23:59:60")]
+    #[case::seconds_too_short("23:59:5")]
+    #[case::too_many_parts("23:59:00:00")]
     fn fails_for_an_invalid_time(#[case] time: &str) {
         let result = serde_json::from_value::<Time>(json!(time));
         assert!(result.is_err());
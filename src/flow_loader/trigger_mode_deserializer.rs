@@ -0,0 +1,44 @@
+use crate::flow_engine::flow::TriggerMode;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+
+impl<'de> Deserialize<'de> for TriggerMode {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = String::deserialize(deserializer)?;
+
+        match value.as_str() {
+            "edge" => Ok(TriggerMode::Edge),
+            "level" => Ok(TriggerMode::Level),
+            _ => Err(Error::custom(format!("unknown trigger mode '{}', expected edge or level", value))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use serde_json::{Value, json};
+
+    #[rstest]
+    #[case::edge(json!("edge"), TriggerMode::Edge)]
+    #[case::level(json!("level"), TriggerMode::Level)]
+    fn deserializes_valid_values(#[case] json: Value, #[case] expected: TriggerMode) {
+        let parsed: TriggerMode = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, expected);
+    }
+
+    #[rstest]
+    #[case::unknown_mode(json!("pulse"), "unknown trigger mode 'pulse', expected edge or level")]
+    #[case::invalid_type(json!(42), "invalid type")]
+    fn deserialize_fails_for_invalid_cases(#[case] json: Value, #[case] expected_message: &str) {
+        let parsed: Result<TriggerMode, _> = serde_json::from_value(json);
+        let err = parsed.expect_err("expected an error but got Ok");
+        let msg = err.to_string();
+        assert!(msg.contains(expected_message), "Expected error message to contain '{expected_message}', but got '{msg}'");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::domain::color::Color;
 use crate::flow_engine::Value;
 use serde::{Deserialize, Deserializer};
 
@@ -10,7 +11,9 @@ impl<'de> Deserialize<'de> for Value {
         match value {
             serde_json::Value::Bool(value) => Ok(Value::Boolean(value)),
             serde_json::Value::Number(value) => Ok(Value::Number((&value).into())),
-            _ => Err(serde::de::Error::custom("expected the value to be a boolean or a number")),
+            serde_json::Value::String(value) => Ok(Value::String(value)),
+            serde_json::Value::Object(_) => Color::deserialize(value).map(Value::Color).map_err(serde::de::Error::custom),
+            _ => Err(serde::de::Error::custom("expected the value to be a boolean, a number, a string or a color")),
         }
     }
 }
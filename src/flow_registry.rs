@@ -1,29 +1,285 @@
 use crate::flow_engine::flow::Flow;
-use std::collections::HashMap;
+use crate::flow_engine::{FlowRuns, TriggerStates};
+use crate::history::PropertyHistory;
+use crate::store::PropertyChange;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::Duration;
 
+#[derive(Debug)]
 pub struct FlowRegistry {
     flows: Vec<Arc<Flow>>,
     by_id: HashMap<String, usize>,
+    by_property: HashMap<(String, String), Vec<usize>>, // Reactive flows by the properties their expression triggers depend on
+    independent: Vec<usize>,                            // Reactive flows with an expression trigger that does not depend on any specific property
+    by_timed_property: HashMap<(String, String), Vec<(usize, Duration)>>, // Reactive flows to evaluate again some time after a property changed
+    runs: FlowRuns,
+    trigger_states: TriggerStates,
+    history: Option<Arc<PropertyHistory>>, // For the expressions of the flows, if the history is enabled
 }
 
 impl FlowRegistry {
     pub fn new(flows: Vec<Flow>) -> Self {
         let by_id = flows.iter().enumerate().map(|(index, flow)| (flow.id().to_string(), index)).collect();
+
+        let mut by_property: HashMap<(String, String), Vec<usize>> = HashMap::new();
+        let mut independent = Vec::new();
+        let mut by_timed_property: HashMap<(String, String), Vec<(usize, Duration)>> = HashMap::new();
+        for (index, flow) in flows.iter().enumerate() {
+            let mut is_independent = false;
+            let mut flow_dependencies = HashSet::new();
+            let mut timed_dependencies = HashSet::new();
+            for (_, expression, _) in flow.trigger_expressions() {
+                let dependencies = expression.property_dependencies();
+                // The devices of a group are only known when the trigger is evaluated, so it is evaluated on every change
+                is_independent |= dependencies.is_empty() || expression.depends_on_device_groups();
+                flow_dependencies.extend(dependencies);
+                timed_dependencies.extend(expression.timed_dependencies());
+            }
+
+            if is_independent {
+                independent.push(index);
+            }
+            for dependency in flow_dependencies {
+                by_property.entry(dependency).or_default().push(index);
+            }
+            for (device_id, property_id, duration) in timed_dependencies {
+                by_timed_property.entry((device_id, property_id)).or_default().push((index, duration));
+            }
+        }
+
         let flow_arcs = flows.into_iter().map(|flow| Arc::new(flow)).collect();
 
-        Self { flows: flow_arcs, by_id }
+        Self {
+            flows: flow_arcs,
+            by_id,
+            by_property,
+            independent,
+            by_timed_property,
+            runs: FlowRuns::new(),
+            trigger_states: TriggerStates::new(),
+            history: None,
+        }
+    }
+
+    pub fn with_history(mut self, history: Arc<PropertyHistory>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Returns the reactive flows affected by the given changes, these are the flows with an expression trigger that
+    /// depends on a changed property or that does not depend on any specific property.
+    pub fn reactive_flows_for(&self, changes: &[PropertyChange]) -> Vec<Arc<Flow>> {
+        let affected: BTreeSet<usize> = changes
+            .iter()
+            .filter_map(|change| self.by_property.get(&(change.device_id.clone(), change.property_id.clone())))
+            .flatten()
+            .chain(self.independent.iter())
+            .copied()
+            .collect();
+
+        affected.into_iter().map(|index| self.flows[index].clone()).collect()
     }
 
-    pub fn reactive_flows(&self) -> Vec<Arc<Flow>> {
-        self.flows.iter().filter(|flow| flow.schedule().is_none()).cloned().collect()
+    /// Returns the reactive flows with a trigger that changes with the passing of time after the given changes, with the
+    /// delay after which to evaluate the trigger again. A flow is returned once per distinct delay.
+    pub fn timed_reevaluations(&self, changes: &[PropertyChange]) -> Vec<(Arc<Flow>, Duration)> {
+        let reevaluations: BTreeSet<(usize, Duration)> = changes
+            .iter()
+            .filter_map(|change| self.by_timed_property.get(&(change.device_id.clone(), change.property_id.clone())))
+            .flatten()
+            .copied()
+            .collect();
+
+        reevaluations.into_iter().map(|(index, delay)| (self.flows[index].clone(), delay)).collect()
     }
 
+    /// Returns the flows with at least one schedule.
     pub fn scheduled_flows(&self) -> Vec<Arc<Flow>> {
-        self.flows.iter().filter(|flow| flow.schedule().is_some()).cloned().collect()
+        self.flows.iter().filter(|flow| flow.schedules().next().is_some()).cloned().collect()
     }
 
     pub fn by_id(&self, id: &str) -> Option<Arc<Flow>> {
         self.by_id.get(id).map(|&index| &self.flows[index]).cloned()
     }
+
+    pub fn runs(&self) -> &FlowRuns {
+        &self.runs
+    }
+
+    pub fn trigger_states(&self) -> &TriggerStates {
+        &self.trigger_states
+    }
+
+    pub fn history(&self) -> Option<&PropertyHistory> {
+        self.history.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flow_engine::expression::{AggregateExpression, AggregateFunction, DeviceSelector, PropertyChangedExpression, PropertyDurationExpression};
+    use crate::flow_engine::flow::{FlowNode, FlowNodeKind, Trigger, TriggerKind, TriggerMode};
+    use crate::flow_engine::{Expression, Schedule, Value};
+    use pretty_assertions::assert_eq;
+
+    fn flow(id: &str, trigger: Option<Expression>) -> Flow {
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start));
+        let flow = Flow::new(id.to_string(), id.to_string(), start_node, HashMap::new()).unwrap();
+        match trigger {
+            Some(expression) => flow.with_triggers(vec![expression_trigger("expression", expression)]),
+            None => flow,
+        }
+    }
+
+    fn expression_trigger(id: &str, expression: Expression) -> Trigger {
+        Trigger::new(
+            id.to_string(),
+            TriggerKind::Expression {
+                expression,
+                mode: TriggerMode::Level,
+            },
+        )
+    }
+
+    fn schedule_trigger(id: &str) -> Trigger {
+        Trigger::new(
+            id.to_string(),
+            TriggerKind::Schedule(Schedule::Cron {
+                expression: "0 0 7 * * *".to_string(),
+                jitter: Duration::ZERO,
+            }),
+        )
+    }
+
+    fn property_value(device_id: &str, property_id: &str) -> Expression {
+        Expression::PropertyValue {
+            device_id: device_id.to_string(),
+            property_id: property_id.to_string(),
+        }
+    }
+
+    fn change(device_id: &str, property_id: &str) -> PropertyChange {
+        PropertyChange {
+            device_id: device_id.to_string(),
+            property_id: property_id.to_string(),
+            previous_value: Value::Boolean(false),
+            value: Value::Boolean(true),
+        }
+    }
+
+    #[test]
+    fn reactive_flows_for_returns_the_flows_depending_on_the_changed_properties() {
+        let registry = FlowRegistry::new(vec![
+            flow(
+                "switchFlow",
+                Some(Expression::PropertyChanged(PropertyChangedExpression {
+                    device_id: "switch".to_string(),
+                    property_id: "on".to_string(),
+                    from: None,
+                    to: None,
+                })),
+            ),
+            flow(
+                "lampFlow",
+                Some(Expression::PropertyValue {
+                    device_id: "lamp".to_string(),
+                    property_id: "on".to_string(),
+                }),
+            ),
+            flow("independentFlow", None),
+        ]);
+
+        let flow_ids = |changes: &[PropertyChange]| registry.reactive_flows_for(changes).iter().map(|flow| flow.id().to_string()).collect::<Vec<_>>();
+
+        assert_eq!(flow_ids(&[change("switch", "on")]), vec!["switchFlow", "independentFlow"]);
+        assert_eq!(flow_ids(&[change("lamp", "on"), change("switch", "on")]), vec!["switchFlow", "lampFlow", "independentFlow"]);
+        assert_eq!(flow_ids(&[change("lamp", "brightness")]), vec!["independentFlow"]);
+    }
+
+    #[test]
+    fn reactive_flows_for_skips_flows_without_triggers() {
+        let call_only_flow = flow("callOnlyFlow", None).with_triggers(vec![]);
+        let registry = FlowRegistry::new(vec![call_only_flow, flow("independentFlow", None)]);
+
+        let flow_ids = registry
+            .reactive_flows_for(&[change("lamp", "on")])
+            .iter()
+            .map(|flow| flow.id().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(flow_ids, vec!["independentFlow"]);
+        assert!(registry.scheduled_flows().is_empty());
+    }
+
+    #[test]
+    fn reactive_flows_for_returns_the_flows_depending_on_a_group_of_devices_for_any_change() {
+        let registry = FlowRegistry::new(vec![flow(
+            "groupFlow",
+            Some(Expression::Aggregate(AggregateExpression {
+                function: AggregateFunction::Any,
+                devices: DeviceSelector {
+                    room: Some("Living room".to_string()),
+                    ..DeviceSelector::default()
+                },
+                property_id: "on".to_string(),
+            })),
+        )]);
+
+        let flow_ids = registry
+            .reactive_flows_for(&[change("lamp", "on")])
+            .iter()
+            .map(|flow| flow.id().to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(flow_ids, vec!["groupFlow"]);
+    }
+
+    #[test]
+    fn timed_reevaluations_returns_the_flows_with_a_trigger_on_the_time_since_the_changed_property_changed() {
+        let unchanged_for = |duration: Duration| {
+            Expression::UnchangedFor(PropertyDurationExpression {
+                device_id: "lamp".to_string(),
+                property_id: "on".to_string(),
+                duration,
+            })
+        };
+        let registry = FlowRegistry::new(vec![
+            flow("lampFlow", Some(unchanged_for(Duration::from_secs(7200)))),
+            flow("otherFlow", Some(property_value("lamp", "on"))),
+        ]);
+
+        let reevaluations = registry
+            .timed_reevaluations(&[change("lamp", "on"), change("switch", "on")])
+            .into_iter()
+            .map(|(flow, delay)| (flow.id().to_string(), delay))
+            .collect::<Vec<_>>();
+
+        assert_eq!(reevaluations, vec![("lampFlow".to_string(), Duration::from_secs(7200))]);
+        assert!(registry.timed_reevaluations(&[change("lamp", "brightness")]).is_empty());
+    }
+
+    #[test]
+    fn flows_with_schedules_and_expression_triggers_are_both_scheduled_and_reactive() {
+        let start_node = || Arc::new(FlowNode::new("startNode".to_string(), vec![], FlowNodeKind::Start));
+        let mixed_flow = Flow::new("mixedFlow".to_string(), "mixedFlow".to_string(), start_node(), HashMap::new())
+            .unwrap()
+            .with_triggers(vec![
+                schedule_trigger("morning"),
+                expression_trigger("door", property_value("door", "open")),
+                expression_trigger("motion", property_value("hallway", "motion")),
+            ]);
+        let scheduled_flow = Flow::new("scheduledFlow".to_string(), "scheduledFlow".to_string(), start_node(), HashMap::new())
+            .unwrap()
+            .with_triggers(vec![schedule_trigger("morning")]);
+        let registry = FlowRegistry::new(vec![mixed_flow, scheduled_flow]);
+
+        let flow_ids = |flows: Vec<Arc<Flow>>| flows.iter().map(|flow| flow.id().to_string()).collect::<Vec<_>>();
+
+        assert_eq!(flow_ids(registry.scheduled_flows()), vec!["mixedFlow", "scheduledFlow"]);
+        assert_eq!(flow_ids(registry.reactive_flows_for(&[change("door", "open")])), vec!["mixedFlow"]);
+        assert_eq!(flow_ids(registry.reactive_flows_for(&[change("hallway", "motion")])), vec!["mixedFlow"]);
+        assert!(registry.reactive_flows_for(&[change("lamp", "on")]).is_empty());
+    }
 }
@@ -0,0 +1,231 @@
+use crate::flow_engine::Value;
+use crate::history::property_history::{HistoryEntry, PropertyHistory};
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::io::{ErrorKind, SeekFrom};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tokio::fs;
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tracing::{instrument, warn};
+
+/// A line of the history log.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct SerializedHistoryRecord {
+    timestamp: i64, // Milliseconds since the Unix epoch
+    device_id: String,
+    property_id: String,
+    value: Option<Value>, // None is written as null
+}
+
+/// The on-disk history, a file with one JSON record per line. Changes are appended to it, the whole file is only
+/// rewritten when the history is compacted.
+#[derive(Debug)]
+pub struct HistoryLog {
+    path: PathBuf,
+    file: File,
+}
+
+impl HistoryLog {
+    /// Opens the log for appending. A line that was partially written when hearth stopped is ended first, so the next
+    /// record starts on a line of its own.
+    pub async fn open(path: &Path) -> Result<Self, HistoryError> {
+        let mut file = open_for_append(path).await?;
+        end_partial_line(path, &mut file)
+            .await
+            .map_err(|e| HistoryError::Io { source: e, path: path.to_path_buf() })?;
+        Ok(HistoryLog { path: path.to_path_buf(), file })
+    }
+
+    /// Adds the entries of the log to the history. Lines that cannot be read, like a line that was partially written
+    /// when hearth stopped, are skipped. Returns the number of entries that were added.
+    #[instrument(skip(history))]
+    pub async fn load(path: &Path, history: &PropertyHistory) -> Result<usize, HistoryError> {
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(0),
+            Err(err) => {
+                return Err(HistoryError::Io {
+                    source: err,
+                    path: path.to_path_buf(),
+                });
+            }
+        };
+
+        let mut loaded = 0;
+        for (line_number, line) in content.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+            let record = match serde_json::from_str::<SerializedHistoryRecord>(line) {
+                Ok(record) => record,
+                Err(err) => {
+                    warn!("⚠️ Skipping line {} of the history log: {}", line_number + 1, err);
+                    continue;
+                }
+            };
+            let Some(timestamp) = Local.timestamp_millis_opt(record.timestamp).single() else {
+                warn!("⚠️ Skipping line {} of the history log: invalid timestamp", line_number + 1);
+                continue;
+            };
+
+            let entry = HistoryEntry {
+                timestamp,
+                value: record.value.unwrap_or(Value::None),
+            };
+            history.record(&record.device_id, &record.property_id, entry);
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+
+    pub async fn append(&mut self, device_id: &str, property_id: &str, entry: &HistoryEntry) -> Result<(), HistoryError> {
+        let line = to_line(device_id, property_id, entry)?;
+        self.file.write_all(line.as_bytes()).await.map_err(|e| self.io_error(e))?;
+        self.file.flush().await.map_err(|e| self.io_error(e))
+    }
+
+    /// Replaces the content of the log by the history, after it has been compacted.
+    #[instrument(skip_all)]
+    pub async fn rewrite(&mut self, history: &PropertyHistory) -> Result<(), HistoryError> {
+        let mut content = String::new();
+        for (device_id, property_id, entries) in history.entries() {
+            for entry in entries {
+                content.push_str(&to_line(&device_id, &property_id, &entry)?);
+            }
+        }
+
+        let temp_path = self.path.with_extension("tmp");
+        fs::write(&temp_path, content).await.map_err(|e| HistoryError::Io { source: e, path: temp_path.clone() })?;
+        fs::rename(&temp_path, &self.path).await.map_err(|e| self.io_error(e))?;
+        self.file = open_for_append(&self.path).await?;
+        Ok(())
+    }
+
+    fn io_error(&self, source: io::Error) -> HistoryError {
+        HistoryError::Io { source, path: self.path.clone() }
+    }
+}
+
+async fn open_for_append(path: &Path) -> Result<File, HistoryError> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(|e| HistoryError::Io { source: e, path: path.to_path_buf() })
+}
+
+async fn end_partial_line(path: &Path, file: &mut File) -> io::Result<()> {
+    if file.metadata().await?.len() == 0 {
+        return Ok(());
+    }
+
+    let mut reader = File::open(path).await?;
+    reader.seek(SeekFrom::End(-1)).await?;
+    let mut last_byte = [0u8];
+    reader.read_exact(&mut last_byte).await?;
+    if last_byte[0] != b'\n' {
+        file.write_all(b"\n").await?;
+        file.flush().await?;
+    }
+    Ok(())
+}
+
+fn to_line(device_id: &str, property_id: &str, entry: &HistoryEntry) -> Result<String, HistoryError> {
+    let record = SerializedHistoryRecord {
+        timestamp: entry.timestamp.timestamp_millis(),
+        device_id: device_id.to_string(),
+        property_id: property_id.to_string(),
+        value: Some(entry.value.clone()),
+    };
+    Ok(format!("{}\n", serde_json::to_string(&record)?))
+}
+
+#[derive(Error, Debug)]
+pub enum HistoryError {
+    #[error("unable to access '{}': {}", path.display(), source)]
+    Io { source: io::Error, path: PathBuf },
+    #[error(transparent)]
+    Serialization(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Number;
+    use crate::history::property_history::HistoryRetention;
+    use chrono::{DateTime, Duration as TimeDelta};
+    use pretty_assertions::assert_eq;
+    use std::env::temp_dir;
+    use std::time::Duration;
+
+    fn history() -> PropertyHistory {
+        PropertyHistory::new(HistoryRetention {
+            retention: Duration::from_secs(24 * 3600),
+            downsample_after: Duration::from_secs(24 * 3600),
+            downsample_interval: Duration::from_secs(3600),
+        })
+    }
+
+    fn entry(timestamp: DateTime<Local>, value: Value) -> HistoryEntry {
+        HistoryEntry { timestamp, value }
+    }
+
+    #[tokio::test]
+    async fn loads_the_appended_entries() -> Result<(), HistoryError> {
+        let directory = temp_dir().join("hearth_history_log");
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("history.jsonl");
+        let now = Local.timestamp_millis_opt(Local::now().timestamp_millis()).unwrap();
+        let entries = vec![
+            entry(now - TimeDelta::minutes(2), Value::Boolean(true)),
+            entry(now - TimeDelta::minutes(1), Value::Number(Number::Float(58.89))),
+            entry(now, Value::None),
+        ];
+
+        let mut log = HistoryLog::open(&path).await?;
+        for entry in &entries {
+            log.append("hallway", "on", entry).await?;
+        }
+        fs::write(&path, format!("{}{{ \"timestamp\": ", fs::read_to_string(&path).await.unwrap())).await.unwrap();
+        let after_restart = entry(now, Value::Boolean(false));
+        HistoryLog::open(&path).await?.append("kitchen", "on", &after_restart).await?;
+
+        let history = history();
+        let loaded = HistoryLog::load(&path, &history).await;
+        fs::remove_dir_all(&directory).await.unwrap();
+
+        assert_eq!(loaded?, 4);
+        assert_eq!(history.query("hallway", "on", now - TimeDelta::hours(1), now), entries);
+        assert_eq!(history.query("kitchen", "on", now, now), vec![after_restart]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn rewrites_the_log_with_the_history() -> Result<(), HistoryError> {
+        let directory = temp_dir().join("hearth_history_log_rewrite");
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("history.jsonl");
+        let now = Local.timestamp_millis_opt(Local::now().timestamp_millis()).unwrap();
+
+        let mut log = HistoryLog::open(&path).await?;
+        log.append("hallway", "on", &entry(now, Value::Boolean(false))).await?;
+        let history = history();
+        history.record("kitchen", "on", entry(now, Value::Boolean(true)));
+        log.rewrite(&history).await?;
+        log.append("kitchen", "on", &entry(now, Value::Boolean(false))).await?;
+
+        let reloaded = self::history();
+        let loaded = HistoryLog::load(&path, &reloaded).await;
+        fs::remove_dir_all(&directory).await.unwrap();
+
+        assert_eq!(loaded?, 2);
+        assert_eq!(reloaded.query("hallway", "on", now, now), vec![]);
+        assert_eq!(
+            reloaded.query("kitchen", "on", now, now),
+            vec![entry(now, Value::Boolean(true)), entry(now, Value::Boolean(false))]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+mod history_log;
+mod property_history;
+mod recorder;
+
+pub use history_log::HistoryLog;
+#[cfg(test)]
+pub use property_history::HistoryEntry;
+pub use property_history::{HistoryRetention, PropertyHistory};
+pub use recorder::record_history;
@@ -0,0 +1,194 @@
+use crate::flow_engine::Value;
+use chrono::{DateTime, Local, TimeDelta};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use std::time::Duration;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub value: Value,
+}
+
+/// Determines how long the history of a property is kept, and how detailed.
+#[derive(Clone, Debug)]
+pub struct HistoryRetention {
+    pub retention: Duration,           // Entries older than this are removed
+    pub downsample_after: Duration,    // Entries older than this are downsampled
+    pub downsample_interval: Duration, // Downsampled entries keep only the last entry of every interval
+}
+
+/// The values of every device property over time, ordered by timestamp.
+#[derive(Debug)]
+pub struct PropertyHistory {
+    series: RwLock<HashMap<(String, String), Vec<HistoryEntry>>>,
+    retention: HistoryRetention,
+}
+
+impl PropertyHistory {
+    pub fn new(retention: HistoryRetention) -> Self {
+        PropertyHistory {
+            series: RwLock::new(HashMap::new()),
+            retention,
+        }
+    }
+
+    pub fn record(&self, device_id: &str, property_id: &str, entry: HistoryEntry) {
+        let mut series = self.series.write().unwrap();
+        let entries = series.entry((device_id.to_string(), property_id.to_string())).or_default();
+        let index = entries.partition_point(|existing| existing.timestamp <= entry.timestamp);
+        entries.insert(index, entry);
+    }
+
+    /// Returns the entries of the property between `from` and `to`, both inclusive. The entry before `from` is
+    /// included as well, as it holds the value of the property at `from`.
+    pub fn query(&self, device_id: &str, property_id: &str, from: DateTime<Local>, to: DateTime<Local>) -> Vec<HistoryEntry> {
+        let series = self.series.read().unwrap();
+        let Some(entries) = series.get(&(device_id.to_string(), property_id.to_string())) else {
+            return Vec::new();
+        };
+
+        let start = entries.partition_point(|entry| entry.timestamp < from).saturating_sub(1);
+        let end = entries.partition_point(|entry| entry.timestamp <= to);
+        entries.get(start..end).map(<[HistoryEntry]>::to_vec).unwrap_or_default()
+    }
+
+    /// Returns all entries, grouped by device and property id.
+    pub fn entries(&self) -> Vec<(String, String, Vec<HistoryEntry>)> {
+        let series = self.series.read().unwrap();
+        series
+            .iter()
+            .map(|((device_id, property_id), entries)| (device_id.clone(), property_id.clone(), entries.clone()))
+            .collect()
+    }
+
+    /// Applies the retention, relative to `now`. The last entry of a property is always kept, as it holds the current
+    /// value of the property. Returns whether any entries were removed.
+    pub fn compact(&self, now: DateTime<Local>) -> bool {
+        let remove_before = before(now, self.retention.retention);
+        let downsample_before = before(now, self.retention.downsample_after);
+        let interval_ms = (self.retention.downsample_interval.as_millis() as i64).max(1);
+
+        let mut removed_any = false;
+        let mut series = self.series.write().unwrap();
+        for entries in series.values_mut() {
+            let len = entries.len();
+            if let Some(remove_before) = remove_before {
+                let removed = entries.partition_point(|entry| entry.timestamp < remove_before).min(entries.len().saturating_sub(1));
+                entries.drain(..removed);
+            }
+
+            // Keeps an entry that is downsampled if the next entry falls in another interval
+            let mut compacted: Vec<HistoryEntry> = Vec::with_capacity(entries.len());
+            for entry in entries.drain(..) {
+                if let Some(previous) = compacted.last()
+                    && downsample_before.is_some_and(|downsample_before| entry.timestamp < downsample_before)
+                    && previous.timestamp.timestamp_millis().div_euclid(interval_ms) == entry.timestamp.timestamp_millis().div_euclid(interval_ms)
+                {
+                    compacted.pop();
+                }
+                compacted.push(entry);
+            }
+            removed_any |= compacted.len() < len;
+            *entries = compacted;
+        }
+        removed_any
+    }
+}
+
+/// Returns the moment the duration before `now`, or `None` if that is out of range.
+fn before(now: DateTime<Local>, duration: Duration) -> Option<DateTime<Local>> {
+    TimeDelta::from_std(duration).ok().and_then(|duration| now.checked_sub_signed(duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use pretty_assertions::assert_eq;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2025, 10, 16, hour, minute, 0).unwrap()
+    }
+
+    fn entry(hour: u32, minute: u32, value: bool) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: at(hour, minute),
+            value: Value::Boolean(value),
+        }
+    }
+
+    fn history(retention_hours: u64, downsample_after_hours: u64) -> PropertyHistory {
+        PropertyHistory::new(HistoryRetention {
+            retention: Duration::from_secs(retention_hours * 3600),
+            downsample_after: Duration::from_secs(downsample_after_hours * 3600),
+            downsample_interval: Duration::from_secs(3600),
+        })
+    }
+
+    fn timestamps(entries: Vec<HistoryEntry>) -> Vec<DateTime<Local>> {
+        entries.into_iter().map(|entry| entry.timestamp).collect()
+    }
+
+    #[test]
+    fn query_returns_the_entries_in_the_range_and_the_entry_before_it() {
+        let history = history(24, 24);
+        history.record("hallway", "on", entry(8, 0, true));
+        history.record("hallway", "on", entry(10, 0, true));
+        history.record("hallway", "on", entry(9, 0, false));
+        history.record("hallway", "on", entry(11, 0, false));
+        history.record("kitchen", "on", entry(9, 30, true));
+
+        let entries = history.query("hallway", "on", at(9, 30), at(10, 30));
+
+        assert_eq!(entries, vec![entry(9, 0, false), entry(10, 0, true)]);
+    }
+
+    #[test]
+    fn query_returns_nothing_for_an_unknown_property() {
+        let history = history(24, 24);
+
+        assert_eq!(history.query("hallway", "on", at(9, 0), at(10, 0)), vec![]);
+    }
+
+    #[test]
+    fn compact_removes_the_entries_older_than_the_retention_except_the_last_one() {
+        let history = history(2, 2);
+        history.record("hallway", "on", entry(8, 0, true));
+        history.record("hallway", "on", entry(11, 0, false));
+        history.record("kitchen", "on", entry(8, 0, true));
+
+        assert!(history.compact(at(12, 0)));
+
+        assert_eq!(timestamps(history.query("hallway", "on", at(0, 0), at(23, 0))), vec![at(11, 0)]);
+        assert_eq!(timestamps(history.query("kitchen", "on", at(0, 0), at(23, 0))), vec![at(8, 0)]);
+    }
+
+    #[test]
+    fn compact_keeps_the_last_entry_per_interval_of_old_entries() {
+        let history = history(24, 2);
+        history.record("hallway", "on", entry(8, 10, true));
+        history.record("hallway", "on", entry(8, 20, false));
+        history.record("hallway", "on", entry(8, 30, true));
+        history.record("hallway", "on", entry(9, 10, false));
+        history.record("hallway", "on", entry(11, 10, true));
+        history.record("hallway", "on", entry(11, 20, false));
+
+        assert!(history.compact(at(12, 0)));
+
+        assert_eq!(
+            timestamps(history.query("hallway", "on", at(0, 0), at(23, 0))),
+            vec![at(8, 30), at(9, 10), at(11, 10), at(11, 20)]
+        );
+    }
+
+    #[test]
+    fn compact_reports_that_nothing_was_removed_if_all_entries_are_within_the_retention() {
+        let history = history(24, 24);
+        history.record("hallway", "on", entry(8, 0, true));
+        history.record("hallway", "on", entry(11, 0, false));
+
+        assert!(!history.compact(at(12, 0)));
+        assert_eq!(timestamps(history.query("hallway", "on", at(0, 0), at(23, 0))), vec![at(8, 0), at(11, 0)]);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::history::history_log::HistoryLog;
+use crate::history::property_history::{HistoryEntry, PropertyHistory};
+use crate::store::{StoreChange, StoreChangeKind};
+use chrono::Local;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::Receiver;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{MissedTickBehavior, interval};
+use tracing::{debug, instrument, warn};
+
+/// Records every property change of the store in the history and its log, and compacts the history at every interval.
+/// The log is only rewritten when compacting removed entries.
+#[instrument(skip_all)]
+pub async fn record_history(mut changes_rx: Receiver<StoreChange>, history: Arc<PropertyHistory>, mut log: HistoryLog, compact_every: Duration) {
+    let mut ticks = interval(compact_every);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            change = changes_rx.recv() => match change {
+                Ok(StoreChange { kind: StoreChangeKind::PropertyChanged(change), timestamp, .. }) => {
+                    let entry = HistoryEntry { timestamp, value: change.value };
+                    if let Err(err) = log.append(&change.device_id, &change.property_id, &entry).await {
+                        warn!("⚠️ Unable to write the history log: {}", err);
+                    }
+                    history.record(&change.device_id, &change.property_id, entry);
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => warn!("⚠️ Missed {} store change(s), the history is incomplete", skipped),
+                Err(RecvError::Closed) => return,
+            },
+            _ = ticks.tick() => {
+                debug!("📈 Compacting history...");
+                if !history.compact(Local::now()) {
+                    debug!("📈 Compacting history... OK, nothing to remove");
+                    continue;
+                }
+                match log.rewrite(&history).await {
+                    Ok(()) => debug!("📈 Compacting history... OK"),
+                    Err(err) => warn!("⚠️ Compacting history... failed: {}", err),
+                }
+            }
+        }
+    }
+}
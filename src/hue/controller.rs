@@ -85,10 +85,10 @@ impl Controller for HueController {
                             property
                                 .get(color_temperature_property.name())
                                 .and_then(|pv| match pv {
-                                    PropertyValue::SetNumberValue(value) => value.as_u64(),
+                                    PropertyValue::SetNumberValue(value) => Some(value.round()), // Computed values can be fractional
                                     _ => None,
                                 })
-                                .and_then(|color_temperature| match color_temperature_property.validate_value(Number::PositiveInt(color_temperature)) {
+                                .and_then(|color_temperature| match color_temperature_property.validate_value(color_temperature) {
                                     ValidatedValue::Valid(value) => value.as_u64(),
                                     ValidatedValue::Clamped(value, PropertyError::ValueTooSmall) => {
                                         #[rustfmt::skip]
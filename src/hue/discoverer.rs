@@ -1,6 +1,6 @@
 use crate::app_config::AppConfig;
 use crate::domain::device::Device;
-use crate::hue::domain::{DeviceGet, HueResponse, LightGet};
+use crate::hue::domain::{DeviceGet, HueResponse, LightGet, RoomGet};
 use crate::hue::map_lights::map_lights;
 use reqwest::{Client, StatusCode};
 use std::collections::HashMap;
@@ -33,15 +33,45 @@ pub async fn discover(client: &Client, config: &AppConfig) -> Result<Vec<Device>
     info!("Retrieving lights... OK, {} found", light_response.data.len());
 
     let mut device_map = hue_response.data.into_iter().map(|device| (device.id.clone(), device)).collect();
-    let devices = map_lights(light_response.data, &mut device_map).unwrap();
+    let mut devices = map_lights(light_response.data, &mut device_map).unwrap();
 
     if !device_map.is_empty() {
         log_unmapped_devices(&device_map);
     }
 
+    // Rooms are optional, the devices are still usable without them
+    match get_rooms(client, hue_url).await {
+        Ok(rooms) => assign_rooms(&mut devices, rooms),
+        Err(err) => warn!("⚠️ Retrieving rooms... failed, devices have no room: {}", err),
+    }
+
     Ok(devices)
 }
 
+async fn get_rooms(client: &Client, hue_url: &str) -> Result<Vec<RoomGet>, DiscoverError> {
+    let response = client
+        .get(format!("{}/clip/v2/resource/room", hue_url))
+        .send()
+        .await?
+        .error_for_status()
+        .map_err(to_discover_error)?;
+
+    let room_response = response.json::<HueResponse<RoomGet>>().await?;
+    info!("Retrieving rooms... OK, {} found", room_response.data.len());
+    Ok(room_response.data)
+}
+
+fn assign_rooms(devices: &mut [Device], rooms: Vec<RoomGet>) {
+    let room_by_device: HashMap<String, String> = rooms
+        .into_iter()
+        .flat_map(|room| room.children.into_iter().map(move |child| (child.rid, room.metadata.name.clone())))
+        .collect();
+
+    for device in devices {
+        device.room = room_by_device.get(&device.id).cloned();
+    }
+}
+
 fn to_discover_error(e: reqwest::Error) -> DiscoverError {
     if let (Some(status), Some(url)) = (e.status(), e.url()) {
         DiscoverError::UnexpectedResponse(status, url.to_string())
@@ -99,6 +129,14 @@ mod tests {
             .create_async()
             .await;
 
+        server
+            .mock("GET", "/clip/v2/resource/room")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(include_str!("../../tests/resources/hue_room_response.json"))
+            .create_async()
+            .await;
+
         let app_config = AppConfigBuilder::new().hue_url(server.url()).build();
         let client = new_client(&app_config).unwrap();
 
@@ -127,6 +165,8 @@ mod tests {
                 external_id: None,
                 address: None,
                 controller_id: Some("hue"),
+                room: Some("Living room".to_string()),
+                tags: Vec::new(),
             }
         );
 
@@ -156,4 +196,37 @@ mod tests {
         mock.assert();
         Ok(())
     }
+
+    #[tokio::test]
+    async fn discover_returns_devices_without_a_room_if_the_rooms_are_unavailable() -> Result<(), DiscoverError> {
+        let mut server = mockito::Server::new_async().await;
+
+        server
+            .mock("GET", "/clip/v2/resource/device")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(include_str!("../../tests/resources/hue_device_response.json"))
+            .create_async()
+            .await;
+
+        server
+            .mock("GET", "/clip/v2/resource/light")
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(include_str!("../../tests/resources/hue_light_simplified_response.json"))
+            .create_async()
+            .await;
+
+        let mock = server.mock("GET", "/clip/v2/resource/room").with_status(500).create_async().await;
+
+        let app_config = AppConfigBuilder::new().hue_url(server.url()).build();
+        let client = new_client(&app_config).unwrap();
+
+        let response = discover(&client, &app_config).await?;
+
+        mock.assert();
+        assert_eq!(response.len(), 1);
+        assert_eq!(response[0].room, None);
+        Ok(())
+    }
 }
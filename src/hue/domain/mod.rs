@@ -1,9 +1,11 @@
 mod device_get;
 mod hue_response;
 mod light_get;
+mod room_get;
 mod sse_payload;
 
 pub(super) use device_get::*;
 pub(super) use hue_response::*;
 pub(super) use light_get::*;
+pub(super) use room_get::*;
 pub(super) use sse_payload::*;
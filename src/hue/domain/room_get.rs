@@ -0,0 +1,16 @@
+use crate::hue::domain::hue_response::Owner;
+use serde::Deserialize;
+
+// API: https://developers.meethue.com/develop/hue-api-v2/api-reference/#resource_room_get
+#[derive(Debug, Deserialize)]
+pub struct RoomGet {
+    pub children: Vec<Owner>, // The devices in the room
+    pub metadata: RoomMetadata,
+}
+
+#[allow(dead_code)]
+#[derive(Debug, Deserialize)]
+pub struct RoomMetadata {
+    pub name: String,
+    pub archetype: String,
+}
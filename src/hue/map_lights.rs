@@ -71,6 +71,8 @@ pub fn map_lights(lights: Vec<LightGet>, device_map: &mut HashMap<String, Device
                 external_id: None,
                 address: None,
                 controller_id: Some(CONTROLLER_ID),
+                room: None,
+                tags: Vec::new(),
             })
         })
         .collect()
@@ -170,6 +172,8 @@ mod tests {
                 external_id: None,
                 address: None,
                 controller_id: Some("hue"),
+                room: None,
+                tags: Vec::new(),
             }
         );
 
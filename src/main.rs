@@ -3,14 +3,17 @@ use crate::domain::controller_registry;
 use crate::domain::events::Event;
 use crate::flow_engine::{SchedulerCommand, scheduler};
 use crate::flow_registry::FlowRegistry;
+use crate::history::{HistoryLog, PropertyHistory};
 use crate::store::Store;
 use crate::store_listener::store_listener;
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
 use tokio::{signal, task};
-use tracing::{error, info, trace};
+use tracing::{error, info, trace, warn};
 
 mod app_config;
+mod calendar_loader;
 mod domain;
 mod execute_flows;
 mod extensions;
@@ -18,12 +21,16 @@ mod flow_engine;
 mod flow_loader;
 mod flow_registry;
 mod geo_location_deserializer;
+mod history;
 mod hue;
+mod persistence;
 mod property_changed_reducer;
 mod sse;
 mod store;
 mod store_listener;
 
+const HISTORY_COMPACT_INTERVAL: Duration = Duration::from_secs(3600);
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt().with_max_level(tracing::Level::INFO).init();
@@ -34,19 +41,28 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     info!("✅  Loaded configuration");
 
     let flows = flow_loader::load_flows_from(config.flows().directory(), "json").await.unwrap_or_else(|_| Vec::new()); // Errors are already logged in the function
-    let flow_registry = Arc::new(FlowRegistry::new(flows));
+    let history = config.history().map(|history_config| Arc::new(PropertyHistory::new(history_config.retention())));
+    let mut flow_registry = FlowRegistry::new(flows);
+    if let Some(history) = &history {
+        flow_registry = flow_registry.with_history(history.clone());
+    }
+    let flow_registry = Arc::new(flow_registry);
     info!("✅  Loaded flows");
 
     let (tx, rx) = mpsc::channel::<Event>(config.core().store_buffer_size());
     let mut store = Store::new(rx);
 
+    let calendars = Arc::new(calendar_loader::load_calendars(config.calendars()).await);
+    info!("✅  Loaded calendars");
+
     let (scheduler_tx, scheduler_rx) = mpsc::channel::<SchedulerCommand>(32);
     let scheduler_tx_clone = scheduler_tx.clone();
     let store_rx = store.notifier();
     let registry_clone = flow_registry.clone();
     let geo_location_clone = config.geo_location().clone();
+    let calendars_clone = calendars.clone();
     task::spawn(async move {
-        scheduler(scheduler_tx_clone, scheduler_rx, store_rx, registry_clone, geo_location_clone).await;
+        scheduler(scheduler_tx_clone, scheduler_rx, store_rx, registry_clone, geo_location_clone, calendars_clone).await;
     });
     info!("✅  Started scheduler");
 
@@ -59,15 +75,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     info!("✅  Scheduled flows");
 
+    let (reply, reply_rx) = oneshot::channel();
+    scheduler_tx.send(SchedulerCommand::ListUpcoming { count: 1, reply }).await?;
+    for (flow_id, times) in reply_rx.await? {
+        if let (Some(flow), Some(time)) = (flow_registry.by_id(&flow_id), times.first()) {
+            info!("🕗 Next run of flow '{}' at {}", flow.name(), time);
+        }
+    }
+
     let hue_client = hue::new_client(&config)?;
     let hue_controller = hue::Controller::new(hue_client.clone(), config.clone());
     controller_registry::register(Arc::new(hue_controller));
     info!("✅  Initialized controllers");
 
+    let mut store_persistence = None;
+    // Restored after the controllers are registered, so the restored devices can be tied to their controller
+    if let Some(store_file) = config.core().store_file() {
+        match persistence::load_devices(store_file).await {
+            Ok(devices) => {
+                info!("✅  Restored {} device(s)", devices.len());
+                store.restore(devices);
+            }
+            Err(err) => warn!("⚠️ Unable to restore the store: {}", err),
+        }
+
+        let store_rx = store.notifier();
+        let store_file = store_file.to_path_buf();
+        let persist_interval = config.core().store_persist_interval_ms();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let handle = task::spawn(async move {
+            persistence::persist_store(store_rx, store_file, persist_interval, shutdown_rx).await;
+        });
+        store_persistence = Some((shutdown_tx, handle));
+        info!("✅  Started store persistence");
+    }
+
+    if let (Some(history_config), Some(history)) = (config.history(), history) {
+        match HistoryLog::load(history_config.file(), &history).await {
+            Ok(loaded) => info!("✅  Loaded {} history entries", loaded),
+            Err(err) => warn!("⚠️ Unable to load the history: {}", err),
+        }
+
+        match HistoryLog::open(history_config.file()).await {
+            Ok(log) => {
+                let changes_rx = store.changes();
+                task::spawn(async move {
+                    history::record_history(changes_rx, history, log, HISTORY_COMPACT_INTERVAL).await;
+                });
+                info!("✅  Started history recording");
+            }
+            Err(err) => warn!("⚠️ Unable to record the history: {}", err),
+        }
+    }
+
+    let changes_rx = store.changes();
     let store_rx = store.notifier();
     let geo_location = config.geo_location().clone();
     task::spawn(async move {
-        store_listener(store_rx, flow_registry, scheduler_tx, geo_location).await;
+        store_listener(changes_rx, store_rx, flow_registry, scheduler_tx, geo_location, calendars).await;
     });
     info!("✅  Initialized store listener");
 
@@ -76,7 +141,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     });
     info!("✅  Initialized store");
 
-    let hue_devices = hue::discover(&hue_client, &config).await.expect("Could not discover Hue devices");
+    let mut hue_devices = hue::discover(&hue_client, &config).await.expect("Could not discover Hue devices");
+    for device in &mut hue_devices {
+        device.tags = config.tags_of(&device.id);
+    }
     trace!("Observed Hue devices: {:?}", &hue_devices);
     tx.send(Event::DiscoveredDevices(hue_devices))
         .await
@@ -94,5 +162,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
 
+    if let Some((shutdown_tx, handle)) = store_persistence {
+        let _ = shutdown_tx.send(());
+        if let Err(err) = handle.await {
+            error!("Unable to persist the store: {}", err);
+        }
+    }
+
     Ok(())
 }
@@ -0,0 +1,52 @@
+use crate::domain::color::Color;
+use serde::ser::{Error, SerializeStruct};
+use serde::{Serialize, Serializer};
+
+/// Serializes a color as an object, a hex color is written as RGB so it is not read back as a string.
+impl Serialize for Color {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Color::CIE_xyY { xy, brightness } => {
+                let mut state = serializer.serialize_struct("Color", 3)?;
+                state.serialize_field("x", &xy.x())?;
+                state.serialize_field("y", &xy.y())?;
+                state.serialize_field("brightness", brightness)?;
+                state.end()
+            }
+            Color::RGB(..) | Color::Hex(_) => {
+                let Color::RGB(r, g, b) = self.clone().to_rgb().map_err(Error::custom)? else {
+                    return Err(Error::custom("unable to convert the color to RGB"));
+                };
+                let mut state = serializer.serialize_struct("Color", 3)?;
+                state.serialize_field("r", &r)?;
+                state.serialize_field("g", &g)?;
+                state.serialize_field("b", &b)?;
+                state.end()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::property::CartesianCoordinate;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::rgb(Color::RGB(255, 180, 107), r#"{"r":255,"g":180,"b":107}"#, Color::RGB(255, 180, 107))]
+    #[case::hex(Color::Hex("#ffb46b".to_string()), r#"{"r":255,"g":180,"b":107}"#, Color::RGB(255, 180, 107))]
+    #[case::cie_xyy(
+        Color::CIE_xyY { xy: CartesianCoordinate::new(0.4851, 0.4331), brightness: 1.0 },
+        r#"{"x":0.4851,"y":0.4331,"brightness":1.0}"#,
+        Color::CIE_xyY { xy: CartesianCoordinate::new(0.4851, 0.4331), brightness: 1.0 }
+    )]
+    fn serialize_round_trip(#[case] color: Color, #[case] expected: &str, #[case] deserialized: Color) {
+        let json = serde_json::to_string(&color).unwrap();
+        assert_eq!(json, expected);
+        assert_eq!(serde_json::from_str::<Color>(&json).unwrap(), deserialized);
+    }
+}
@@ -0,0 +1,7 @@
+mod color_serializer;
+mod number_serializer;
+mod serialized_store;
+mod store_file;
+mod value_serializer;
+
+pub use store_file::{load_devices, persist_store};
@@ -0,0 +1,31 @@
+use crate::domain::Number;
+use serde::{Serialize, Serializer};
+
+impl Serialize for Number {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Number::PositiveInt(value) => serializer.serialize_u64(*value),
+            Number::NegativeInt(value) => serializer.serialize_i64(*value),
+            Number::Float(value) => serializer.serialize_f64(*value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::positive_int(Number::PositiveInt(42), "42")]
+    #[case::negative_int(Number::NegativeInt(-42), "-42")]
+    #[case::float(Number::Float(42.5), "42.5")]
+    fn serialize_round_trip(#[case] number: Number, #[case] expected: &str) {
+        let json = serde_json::to_string(&number).unwrap();
+        assert_eq!(json, expected);
+        assert_eq!(serde_json::from_str::<Number>(&json).unwrap(), number);
+    }
+}
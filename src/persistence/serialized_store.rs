@@ -0,0 +1,206 @@
+use crate::domain::controller_registry;
+use crate::domain::device::{Device, DeviceType};
+use crate::domain::property::{BooleanProperty, ColorProperty, NumberProperty, Property};
+use crate::store::DeviceMap;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::warn;
+
+/// The persisted state of the store.
+#[derive(Serialize, Deserialize, Debug)]
+pub(in crate::persistence) struct SerializedStore {
+    pub(in crate::persistence) devices: Vec<SerializedDevice>,
+}
+
+#[derive(PartialEq, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub(in crate::persistence) struct SerializedDevice {
+    id: String,
+    r#type: DeviceType,
+    manufacturer: String,
+    model_id: String,
+    product_name: String,
+    name: String,
+    properties: HashMap<String, SerializedProperty>,
+    external_id: Option<String>,
+    address: Option<String>,
+    controller_id: Option<String>,
+    room: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(PartialEq, Serialize, Deserialize, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+enum SerializedProperty {
+    Boolean(BooleanProperty),
+    Number(NumberProperty),
+    Color(ColorProperty),
+}
+
+impl From<&DeviceMap> for SerializedStore {
+    fn from(devices: &DeviceMap) -> Self {
+        let mut devices: Vec<_> = devices.values().map(|device| SerializedDevice::from(device.as_ref())).collect();
+        devices.sort_by(|a, b| a.id.cmp(&b.id));
+        SerializedStore { devices }
+    }
+}
+
+impl From<SerializedStore> for DeviceMap {
+    fn from(store: SerializedStore) -> Self {
+        store.devices.into_iter().map(|device| (device.id.clone(), Arc::new(device.into()))).collect()
+    }
+}
+
+impl From<&Device> for SerializedDevice {
+    fn from(device: &Device) -> Self {
+        let properties = device
+            .properties
+            .iter()
+            .filter_map(|(property_id, property)| {
+                let serialized_property = SerializedProperty::from_property(property.as_ref());
+                if serialized_property.is_none() {
+                    warn!(device_id = device.id, "⚠️ Unable to persist property '{}' of device '{}'", property_id, device.name);
+                }
+                serialized_property.map(|serialized_property| (property_id.clone(), serialized_property))
+            })
+            .collect();
+
+        SerializedDevice {
+            id: device.id.clone(),
+            r#type: device.r#type.clone(),
+            manufacturer: device.manufacturer.clone(),
+            model_id: device.model_id.clone(),
+            product_name: device.product_name.clone(),
+            name: device.name.clone(),
+            properties,
+            external_id: device.external_id.clone(),
+            address: device.address.clone(),
+            controller_id: device.controller_id.map(str::to_string),
+            room: device.room.clone(),
+            tags: device.tags.clone(),
+        }
+    }
+}
+
+impl From<SerializedDevice> for Device {
+    /// The controller of the device must be registered already, the device is not tied to a controller otherwise.
+    fn from(device: SerializedDevice) -> Self {
+        let controller_id = device.controller_id.and_then(|controller_id| {
+            let controller = controller_registry::get(&controller_id);
+            if controller.is_none() {
+                warn!(device_id = device.id, "⚠️ Unknown controller '{}' for device '{}'", controller_id, device.name);
+            }
+            controller.map(|controller| controller.id())
+        });
+
+        Device {
+            id: device.id,
+            r#type: device.r#type,
+            manufacturer: device.manufacturer,
+            model_id: device.model_id,
+            product_name: device.product_name,
+            name: device.name,
+            properties: device
+                .properties
+                .into_iter()
+                .map(|(property_id, property)| (property_id, property.into_property()))
+                .collect(),
+            external_id: device.external_id,
+            address: device.address,
+            controller_id,
+            room: device.room,
+            tags: device.tags,
+        }
+    }
+}
+
+impl SerializedProperty {
+    fn from_property(property: &dyn Property) -> Option<Self> {
+        let property = property.as_any();
+        if let Some(property) = property.downcast_ref::<BooleanProperty>() {
+            Some(SerializedProperty::Boolean(property.clone()))
+        } else if let Some(property) = property.downcast_ref::<NumberProperty>() {
+            Some(SerializedProperty::Number(property.clone()))
+        } else {
+            property.downcast_ref::<ColorProperty>().map(|property| SerializedProperty::Color(property.clone()))
+        }
+    }
+
+    fn into_property(self) -> Box<dyn Property> {
+        match self {
+            SerializedProperty::Boolean(property) => Box::new(property),
+            SerializedProperty::Number(property) => Box::new(property),
+            SerializedProperty::Color(property) => Box::new(property),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::property::{CartesianCoordinate, Gamut, PropertyType, Unit};
+    use pretty_assertions::assert_eq;
+
+    fn device(controller_id: Option<&'static str>) -> Device {
+        let on_property: Box<dyn Property> = Box::new(BooleanProperty::new("on".to_string(), PropertyType::On, false, Some("on_id".to_string()), true));
+        let brightness_property: Box<dyn Property> = Box::new(
+            NumberProperty::builder("brightness".to_string(), PropertyType::Brightness, false)
+                .unit(Unit::Percentage)
+                .float(58.89, Some(0.0), Some(100.0))
+                .build(),
+        );
+        let color_property: Box<dyn Property> = Box::new(ColorProperty::new(
+            "color".to_string(),
+            PropertyType::Color,
+            false,
+            None,
+            CartesianCoordinate::new(0.4573, 0.41),
+            Some(Gamut::new(
+                CartesianCoordinate::new(0.6915, 0.3083),
+                CartesianCoordinate::new(0.17, 0.7),
+                CartesianCoordinate::new(0.1532, 0.0475),
+            )),
+        ));
+
+        Device {
+            id: "ab917a9a-a7d5-4853-9518-75909236a182".to_string(),
+            r#type: DeviceType::Light,
+            manufacturer: "Signify Netherlands B.V.".to_string(),
+            model_id: "LCT007".to_string(),
+            product_name: "Hue color lamp".to_string(),
+            name: "Lamp".to_string(),
+            properties: HashMap::from([
+                ("on".to_string(), on_property),
+                ("brightness".to_string(), brightness_property),
+                ("color".to_string(), color_property),
+            ]),
+            external_id: Some("external_id".to_string()),
+            address: None,
+            controller_id,
+            room: Some("Living room".to_string()),
+            tags: vec!["downstairs".to_string()],
+        }
+    }
+
+    #[test]
+    fn restores_a_serialized_device() {
+        let devices: DeviceMap = HashMap::from([("ab917a9a-a7d5-4853-9518-75909236a182".to_string(), Arc::new(device(None)))]);
+
+        let json = serde_json::to_string(&SerializedStore::from(&devices)).unwrap();
+        let restored: DeviceMap = serde_json::from_str::<SerializedStore>(&json).unwrap().into();
+
+        assert_eq!(restored, devices);
+    }
+
+    #[test]
+    fn restores_a_device_without_a_controller_if_the_controller_is_unknown() {
+        let serialized_device = SerializedDevice::from(&device(Some("unknown")));
+        assert_eq!(serialized_device.controller_id, Some("unknown".to_string()));
+
+        let restored = Device::from(serialized_device);
+
+        assert_eq!(restored.controller_id, None);
+    }
+}
@@ -0,0 +1,175 @@
+use crate::persistence::serialized_store::SerializedStore;
+use crate::store::{DeviceMap, StoreSnapshot};
+use std::io;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::fs;
+use tokio::sync::oneshot;
+use tokio::sync::watch::Receiver;
+use tokio::time::{MissedTickBehavior, interval};
+use tracing::{debug, info, instrument, warn};
+
+/// Loads the devices persisted in the store file, returns no devices if there is no store file yet.
+#[instrument]
+pub async fn load_devices(path: &Path) -> Result<DeviceMap, PersistenceError> {
+    let content = match fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(DeviceMap::new()),
+        Err(err) => {
+            return Err(PersistenceError::Io {
+                source: err,
+                path: path.to_path_buf(),
+            });
+        }
+    };
+
+    let store: SerializedStore = serde_json::from_str(&content).map_err(|e| PersistenceError::Serialization { source: e, path: path.to_path_buf() })?;
+    Ok(store.into())
+}
+
+/// Writes the devices to the store file. The devices are written to a temporary file first, so an interrupted write
+/// never leaves a corrupt store file behind.
+#[instrument(skip(devices))]
+pub async fn save_devices(path: &Path, devices: &DeviceMap) -> Result<(), PersistenceError> {
+    let content = serde_json::to_string_pretty(&SerializedStore::from(devices)).map_err(|e| PersistenceError::Serialization { source: e, path: path.to_path_buf() })?;
+
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, content)
+        .await
+        .map_err(|e| PersistenceError::Io { source: e, path: temp_path.clone() })?;
+    fs::rename(&temp_path, path).await.map_err(|e| PersistenceError::Io { source: e, path: path.to_path_buf() })
+}
+
+/// Writes the devices of the store to the store file at every interval, if they changed since the last write. Writes
+/// the last changes once more when the shutdown signal is received, before returning.
+#[instrument(skip(notifier_rx, shutdown_rx))]
+pub async fn persist_store(mut notifier_rx: Receiver<StoreSnapshot>, path: PathBuf, every: Duration, mut shutdown_rx: oneshot::Receiver<()>) {
+    let mut ticks = interval(every);
+    ticks.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            _ = ticks.tick() => {
+                if !persist_changes(&mut notifier_rx, &path).await {
+                    return;
+                }
+            }
+            _ = &mut shutdown_rx => {
+                persist_changes(&mut notifier_rx, &path).await;
+                return;
+            }
+        }
+    }
+}
+
+/// Writes the devices to the store file if they changed since the last write, returns false once the store is gone.
+async fn persist_changes(notifier_rx: &mut Receiver<StoreSnapshot>, path: &Path) -> bool {
+    match notifier_rx.has_changed() {
+        Ok(true) => {}
+        Ok(false) => return true,
+        Err(_) => return false,
+    }
+
+    let devices = notifier_rx.borrow_and_update().devices.clone();
+    debug!("💾 Persisting {} device(s)...", devices.len());
+    match save_devices(path, &devices).await {
+        Ok(()) => info!("💾 Persisting {} device(s)... OK", devices.len()),
+        Err(err) => warn!("⚠️ Persisting {} device(s)... failed: {}", devices.len(), err),
+    }
+    true
+}
+
+#[derive(Error, Debug)]
+pub enum PersistenceError {
+    #[error("unable to access '{}': {}", path.display(), source)]
+    Io { source: io::Error, path: PathBuf },
+    #[error("invalid store file '{}': {}", path.display(), source)]
+    Serialization { source: serde_json::Error, path: PathBuf },
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::device::{Device, DeviceType};
+    use crate::domain::property::{BooleanProperty, Property, PropertyType};
+    use std::collections::HashMap;
+    use std::env::temp_dir;
+    use std::sync::Arc;
+    use tokio::sync::watch;
+
+    fn devices() -> DeviceMap {
+        let on_property: Box<dyn Property> = Box::new(BooleanProperty::new("on".to_string(), PropertyType::On, false, None, true));
+        let device = Device {
+            id: "device".to_string(),
+            r#type: DeviceType::Light,
+            manufacturer: "Signify Netherlands B.V.".to_string(),
+            model_id: "LWA004".to_string(),
+            product_name: "Hue filament bulb".to_string(),
+            name: "Woonkamer".to_string(),
+            properties: HashMap::from([("on".to_string(), on_property)]),
+            external_id: None,
+            address: None,
+            controller_id: None,
+            room: None,
+            tags: Vec::new(),
+        };
+
+        HashMap::from([(device.id.clone(), Arc::new(device))])
+    }
+
+    #[tokio::test]
+    async fn loads_the_saved_devices() -> Result<(), PersistenceError> {
+        let directory = temp_dir().join("hearth_store_file");
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("store.json");
+
+        save_devices(&path, &devices()).await?;
+        let loaded = load_devices(&path).await;
+        fs::remove_dir_all(&directory).await.unwrap();
+
+        assert_eq!(loaded?, devices());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn persists_the_last_changes_on_shutdown() -> Result<(), PersistenceError> {
+        let directory = temp_dir().join("hearth_store_file_shutdown");
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("store.json");
+        let (notifier_tx, notifier_rx) = watch::channel(StoreSnapshot::default());
+        let (shutdown_tx, shutdown_rx) = oneshot::channel();
+        let persistence = tokio::spawn(persist_store(notifier_rx, path.clone(), Duration::from_secs(3600), shutdown_rx));
+
+        notifier_tx.send_modify(|snapshot| snapshot.devices = Arc::new(devices()));
+        shutdown_tx.send(()).unwrap();
+        persistence.await.unwrap();
+        let loaded = load_devices(&path).await;
+        fs::remove_dir_all(&directory).await.unwrap();
+
+        assert_eq!(loaded?, devices());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn loads_no_devices_if_there_is_no_store_file() -> Result<(), PersistenceError> {
+        let devices = load_devices(&temp_dir().join("hearth_missing_store.json")).await?;
+
+        assert!(devices.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn returns_an_error_for_an_invalid_store_file() {
+        let directory = temp_dir().join("hearth_invalid_store_file");
+        fs::create_dir_all(&directory).await.unwrap();
+        let path = directory.join("store.json");
+        fs::write(&path, "{ \"devices\": 42 }").await.unwrap();
+
+        let result = load_devices(&path).await;
+        fs::remove_dir_all(&directory).await.unwrap();
+
+        assert!(matches!(result, Err(PersistenceError::Serialization { .. })));
+    }
+}
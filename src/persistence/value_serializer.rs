@@ -0,0 +1,35 @@
+use crate::flow_engine::Value;
+use serde::{Serialize, Serializer};
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Boolean(value) => serializer.serialize_bool(*value),
+            Value::Number(number) => number.serialize(serializer),
+            Value::String(value) => serializer.serialize_str(value),
+            Value::Color(color) => color.serialize(serializer),
+            Value::None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::Number;
+    use crate::domain::color::Color;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case::boolean(Value::Boolean(true), "true")]
+    #[case::number(Value::Number(Number::Float(58.89)), "58.89")]
+    #[case::color(Value::Color(Color::RGB(255, 180, 107)), r#"{"r":255,"g":180,"b":107}"#)]
+    #[case::string(Value::String("long_press".to_string()), "\"long_press\"")]
+    #[case::none(Value::None, "null")]
+    fn serialize(#[case] value: Value, #[case] expected: &str) {
+        assert_eq!(serde_json::to_string(&value).unwrap(), expected);
+    }
+}
@@ -1,15 +1,18 @@
 use crate::domain::property::{Property, PropertyError};
-use crate::store::DeviceMap;
+use crate::flow_engine::Value;
+use crate::store::{DeviceMap, PropertyChange};
 use std::any::type_name;
 use std::sync::Arc;
 use thiserror::Error;
-use tracing::{info, warn};
+use tracing::{debug, info, warn};
 
+/// Sets the value of a device property, replacing the device in the map. Returns `None` if the value did not change, the
+/// map is left untouched then.
 #[inline(always)]
-pub(crate) fn reduce_property_changed_event<F, T>(devices: &mut DeviceMap, device_id: &str, property_id: &str, set_value: F) -> Result<(), ReducerError>
+pub(crate) fn reduce_property_changed_event<F, T>(devices: &mut DeviceMap, device_id: &str, property_id: &str, set_value: F) -> Result<Option<PropertyChange>, ReducerError>
 where
     F: FnOnce(&mut T) -> Result<(), PropertyError>,
-    T: Property + 'static,
+    T: Property + Clone + PartialEq + 'static,
 {
     let mut new_device = if let Some(device) = devices.get(device_id) {
         device.as_ref().clone()
@@ -27,6 +30,7 @@ where
     };
 
     let previous_value = property.value_string();
+    let previous = Value::try_from(property.as_ref()).unwrap_or(Value::None);
     let Some(downcast_property) = property.as_any_mut().downcast_mut::<T>() else {
         warn!(device_id, "⚠️ Expected '{}' property for property '{}'", type_name::<T>(), &property_id);
         return Err(ReducerError::IncorrectPropertyType {
@@ -36,11 +40,17 @@ where
         });
     };
 
+    let unchanged_property = downcast_property.clone();
     if let Err(err) = set_value(downcast_property) {
         warn!(device_id = new_device.id, "⚠️ Could not set value for property '{}': {}", property_id, err);
         return Err(ReducerError::PropertyChangedError(err));
     }
 
+    if *downcast_property == unchanged_property {
+        debug!(device_id, "⚪ Value of '{}' for device '{}' did not change", property_id, &new_device.name);
+        return Ok(None);
+    }
+
     info!(
         device_id,
         "🟢 Updated device '{}', set '{}' to '{}', was '{}'",
@@ -50,9 +60,15 @@ where
         previous_value
     );
 
+    let change = PropertyChange {
+        device_id: device_id.to_string(),
+        property_id: property_id.to_string(),
+        previous_value: previous,
+        value: Value::try_from(property.as_ref()).unwrap_or(Value::None),
+    };
     devices.insert(device_id.to_string(), Arc::new(new_device));
 
-    Ok(()) // return new_device and insert in store?
+    Ok(Some(change))
 }
 
 #[derive(Error, PartialEq, Debug)]
@@ -98,6 +114,8 @@ mod tests {
             external_id: None,
             address: None,
             controller_id: None,
+            room: None,
+            tags: Vec::new(),
         };
 
         HashMap::from([(DEVICE_ID.to_string(), Arc::new(device))])
@@ -143,6 +161,33 @@ mod tests {
         );
     }
 
+    #[test]
+    fn reduce_returns_the_property_change() {
+        let mut devices = create_devices();
+        let result = reduce_property_changed_event(&mut devices, DEVICE_ID, "on", |property: &mut BooleanProperty| property.set_value(true));
+
+        assert_eq!(
+            result,
+            Ok(Some(PropertyChange {
+                device_id: DEVICE_ID.to_string(),
+                property_id: "on".to_string(),
+                previous_value: Value::Boolean(false),
+                value: Value::Boolean(true),
+            }))
+        );
+        assert_eq!(devices[DEVICE_ID].properties["on"].value_string(), "true");
+    }
+
+    #[test]
+    fn reduce_leaves_the_device_untouched_if_the_value_did_not_change() {
+        let mut devices = create_devices();
+        let device = devices[DEVICE_ID].clone();
+        let result = reduce_property_changed_event(&mut devices, DEVICE_ID, "on", |property: &mut BooleanProperty| property.set_value(false));
+
+        assert_eq!(result, Ok(None));
+        assert!(Arc::ptr_eq(&devices[DEVICE_ID], &device));
+    }
+
     #[test]
     fn reduce_returns_error_if_the_lambda_returns_an_error() {
         let mut devices = create_devices();
@@ -1,40 +1,98 @@
 use crate::domain::device::Device;
 use crate::domain::events::Event;
 use crate::domain::property::{BooleanProperty, ColorProperty, NumberProperty};
+use crate::flow_engine::Value;
 use crate::property_changed_reducer::reduce_property_changed_event;
+use chrono::{DateTime, Local};
 use std::collections::HashMap;
 use std::sync::Arc;
+use tokio::sync::broadcast::{Receiver as BroadcastReceiver, Sender as BroadcastSender};
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::watch;
 use tokio::sync::watch::{Receiver as WatchReceiver, Sender as WatchSender};
+use tokio::sync::{broadcast, watch};
 use tracing::{debug, info, instrument};
 
 pub type DeviceMap = HashMap<String, Arc<Device>>;
+pub type ChangeSet = Vec<PropertyChange>;
+pub type LastChangedMap = HashMap<(String, String), DateTime<Local>>; // By device and property id
 
+/// The state of the store, together with the property changes of the event that produced it.
 #[derive(Default, Clone, Debug)]
 pub struct StoreSnapshot {
     pub devices: Arc<DeviceMap>,
+    pub changes: Arc<ChangeSet>,
+    pub last_changed: Arc<LastChangedMap>,
 }
 
+impl StoreSnapshot {
+    /// Returns the same state without the changes, for flows that do not run in response to a store change.
+    pub fn without_changes(&self) -> StoreSnapshot {
+        StoreSnapshot {
+            devices: self.devices.clone(),
+            changes: Arc::default(),
+            last_changed: self.last_changed.clone(),
+        }
+    }
+
+    /// Returns when the property last changed, or when the store learned about it if it did not change since.
+    pub fn last_changed(&self, device_id: &str, property_id: &str) -> Option<DateTime<Local>> {
+        self.last_changed.get(&(device_id.to_string(), property_id.to_string())).copied()
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct PropertyChange {
+    pub device_id: String,
+    pub property_id: String,
+    pub previous_value: Value, // None for properties of newly discovered devices
+    pub value: Value,
+}
+
+/// A single change of the store, published on the change stream of the store in the order the changes were made.
+#[derive(Clone, Debug)]
+pub struct StoreChange {
+    pub kind: StoreChangeKind,
+    pub timestamp: DateTime<Local>,
+    pub event: Arc<Event>,       // The event that caused the change
+    pub snapshot: StoreSnapshot, // Right after the event, later events are not included
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum StoreChangeKind {
+    DeviceAdded { device_id: String },
+    PropertyChanged(PropertyChange),
+}
+
+const CHANGES_CAPACITY: usize = 1024;
+
 #[derive(Debug)]
 pub struct Store {
     devices: DeviceMap,
+    last_changed: LastChangedMap,
     rx: Receiver<Event>,
     notifier_tx: WatchSender<StoreSnapshot>,
     notifier_rx: WatchReceiver<StoreSnapshot>,
+    changes_tx: BroadcastSender<StoreChange>,
 }
 
 impl Store {
     pub fn new(rx: Receiver<Event>) -> Self {
         let devices = HashMap::new();
-        let snapshot = StoreSnapshot { devices: Arc::new(devices.clone()) };
+        let snapshot = StoreSnapshot {
+            devices: Arc::new(devices.clone()),
+            changes: Arc::default(),
+            last_changed: Arc::default(),
+        };
         let (notifier_tx, notifier_rx) = watch::channel::<StoreSnapshot>(snapshot);
+        let (changes_tx, _) = broadcast::channel::<StoreChange>(CHANGES_CAPACITY);
 
         Store {
             devices,
+            last_changed: HashMap::new(),
             rx,
             notifier_tx,
             notifier_rx,
+            changes_tx,
         }
     }
 
@@ -42,42 +100,325 @@ impl Store {
         self.notifier_rx.clone()
     }
 
+    /// Replaces the devices by the persisted devices of a previous run. Nothing is published on the change stream, as the
+    /// devices did not change in the meantime as far as the store knows.
+    pub fn restore(&mut self, devices: DeviceMap) {
+        self.devices = devices;
+        self.learned_about(&Local::now());
+        let snapshot = StoreSnapshot {
+            devices: Arc::new(self.devices.clone()),
+            changes: Arc::default(),
+            last_changed: Arc::new(self.last_changed.clone()),
+        };
+        self.notifier_tx.send(snapshot).unwrap_or_default();
+    }
+
+    /// Subscribes to the changes of the store, a subscriber only receives the changes made after subscribing.
+    pub fn changes(&self) -> BroadcastReceiver<StoreChange> {
+        self.changes_tx.subscribe()
+    }
+
     #[instrument(skip(self))]
     pub async fn listen(&mut self) {
         while let Some(event) = self.rx.recv().await {
-            debug!("🔵 Received event: {:?}", event);
-            match event {
-                Event::DiscoveredDevices(discovered_devices) => {
-                    let num_devices = discovered_devices.len();
-                    debug!("🔵 Registring {} new device(s)...", num_devices);
-                    self.devices.extend(discovered_devices.into_iter().map(|device| (device.id.clone(), Arc::new(device))));
-                    info!("🔵 Registring {} new device(s)... OK", num_devices);
-                }
-                Event::BooleanPropertyChanged { device_id, property_id, value } => {
-                    reduce_property_changed_event(&mut self.devices.clone(), &device_id, &property_id, |property: &mut BooleanProperty| {
-                        property.set_value(value)
-                    })
-                    .unwrap_or_default();
-                }
-                Event::NumberPropertyChanged { device_id, property_id, value } => {
-                    reduce_property_changed_event(&mut self.devices.clone(), &device_id.clone(), &property_id.clone(), move |property: &mut NumberProperty| {
-                        property.set_value(value)
-                    })
-                    .unwrap_or_default();
-                }
-                Event::ColorPropertyChanged { device_id, property_id, xy, gamut } => {
-                    reduce_property_changed_event(&mut self.devices.clone(), &device_id, &property_id, |property: &mut ColorProperty| {
-                        property.set_value(xy, gamut)
-                    })
-                    .unwrap_or_default();
-                }
+            self.handle(event);
+        }
+    }
+
+    /// Applies the event to the devices and publishes the new snapshot and its changes. Property events that do not
+    /// change a value publish nothing, discovered devices are always published as they replace the known devices.
+    fn handle(&mut self, event: Event) {
+        debug!("🔵 Received event: {:?}", event);
+        let timestamp = Local::now();
+        let event = Arc::new(event);
+        let mut added_device_ids = Vec::new();
+        let changes: ChangeSet = match event.as_ref() {
+            Event::DiscoveredDevices(discovered_devices) => {
+                let num_devices = discovered_devices.len();
+                debug!("🔵 Registring {} new device(s)...", num_devices);
+                added_device_ids = discovered_devices
+                    .iter()
+                    .filter(|device| !self.devices.contains_key(&device.id))
+                    .map(|device| device.id.clone())
+                    .collect();
+                let changes = discovered_devices.iter().flat_map(|device| self.discovery_changes(device)).collect();
+                self.devices.extend(discovered_devices.iter().map(|device| (device.id.clone(), Arc::new(device.clone()))));
+                info!("🔵 Registring {} new device(s)... OK", num_devices);
+                changes
+            }
+            Event::BooleanPropertyChanged { device_id, property_id, value } => {
+                reduce_property_changed_event(&mut self.devices, device_id, property_id, |property: &mut BooleanProperty| property.set_value(*value))
+                    .ok()
+                    .flatten()
+                    .into_iter()
+                    .collect()
+            }
+            Event::NumberPropertyChanged { device_id, property_id, value } => {
+                reduce_property_changed_event(&mut self.devices, device_id, property_id, |property: &mut NumberProperty| property.set_value(*value))
+                    .ok()
+                    .flatten()
+                    .into_iter()
+                    .collect()
             }
+            Event::ColorPropertyChanged { device_id, property_id, xy, gamut } => {
+                reduce_property_changed_event(&mut self.devices, device_id, property_id, |property: &mut ColorProperty| {
+                    property.set_value(xy.clone(), gamut.clone())
+                })
+                .ok()
+                .flatten()
+                .into_iter()
+                .collect()
+            }
+        };
+
+        if changes.is_empty() && !matches!(event.as_ref(), Event::DiscoveredDevices(_)) {
+            debug!("🔄 Store did not change");
+            return;
+        }
+
+        for change in &changes {
+            self.last_changed.insert((change.device_id.clone(), change.property_id.clone()), timestamp);
+        }
+        self.learned_about(&timestamp);
+
+        // The snapshot goes first, so subscribers of the changes always find the changed state in the snapshot
+        let snapshot = StoreSnapshot {
+            devices: Arc::new(self.devices.clone()),
+            changes: Arc::new(changes),
+            last_changed: Arc::new(self.last_changed.clone()),
+        };
+        self.notifier_tx.send(snapshot.clone()).unwrap_or_default();
+        self.publish_changes(&event, timestamp, added_device_ids, &snapshot);
+        info!("🔄 Updated store");
+    }
+
+    /// Sets the last changed timestamp of the properties the store did not know about yet.
+    fn learned_about(&mut self, timestamp: &DateTime<Local>) {
+        for device in self.devices.values() {
+            for property_id in device.properties.keys() {
+                self.last_changed.entry((device.id.clone(), property_id.clone())).or_insert(*timestamp);
+            }
+        }
+    }
 
-            let snapshot = StoreSnapshot {
-                devices: Arc::new(self.devices.clone()),
+    fn publish_changes(&self, event: &Arc<Event>, timestamp: DateTime<Local>, added_device_ids: Vec<String>, snapshot: &StoreSnapshot) {
+        let added_devices = added_device_ids.into_iter().map(|device_id| StoreChangeKind::DeviceAdded { device_id });
+        let changed_properties = snapshot.changes.iter().cloned().map(StoreChangeKind::PropertyChanged);
+
+        for kind in added_devices.chain(changed_properties) {
+            let change = StoreChange {
+                kind,
+                timestamp,
+                event: event.clone(),
+                snapshot: snapshot.clone(),
             };
-            self.notifier_tx.send(snapshot).unwrap_or_default();
-            info!("🔄 Updated store");
+            self.changes_tx.send(change).unwrap_or_default(); // Fails only if there are no subscribers
+        }
+    }
+
+    /// Returns a change for every property of a discovered device that differs from the known device, if any.
+    fn discovery_changes(&self, device: &Device) -> ChangeSet {
+        let known_device = self.devices.get(&device.id);
+        device
+            .properties
+            .iter()
+            .filter_map(|(property_id, property)| {
+                let previous_value = known_device
+                    .and_then(|known_device| known_device.properties.get(property_id))
+                    .map(|known_property| Value::try_from(known_property.as_ref()).unwrap_or(Value::None))
+                    .unwrap_or(Value::None);
+                let value = Value::try_from(property.as_ref()).unwrap_or(Value::None);
+
+                (known_device.is_none() || previous_value != value).then(|| PropertyChange {
+                    device_id: device.id.clone(),
+                    property_id: property_id.clone(),
+                    previous_value,
+                    value,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::device::DeviceType;
+    use crate::domain::property::{Property, PropertyType};
+    use pretty_assertions::assert_eq;
+    use tokio::sync::mpsc;
+
+    const DEVICE_ID: &str = "079e0321-7e18-46bc-bc16-fcbc3dd09e30";
+
+    fn device() -> Device {
+        let on_property: Box<dyn Property> = Box::new(BooleanProperty::new("on".to_string(), PropertyType::On, false, None, false));
+
+        Device {
+            id: DEVICE_ID.to_string(),
+            r#type: DeviceType::Light,
+            manufacturer: "Signify Netherlands B.V.".to_string(),
+            model_id: "LWA004".to_string(),
+            product_name: "Hue filament bulb".to_string(),
+            name: "Woonkamer".to_string(),
+            properties: HashMap::from([(on_property.name().to_string(), on_property)]),
+            external_id: None,
+            address: None,
+            controller_id: None,
+            room: None,
+            tags: Vec::new(),
         }
     }
+
+    fn switched_on() -> Event {
+        switched(true)
+    }
+
+    fn switched(value: bool) -> Event {
+        Event::BooleanPropertyChanged {
+            device_id: DEVICE_ID.to_string(),
+            property_id: "on".to_string(),
+            value,
+        }
+    }
+
+    fn store_with_device() -> (Store, WatchReceiver<StoreSnapshot>) {
+        let (_, rx) = mpsc::channel(1);
+        let mut store = Store::new(rx);
+        let mut notifier = store.notifier();
+        store.handle(Event::DiscoveredDevices(vec![device()]));
+        notifier.borrow_and_update();
+        (store, notifier)
+    }
+
+    fn on_value(snapshot: &StoreSnapshot) -> String {
+        snapshot.devices[DEVICE_ID].properties["on"].value_string()
+    }
+
+    fn property_change(previous_value: Value, value: Value) -> StoreChangeKind {
+        StoreChangeKind::PropertyChanged(PropertyChange {
+            device_id: DEVICE_ID.to_string(),
+            property_id: "on".to_string(),
+            previous_value,
+            value,
+        })
+    }
+
+    #[tokio::test]
+    async fn publishes_the_changes_of_each_event() {
+        let (tx, rx) = mpsc::channel(8);
+        let mut store = Store::new(rx);
+        let mut changes_rx = store.changes();
+        tokio::spawn(async move { store.listen().await });
+
+        tx.send(Event::DiscoveredDevices(vec![device()])).await.unwrap();
+        tx.send(switched_on()).await.unwrap();
+
+        let device_added = changes_rx.recv().await.unwrap();
+        assert_eq!(device_added.kind, StoreChangeKind::DeviceAdded { device_id: DEVICE_ID.to_string() });
+        assert_eq!(device_added.event.as_ref(), &Event::DiscoveredDevices(vec![device()]));
+
+        let discovered = changes_rx.recv().await.unwrap();
+        assert_eq!(discovered.kind, property_change(Value::None, Value::Boolean(false)));
+        assert!(Arc::ptr_eq(&discovered.event, &device_added.event));
+
+        let changed = changes_rx.recv().await.unwrap();
+        assert_eq!(changed.kind, property_change(Value::Boolean(false), Value::Boolean(true)));
+        assert_eq!(changed.event.as_ref(), &switched_on());
+        assert!(changed.timestamp >= discovered.timestamp);
+    }
+
+    #[test]
+    fn publishes_the_changes_with_the_state_right_after_their_event() {
+        let (mut store, _notifier) = store_with_device();
+        let mut changes_rx = store.changes();
+
+        store.handle(switched_on());
+        store.handle(switched(false));
+
+        assert_eq!(on_value(&changes_rx.try_recv().unwrap().snapshot), "true");
+        assert_eq!(on_value(&changes_rx.try_recv().unwrap().snapshot), "false");
+    }
+
+    #[test]
+    fn publishes_a_snapshot_with_the_changed_value() {
+        let (mut store, mut notifier) = store_with_device();
+
+        store.handle(switched_on());
+
+        assert!(notifier.has_changed().unwrap());
+        let snapshot = notifier.borrow_and_update().clone();
+        assert_eq!(on_value(&snapshot), "true");
+        assert_eq!(
+            snapshot.changes.as_ref(),
+            &vec![PropertyChange {
+                device_id: DEVICE_ID.to_string(),
+                property_id: "on".to_string(),
+                previous_value: Value::Boolean(false),
+                value: Value::Boolean(true),
+            }]
+        );
+
+        store.handle(switched(false));
+        assert_eq!(on_value(&notifier.borrow_and_update()), "false");
+    }
+
+    #[test]
+    fn does_not_publish_a_snapshot_if_the_value_did_not_change() {
+        let (mut store, notifier) = store_with_device();
+        let mut changes_rx = store.changes();
+
+        store.handle(switched(false));
+
+        assert!(!notifier.has_changed().unwrap());
+        assert!(changes_rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn tracks_when_a_property_last_changed() {
+        let (mut store, mut notifier) = store_with_device();
+        let discovered_at = notifier.borrow().last_changed(DEVICE_ID, "on").unwrap();
+
+        store.handle(switched(false));
+        assert_eq!(notifier.borrow().last_changed(DEVICE_ID, "on"), Some(discovered_at));
+
+        store.handle(switched_on());
+        let changed_at = notifier.borrow_and_update().last_changed(DEVICE_ID, "on").unwrap();
+        assert!(changed_at >= discovered_at);
+        assert_eq!(notifier.borrow().last_changed(DEVICE_ID, "unknown"), None);
+    }
+
+    #[test]
+    fn does_not_publish_a_snapshot_for_an_unknown_device() {
+        let (mut store, notifier) = store_with_device();
+
+        store.handle(Event::BooleanPropertyChanged {
+            device_id: "unknown".to_string(),
+            property_id: "on".to_string(),
+            value: true,
+        });
+
+        assert!(!notifier.has_changed().unwrap());
+        assert_eq!(on_value(&notifier.borrow()), "false");
+    }
+
+    #[test]
+    fn discovery_after_a_restore_only_publishes_the_changed_values() {
+        let (_, rx) = mpsc::channel(1);
+        let mut store = Store::new(rx);
+        let mut notifier = store.notifier();
+        let mut changes_rx = store.changes();
+
+        store.restore(HashMap::from([(DEVICE_ID.to_string(), Arc::new(device()))]));
+        assert_eq!(on_value(&notifier.borrow_and_update()), "false");
+        assert!(changes_rx.try_recv().is_err());
+
+        let mut discovered_device = device();
+        discovered_device
+            .properties
+            .insert("on".to_string(), Box::new(BooleanProperty::new("on".to_string(), PropertyType::On, false, None, true)));
+        store.handle(Event::DiscoveredDevices(vec![discovered_device]));
+
+        assert_eq!(changes_rx.try_recv().unwrap().kind, property_change(Value::Boolean(false), Value::Boolean(true)));
+        assert!(changes_rx.try_recv().is_err());
+    }
 }
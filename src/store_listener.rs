@@ -1,17 +1,268 @@
-use crate::domain::GeoLocation;
-use crate::execute_flows::execute_flows;
+use crate::domain::{Calendars, GeoLocation};
+use crate::execute_flows::{Cause, execute_flows};
 use crate::flow_registry::FlowRegistry;
 use crate::scheduler::SchedulerCommand;
-use crate::store::StoreSnapshot;
+use crate::store::{StoreChange, StoreChangeKind, StoreSnapshot};
 use std::sync::Arc;
+use tokio::sync::broadcast::Receiver as BroadcastReceiver;
+use tokio::sync::broadcast::error::{RecvError, TryRecvError};
 use tokio::sync::mpsc::Sender;
-use tokio::sync::watch::Receiver;
-use tracing::instrument;
+use tokio::sync::watch::Receiver as WatchReceiver;
+use tracing::{debug, instrument, warn};
 
 #[instrument(skip_all)]
-pub async fn store_listener(mut rx: Receiver<StoreSnapshot>, flow_registry: Arc<FlowRegistry>, scheduler_tx: Sender<SchedulerCommand>, geo_location: GeoLocation) {
-    while rx.changed().await.is_ok() {
-        let snapshot: StoreSnapshot = rx.borrow().clone();
-        execute_flows(flow_registry.reactive_flows(), snapshot, scheduler_tx.clone(), geo_location.clone()).await;
+pub async fn store_listener(
+    mut changes_rx: BroadcastReceiver<StoreChange>,
+    notifier_rx: WatchReceiver<StoreSnapshot>, // For runs that continue after a sleep
+    flow_registry: Arc<FlowRegistry>,
+    scheduler_tx: Sender<SchedulerCommand>,
+    geo_location: GeoLocation,
+    calendars: Arc<Calendars>,
+) {
+    let mut pending = None;
+    while let Some(batch) = next_batch(&mut changes_rx, &mut pending).await {
+        debug!(timestamp = %batch[0].timestamp, "🔄 Handling {} store change(s)...", batch.len());
+        let state = batch[0].snapshot.without_changes(); // Of the event of the batch, the store may have moved on
+        let changes = batch
+            .into_iter()
+            .filter_map(|change| match change.kind {
+                StoreChangeKind::PropertyChanged(property_change) => Some(property_change),
+                StoreChangeKind::DeviceAdded { .. } => None,
+            })
+            .collect();
+        let snapshot = StoreSnapshot { changes: Arc::new(changes), ..state };
+
+        let flows = flow_registry.reactive_flows_for(&snapshot.changes);
+        let reevaluations = flow_registry.timed_reevaluations(&snapshot.changes);
+        execute_flows(
+            flows,
+            Cause::StoreChange(snapshot),
+            notifier_rx.clone(),
+            scheduler_tx.clone(),
+            flow_registry.clone(),
+            geo_location.clone(),
+            calendars.clone(),
+        )
+        .await;
+
+        for (flow, delay) in reevaluations {
+            let command = SchedulerCommand::Reevaluate {
+                flow_id: flow.id().to_string(),
+                delay,
+            };
+            if scheduler_tx.send(command).await.is_err() {
+                warn!("⚠️ Unable to evaluate flow '{}' again, the scheduler stopped", flow.name());
+            }
+        }
+    }
+}
+
+/// Receives the changes caused by the next event, so the flows run once per event. A change of another event that is
+/// received in the meantime is kept as pending for the next batch. Returns `None` once the store is gone.
+async fn next_batch(rx: &mut BroadcastReceiver<StoreChange>, pending: &mut Option<StoreChange>) -> Option<Vec<StoreChange>> {
+    let first = match pending.take() {
+        Some(change) => change,
+        None => loop {
+            match rx.recv().await {
+                Ok(change) => break change,
+                Err(RecvError::Lagged(skipped)) => warn!("⚠️ Missed {} store change(s), flows fell behind", skipped),
+                Err(RecvError::Closed) => return None,
+            }
+        },
+    };
+
+    let mut batch = vec![first];
+    loop {
+        match rx.try_recv() {
+            Ok(change) if Arc::ptr_eq(&change.event, &batch[0].event) => batch.push(change),
+            Ok(change) => {
+                *pending = Some(change);
+                break;
+            }
+            Err(TryRecvError::Lagged(skipped)) => warn!("⚠️ Missed {} store change(s), flows fell behind", skipped),
+            Err(TryRecvError::Empty | TryRecvError::Closed) => break,
+        }
+    }
+    Some(batch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::commands::Command;
+    use crate::domain::controller::Controller;
+    use crate::domain::controller_registry;
+    use crate::domain::device::{Device, DeviceType};
+    use crate::domain::events::Event;
+    use crate::domain::property::{BooleanProperty, Property, PropertyType};
+    use crate::flow_engine::action::ControlDeviceAction;
+    use crate::flow_engine::expression::PropertyDurationExpression;
+    use crate::flow_engine::flow::{ActionFlowNode, Flow, FlowLink, FlowNode, FlowNodeKind, Trigger, TriggerKind, TriggerMode};
+    use crate::flow_engine::property_value::PropertyValue;
+    use crate::flow_engine::property_value::{Payload, PropertyValueExpression};
+    use crate::flow_engine::{Expression, Value, scheduler};
+    use crate::store::{PropertyChange, Store};
+    use async_trait::async_trait;
+    use chrono::Local;
+    use pretty_assertions::assert_eq;
+    use std::collections::HashMap;
+    use std::time::{Duration, Instant};
+    use tokio::sync::{broadcast, mpsc};
+
+    fn change(event: &Arc<Event>, property_id: &str) -> StoreChange {
+        StoreChange {
+            kind: StoreChangeKind::PropertyChanged(PropertyChange {
+                device_id: "device".to_string(),
+                property_id: property_id.to_string(),
+                previous_value: Value::None,
+                value: Value::Boolean(true),
+            }),
+            timestamp: Local::now(),
+            event: event.clone(),
+            snapshot: StoreSnapshot::default(),
+        }
+    }
+
+    fn property_ids(batch: Option<Vec<StoreChange>>) -> Vec<String> {
+        batch
+            .unwrap()
+            .into_iter()
+            .filter_map(|change| match change.kind {
+                StoreChangeKind::PropertyChanged(property_change) => Some(property_change.property_id),
+                StoreChangeKind::DeviceAdded { .. } => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn next_batch_groups_the_changes_by_event() {
+        let (tx, mut rx) = broadcast::channel(16);
+        let discovery = Arc::new(Event::DiscoveredDevices(vec![]));
+        let property_changed = Arc::new(Event::BooleanPropertyChanged {
+            device_id: "device".to_string(),
+            property_id: "on".to_string(),
+            value: true,
+        });
+        tx.send(change(&discovery, "on")).unwrap();
+        tx.send(change(&discovery, "brightness")).unwrap();
+        tx.send(change(&property_changed, "on")).unwrap();
+        drop(tx);
+
+        let mut pending = None;
+        assert_eq!(property_ids(next_batch(&mut rx, &mut pending).await), vec!["on", "brightness"]);
+        assert_eq!(property_ids(next_batch(&mut rx, &mut pending).await), vec!["on"]);
+        assert!(next_batch(&mut rx, &mut pending).await.is_none());
+    }
+
+    #[derive(Debug)]
+    struct RecordingController {
+        commands_tx: mpsc::UnboundedSender<Command>,
+    }
+
+    #[async_trait]
+    impl Controller for RecordingController {
+        fn id(&self) -> &'static str {
+            "recording"
+        }
+
+        async fn execute(&self, command: Command) {
+            let _ = self.commands_tx.send(command);
+        }
+    }
+
+    fn lamp() -> Device {
+        let on_property: Box<dyn Property> = Box::new(BooleanProperty::new("on".to_string(), PropertyType::On, false, None, false));
+        Device {
+            id: "lamp".to_string(),
+            r#type: DeviceType::Light,
+            manufacturer: "Signify Netherlands B.V.".to_string(),
+            model_id: "LWA004".to_string(),
+            product_name: "Hue filament bulb".to_string(),
+            name: "Lamp".to_string(),
+            properties: HashMap::from([("on".to_string(), on_property)]),
+            external_id: None,
+            address: None,
+            controller_id: Some("recording"),
+            room: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Turns off the lamp once it has been on for the duration.
+    fn turn_off_flow(duration: Duration) -> Flow {
+        let end_node = Arc::new(FlowNode::new("endNode".to_string(), vec![], FlowNodeKind::End));
+        let action = ControlDeviceAction::new(
+            "lamp".to_string(),
+            HashMap::from([("on".to_string(), PropertyValueExpression::SetBooleanValue(Payload::Literal(false)))]),
+        );
+        let turn_off_node = Arc::new(FlowNode::new(
+            "turnOffNode".to_string(),
+            vec![FlowLink::new(end_node, Value::None)],
+            FlowNodeKind::Action(ActionFlowNode::new(Box::new(action))),
+        ));
+        let start_node = Arc::new(FlowNode::new("startNode".to_string(), vec![FlowLink::new(turn_off_node, Value::None)], FlowNodeKind::Start));
+        let trigger = Expression::And {
+            lhs: Box::new(Expression::EqualTo {
+                lhs: Box::new(Expression::PropertyValue {
+                    device_id: "lamp".to_string(),
+                    property_id: "on".to_string(),
+                }),
+                rhs: Box::new(Expression::Literal { value: Value::Boolean(true) }),
+            }),
+            rhs: Box::new(Expression::UnchangedFor(PropertyDurationExpression {
+                device_id: "lamp".to_string(),
+                property_id: "on".to_string(),
+                duration,
+            })),
+        };
+
+        Flow::new("turnOff".to_string(), "turnOff".to_string(), start_node, HashMap::new())
+            .unwrap()
+            .with_triggers(vec![Trigger::new(
+                "expression".to_string(),
+                TriggerKind::Expression {
+                    expression: trigger,
+                    mode: TriggerMode::Edge,
+                },
+            )])
+    }
+
+    #[tokio::test]
+    async fn turns_off_a_light_once_it_has_been_on_for_the_duration_of_the_trigger() {
+        let (commands_tx, mut commands_rx) = mpsc::unbounded_channel();
+        controller_registry::register(Arc::new(RecordingController { commands_tx }));
+        let duration = Duration::from_millis(200);
+
+        let (events_tx, events_rx) = mpsc::channel(8);
+        let mut store = Store::new(events_rx);
+        let (changes_rx, notifier_rx) = (store.changes(), store.notifier());
+        let flow_registry = Arc::new(FlowRegistry::new(vec![turn_off_flow(duration)]));
+        let (scheduler_tx, scheduler_rx) = mpsc::channel(32);
+        tokio::spawn(scheduler(
+            scheduler_tx.clone(),
+            scheduler_rx,
+            notifier_rx.clone(),
+            flow_registry.clone(),
+            GeoLocation::default(),
+            Arc::default(),
+        ));
+        tokio::spawn(store_listener(changes_rx, notifier_rx, flow_registry, scheduler_tx, GeoLocation::default(), Arc::default()));
+        tokio::spawn(async move { store.listen().await });
+
+        events_tx.send(Event::DiscoveredDevices(vec![lamp()])).await.unwrap();
+        let switched_on = Instant::now();
+        events_tx
+            .send(Event::BooleanPropertyChanged {
+                device_id: "lamp".to_string(),
+                property_id: "on".to_string(),
+                value: true,
+            })
+            .await
+            .unwrap();
+
+        let Command::ControlDevice { device, property } = tokio::time::timeout(Duration::from_secs(2), commands_rx.recv()).await.unwrap().unwrap();
+        assert!(switched_on.elapsed() >= duration, "Expected the lamp to stay on for the duration");
+        assert_eq!(device.id, "lamp");
+        assert_eq!(property.as_ref(), &HashMap::from([("on".to_string(), PropertyValue::SetBooleanValue(false))]));
     }
 }